@@ -0,0 +1,78 @@
+//! Lexes generated sources of growing size and checks that the time spent per
+//! byte stays roughly flat. Run with `cargo bench --bench lexer`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use proto_rs::frontend::lexer::Lexer;
+
+const BASE_FUNCTIONS: usize = 2_000;
+const SCALES: [usize; 4] = [1, 2, 4, 8];
+const RUNS: usize = 5;
+
+/// Allowed growth of the per-byte cost between the smallest and the largest
+/// input before the lexer is considered super-linear.
+const MAX_SLOWDOWN: f64 = 2.0;
+
+fn generate_source(functions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..functions {
+        source.push_str(&format!(
+            "// función número {i} — ünïcödé comment\n\
+             /* block comment with 日本語 text */\n\
+             fn f{i}(a: i32, b: f64) -> i32 {{\n\
+             \tlet s = \"héllo wörld ✓ {i}\";\n\
+             \tlet c = 'λ';\n\
+             \tlet xs = [1, 2, 3];\n\
+             \tif a >= 10 {{ return a * 2 + xs[0]; }}\n\
+             \twhile a != 0 {{ a = a - 1; }}\n\
+             \treturn a % 3;\n\
+             }}\n"
+        ));
+    }
+    source
+}
+
+fn lex(source: &str) -> usize {
+    Lexer::new(source.to_string(), 0).count()
+}
+
+fn time_lexing(source: &str) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(lex(black_box(source)));
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let mut costs = vec![];
+
+    for scale in SCALES {
+        let source = generate_source(BASE_FUNCTIONS * scale);
+        let elapsed = time_lexing(&source);
+        let ns_per_byte = elapsed.as_nanos() as f64 / source.len() as f64;
+
+        println!(
+            "lexer/{scale}x: {} bytes, {} tokens, {:?} ({:.2} ns/byte)",
+            source.len(),
+            lex(&source),
+            elapsed,
+            ns_per_byte
+        );
+
+        costs.push(ns_per_byte);
+    }
+
+    let slowdown = costs[costs.len() - 1] / costs[0];
+    println!("lexer: per-byte cost grew {:.2}x from {}x to {}x input", slowdown, SCALES[0], SCALES[SCALES.len() - 1]);
+
+    assert!(
+        slowdown < MAX_SLOWDOWN,
+        "lexing time grows super-linearly with input size ({:.2}x per-byte slowdown)",
+        slowdown
+    );
+}
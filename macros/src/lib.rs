@@ -32,7 +32,19 @@ fn common_derive(item: TokenStream, trait_name: &str, returns: bool) -> TokenStr
     let trait_name = syn::Ident::new(trait_name, item_name.span());
     
     let return_value = if returns {
-        quote! { -> Value<'ctx> }
+        quote! { -> Result<Value<'ctx>, Diagnostic> }
+    } else {
+        quote! { -> Result<(), Diagnostic> }
+    };
+    
+    // Expressions are also visited by the type checker, which finds their
+    // type instead of a value.
+    let accept_type = if returns {
+        quote! {
+            fn accept_type(&self, visitor: &mut dyn ExprTypeVisitor<'ctx>) -> Result<Type, Diagnostic> {
+                visitor.#visit(self)
+            }
+        }
     } else {
         quote! {}
     };
@@ -42,6 +54,12 @@ fn common_derive(item: TokenStream, trait_name: &str, returns: bool) -> TokenStr
             fn accept(&self, visitor: &mut dyn #visitor<'ctx>) #return_value {
                 visitor.#visit(self)
             }
+
+            #accept_type
+
+            fn span(&self) -> Span {
+                self.span
+            }
         }
     
         impl<'ctx> #item_name<#lifetime> {
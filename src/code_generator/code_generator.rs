@@ -1,36 +1,70 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
-use inkwell::FloatPredicate;
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate};
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::basic_block::BasicBlock;
 use inkwell::context::Context;
-use inkwell::module::Module;
-use inkwell::types::{BasicMetadataTypeEnum, BasicTypeEnum};
-use inkwell::values::{FloatValue, IntValue, BasicMetadataValueEnum, ArrayValue};
+use inkwell::intrinsics::Intrinsic;
+use inkwell::module::{Linkage, Module};
+use inkwell::passes::PassManager;
+use inkwell::targets::TargetData;
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, StructType};
+use inkwell::values::{FloatValue, FunctionValue, IntValue, BasicMetadataValueEnum, ArrayValue, PointerValue, BasicValue};
 use inkwell::{builder::Builder, values::BasicValueEnum};
 use crate::code_generator::CodeGenerator;
-use crate::frontend::expr::{BinaryExpr, LiteralExpr, UnaryExpr, VariableExpr, VarAssignExpr, CallExpr, ListExpr, IndexExpr};
-use crate::frontend::stmt::{Stmt, ExprStmt, VarDeclStmt, ReturnStmt, BlockStmt, IfStmt, WhileStmt, BreakStmt, ContinueStmt, FunctionDeclStmt, FunctionDefStmt};
-use crate::frontend::type_::{Type, LiteralType, self};
-use crate::frontend::value::{self, Value, IntegerValue, FloatingValue};
+use crate::compile_time_evaluator::CompileTimeEvaluator;
+use crate::diagnostic::Diagnostic;
+use crate::frontend::expr::{Expr, BinaryExpr, LogicalExpr, LiteralExpr, UnaryExpr, VariableExpr, AssignExpr, LValue, CallExpr, ListExpr, RepeatExpr, IndexExpr, FieldExpr, StructExpr, VariantExpr, MatchExpr, LoopExpr, BlockExpr, IfExpr, StringExpr, CastExpr, AddressOfExpr, DerefExpr, SliceExpr, ArmBody, Pattern};
+use crate::frontend::stmt::{Stmt, ExprStmt, VarDeclStmt, ConstDeclStmt, StaticDeclStmt, ReturnStmt, BlockStmt, IfStmt, WhileStmt, ForStmt, ForIterable, BreakStmt, ContinueStmt, FunctionDeclStmt, FunctionDefStmt, StructDeclStmt, EnumDeclStmt};
+use crate::frontend::type_::{Type, LiteralType, IntType, PointerKind, self};
+use crate::frontend::source_map::SourceMap;
+use crate::frontend::span::Span;
+use crate::frontend::value::{self, Value, LiteralValue, IntegerValue, FloatingValue};
 use crate::frontend::visitor::{StmtVisitor, ExprVisitor};
-use crate::frontend::token::TokenKind;
+use crate::frontend::token::{Token, TokenKind};
 
-use super::{VariableInfo, FunctionInfo};
+use super::{VariableInfo, FunctionInfo, StructInfo, EnumInfo, LoopInfo};
 
 impl<'ctx> CodeGenerator<'ctx> {
-    pub fn new(context: &'ctx Context, module: Module<'ctx>, builder: Builder<'ctx>) -> CodeGenerator<'ctx> {
+    pub fn new(context: &'ctx Context, module: Module<'ctx>, builder: Builder<'ctx>, source_map: SourceMap, bounds_checks: bool) -> CodeGenerator<'ctx> {
+        // The layout of the target decides how large the payload of an enum
+        // has to be.
+        let target_data = TargetData::create(module.get_data_layout().as_str().to_str().unwrap());
+
+        let pass_manager = PassManager::create(&module);
+        pass_manager.add_scalar_repl_aggregates_pass();
+        pass_manager.add_promote_memory_to_register_pass();
+        pass_manager.initialize();
+
         CodeGenerator {
             context,
             module,
             builder,
             symbol_table: vec![HashMap::new()],
             function_table: HashMap::new(),
-            break_block_stack: vec![],
-            continue_block_stack: vec![],
+            struct_table: HashMap::new(),
+            enum_table: HashMap::new(),
+            target_data,
+            source_map,
+            bounds_checks,
+            pass_manager,
+            expected_type: None,
+            loop_stack: vec![],
         }
     }
 
-    pub fn generate_code(&mut self, stmt: &dyn Stmt<'ctx>) {
-        stmt.accept(self)
+    pub fn generate_code(&mut self, stmt: &dyn Stmt<'ctx>) -> Result<(), Diagnostic> {
+        self.generate_stmt(stmt)
+    }
+
+    /// Generates code for `stmt`, pointing any diagnostic that lacks a more
+    /// precise location at the statement itself.
+    fn generate_stmt(&mut self, stmt: &dyn Stmt<'ctx>) -> Result<(), Diagnostic> {
+        stmt.accept(self).map_err(|diagnostic| match diagnostic.span {
+            Some(_) => diagnostic,
+            None => diagnostic.with_span(stmt.span()),
+        })
     }
 
     pub fn get_module(&self) -> &Module<'ctx> {
@@ -45,35 +79,706 @@ impl<'ctx> CodeGenerator<'ctx> {
         self.symbol_table.pop();
     }
 
-    fn get_type(&self, type_: &Type) -> BasicTypeEnum<'ctx> {
+    /// Allocates a stack slot at the start of the entry block of the current
+    /// function, wherever the code being generated is. A slot allocated in a
+    /// loop would otherwise take more of the stack on every iteration, and
+    /// only the slots in the entry block are promoted to SSA values.
+    fn build_entry_alloca<T: BasicType<'ctx>>(&self, type_: T, name: &str) -> PointerValue<'ctx> {
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let entry_block = function.get_first_basic_block().unwrap();
+
+        let builder = self.context.create_builder();
+        match entry_block.get_first_instruction() {
+            Some(instruction) => builder.position_before(&instruction),
+            None => builder.position_at_end(entry_block),
+        }
+
+        builder.build_alloca(type_, name)
+    }
+
+    fn get_type(&self, type_: &Type) -> Result<BasicTypeEnum<'ctx>, Diagnostic> {
+        match type_ {
+            Type::Literal(LiteralType::Bool) => Ok(self.context.bool_type().into()),
+            Type::Literal(LiteralType::Char) |
+            Type::Literal(LiteralType::Int(IntType::U8)) |
+            Type::Literal(LiteralType::Int(IntType::I8)) => Ok(self.context.i8_type().into()),
+            Type::Literal(LiteralType::Int(IntType::U16)) |
+            Type::Literal(LiteralType::Int(IntType::I16)) => Ok(self.context.i16_type().into()),
+            Type::Literal(LiteralType::Int(IntType::U32)) |
+            Type::Literal(LiteralType::Int(IntType::I32)) => Ok(self.context.i32_type().into()),
+            Type::Literal(LiteralType::Int(IntType::U64)) |
+            Type::Literal(LiteralType::Int(IntType::I64)) => Ok(self.context.i64_type().into()),
+            Type::Literal(LiteralType::Float(type_::FloatType::F32)) => Ok(self.context.f32_type().into()),
+            Type::Literal(LiteralType::Float(type_::FloatType::F64)) => Ok(self.context.f64_type().into()),
+            Type::List(element, len) => Ok(self.get_type(element)?.array_type(*len as u32).into()),
+            Type::Slice(element, _) => Ok(self.get_slice_type(element)?.into()),
+            Type::Str => Ok(self.get_str_type().into()),
+            Type::Named(name) => match self.struct_table.get(name) {
+                Some(struct_info) => Ok(struct_info.llvm_type.into()),
+                None => Ok(self.enum_table[name].llvm_type.into()),
+            },
+            Type::Pointer(pointee, _) => Ok(self.get_type(pointee)?.ptr_type(AddressSpace::default()).into()),
+            _ => Err(Diagnostic::error(format!("unsupported type `{}`", type_))),
+        }
+    }
+
+    /// A `str` is a pointer to its first byte followed by the count of its
+    /// bytes. Passed by value, the two travel in separate registers, so that
+    /// a `str` parameter of an extern function lines up with the `const char
+    /// *buf, size_t count` pair of C functions such as `write`.
+    fn get_str_type(&self) -> StructType<'ctx> {
+        let byte_pointer = self.context.i8_type().ptr_type(AddressSpace::default());
+        self.context.struct_type(&[byte_pointer.into(), self.context.i64_type().into()], false)
+    }
+
+    /// A slice is laid out like a `str`: a pointer to its first element
+    /// followed by the count of its elements.
+    fn get_slice_type(&self, element: &Type) -> Result<StructType<'ctx>, Diagnostic> {
+        let element_pointer = self.get_type(element)?.ptr_type(AddressSpace::default());
+        Ok(self.context.struct_type(&[element_pointer.into(), self.context.i64_type().into()], false))
+    }
+
+    /// The LLVM struct holding the fields of a variant in the payload of its
+    /// enum.
+    fn get_payload_type(&self, fields: &[Type]) -> Result<StructType<'ctx>, Diagnostic> {
+        let fields = fields.iter()
+            .map(|field| self.get_type(field))
+            .collect::<Result<Vec<BasicTypeEnum>, Diagnostic>>()?;
+
+        Ok(self.context.struct_type(&fields, false))
+    }
+
+    /// Returns the address of the payload of the enum stored at `address`,
+    /// viewed as the fields of the variant `variant`.
+    fn payload_address(&self, enum_name: &str, variant: usize, address: PointerValue<'ctx>) -> Result<(PointerValue<'ctx>, StructType<'ctx>), Diagnostic> {
+        let enum_info = &self.enum_table[enum_name];
+        let payload_type = self.get_payload_type(&enum_info.variants[variant].1)?;
+
+        let payload = self.builder.build_struct_gep(enum_info.llvm_type, address, 1, "payload").unwrap();
+        let payload = self.builder.build_pointer_cast(payload, payload_type.ptr_type(AddressSpace::default()), "payload");
+
+        Ok((payload, payload_type))
+    }
+
+    fn get_int_type(&self, type_: IntType) -> inkwell::types::IntType<'ctx> {
+        self.context.custom_width_int_type(type_.bit_width())
+    }
+
+    /// Evaluates `expr` as a value of type `type_`. Constant expressions are
+    /// built directly in `type_`; the type checker has made sure any other
+    /// expression already has it.
+    fn build_initializer(&mut self, expr: &dyn Expr<'ctx>, type_: &Type) -> Result<Value<'ctx>, Diagnostic> {
+        if let Ok(constant) = expr.accept(&mut CompileTimeEvaluator::new()) {
+            return Ok(Value::new(self.build_constant(&constant, type_, expr.span())?, type_.clone()));
+        }
+
+        self.expected_type = Some(type_.clone());
+        let value = expr.accept(self);
+        self.expected_type = None;
+
+        Ok(Value::new(value?.as_llvm_basic_value_enum(), type_.clone()))
+    }
+
+    /// Builds a constant folded without a type to convert it to in the type
+    /// it has on its own, as the type checker does.
+    fn build_folded(&self, value: &Value<'ctx>, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+        let type_ = value.get_type();
+        Ok(Value::new(self.build_constant(value, &type_, span)?, type_))
+    }
+
+    /// Builds the compile-time value `value` as a constant of type `type_`,
+    /// which the type checker has made sure `value` fits into.
+    fn build_constant(&self, value: &Value<'ctx>, type_: &Type, span: Span) -> Result<BasicValueEnum<'ctx>, Diagnostic> {
+        match (value, type_) {
+            (Value::Literal(LiteralValue::Bool(value)), _) => Ok(self.context.bool_type().const_int(*value as u64, false).into()),
+            (Value::Literal(LiteralValue::Char(value)), _) => Ok(self.context.i8_type().const_int(*value as u64, false).into()),
+            (Value::Literal(LiteralValue::Int(value)), Type::Literal(LiteralType::Int(int_type))) => {
+                Ok(self.get_int_type(*int_type).const_int(value.as_i128() as u64, int_type.is_signed()).into())
+            }
+            (Value::Literal(LiteralValue::Int(value)), Type::Literal(LiteralType::Float(_))) => {
+                Ok(self.get_type(type_)?.into_float_type().const_float(value.as_i128() as f64).into())
+            }
+            (Value::Literal(LiteralValue::Float(value)), Type::Literal(LiteralType::Float(_))) => {
+                Ok(self.get_type(type_)?.into_float_type().const_float(value.as_f64()).into())
+            }
+            (Value::List(values), Type::List(_, _)) if values.is_empty() => Ok(self.get_type(type_)?.const_zero()),
+            (Value::List(values), Type::List(element, _)) => {
+                let values = values.iter()
+                    .map(|value| self.build_constant(value, element, span))
+                    .collect::<Result<Vec<BasicValueEnum>, Diagnostic>>()?;
+
+                match self.get_type(element)? {
+                    BasicTypeEnum::IntType(element) => Ok(element.const_array(&values.iter().map(|value| value.into_int_value()).collect::<Vec<IntValue>>()).into()),
+                    BasicTypeEnum::FloatType(element) => Ok(element.const_array(&values.iter().map(|value| value.into_float_value()).collect::<Vec<FloatValue>>()).into()),
+                    BasicTypeEnum::ArrayType(element) => Ok(element.const_array(&values.iter().map(|value| value.into_array_value()).collect::<Vec<ArrayValue>>()).into()),
+                    element => Err(Diagnostic::error(format!("unsupported list element type `{}`", element)).with_span(span)),
+                }
+            }
+            _ => Err(Diagnostic::error(format!("cannot build a constant of type `{}`", type_)).with_span(span)),
+        }
+    }
+
+    /// Applies the binary operator `op` to two already evaluated operands.
+    fn build_binary(&mut self, left: Value<'ctx>, right: Value<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+        let left_type = left.get_type();
+        let right_type = right.get_type();
+
+        if left_type == Type::Literal(LiteralType::Bool) && right_type == Type::Literal(LiteralType::Bool) {
+            let left = left.as_llvm_basic_value_enum().into_int_value();
+            let right = right.as_llvm_basic_value_enum().into_int_value();
+            return self.build_bool_binary(left, right, op, span);
+        }
+
+        match (left_type.numeric(), right_type.numeric()) {
+            (Some(LiteralType::Int(left_type)), Some(LiteralType::Int(right_type))) => {
+                self.visit_binary_expr_int_int(Self::integer(left, left_type), Self::integer(right, right_type), op, span)
+            }
+            (Some(LiteralType::Int(left_type)), Some(LiteralType::Float(right_type))) => {
+                self.visit_binary_expr_int_float(Self::integer(left, left_type), Self::floating(right, right_type), op, span)
+            }
+            (Some(LiteralType::Float(left_type)), Some(LiteralType::Int(right_type))) => {
+                self.visit_binary_expr_float_int(Self::floating(left, left_type), Self::integer(right, right_type), op, span)
+            }
+            (Some(LiteralType::Float(left_type)), Some(LiteralType::Float(right_type))) => {
+                self.visit_binary_expr_float_float(Self::floating(left, left_type), Self::floating(right, right_type), op, span)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Evaluates an operand of a binary operator. A number known at compile
+    /// time is kept as one, so that the operands are brought to the type the
+    /// type checker chose, which only counts such numbers as constants. LLVM
+    /// folds more than that, such as the length of a list.
+    fn build_operand(&mut self, expr: &dyn Expr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        match expr.accept(&mut CompileTimeEvaluator::new()) {
+            Ok(constant @ Value::Literal(LiteralValue::Int(_) | LiteralValue::Char(_) | LiteralValue::Float(_))) => Ok(constant),
+            _ => expr.accept(self),
+        }
+    }
+
+    /// An integer operand of type `type_`, which a `char` is as its `u8` code.
+    fn integer(operand: Value<'ctx>, type_: IntType) -> IntegerValue<'ctx> {
+        match operand {
+            Value::Literal(LiteralValue::Int(constant)) => IntegerValue::Int(constant),
+            Value::Literal(LiteralValue::Char(constant)) => IntegerValue::Int(value::IntValue::U8(constant as u8)),
+            operand => IntegerValue::LLVMInt(operand.as_llvm_basic_value_enum().into_int_value(), type_),
+        }
+    }
+
+    fn floating(operand: Value<'ctx>, type_: type_::FloatType) -> FloatingValue<'ctx> {
+        match operand {
+            Value::Literal(LiteralValue::Float(constant)) => FloatingValue::Float(constant),
+            operand => FloatingValue::LLVMFloat(operand.as_llvm_basic_value_enum().into_float_value(), type_),
+        }
+    }
+
+    fn build_bool_binary(&mut self, left: IntValue<'ctx>, right: IntValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+        let value = match op.kind {
+            TokenKind::Ampersand => self.builder.build_and(left, right, "and"),
+            TokenKind::Pipe => self.builder.build_or(left, right, "or"),
+            TokenKind::Caret | TokenKind::NotEqual => self.builder.build_xor(left, right, "xor"),
+            TokenKind::Equal => self.builder.build_int_compare(IntPredicate::EQ, left, right, "eq"),
+            _ => return Err(Diagnostic::error(format!("unexpected operator {}", op.kind)).with_span(span)),
+        };
+
+        Ok(Value::new(value, Type::Literal(LiteralType::Bool)))
+    }
+
+    /// Brings two integer operands to a common type, the same one the type
+    /// checker found. An operand known at compile time takes the type of the
+    /// other one if its value fits there; otherwise the narrower operand is
+    /// extended.
+    fn unify_ints(&self, left: IntegerValue<'ctx>, right: IntegerValue<'ctx>) -> (IntValue<'ctx>, IntValue<'ctx>, IntType) {
+        let fits = |operand: &IntegerValue, type_: IntType| {
+            matches!(operand, IntegerValue::Int(constant) if value::IntValue::fits(constant.as_i128(), type_))
+        };
+
+        let (left_type, right_type) = (left.get_type(), right.get_type());
+        let type_ = if left_type == right_type || fits(&right, left_type) {
+            left_type
+        } else if fits(&left, right_type) {
+            right_type
+        } else if left_type.bit_width() > right_type.bit_width() {
+            left_type
+        } else {
+            right_type
+        };
+
+        (self.build_integer(left, type_), self.build_integer(right, type_), type_)
+    }
+
+    /// Builds the integer operand `operand` as a value of type `type_`, which
+    /// is at least as wide as its own type.
+    fn build_integer(&self, operand: IntegerValue<'ctx>, type_: IntType) -> IntValue<'ctx> {
+        match operand {
+            IntegerValue::Int(constant) => self.get_int_type(type_).const_int(constant.as_i128() as u64, type_.is_signed()),
+            IntegerValue::LLVMInt(operand, from) => self.build_int_extend(operand, from, type_),
+        }
+    }
+
+    /// Widens `value` from `from` to `to`, sign extending signed values and
+    /// zero extending unsigned ones.
+    fn build_int_extend(&self, value: IntValue<'ctx>, from: IntType, to: IntType) -> IntValue<'ctx> {
+        if from.bit_width() == to.bit_width() {
+            return value;
+        }
+
+        let type_ = self.get_int_type(to);
+        if from.is_signed() {
+            self.builder.build_int_s_extend(value, type_, "s_extend")
+        } else {
+            self.builder.build_int_z_extend(value, type_, "z_extend")
+        }
+    }
+
+    /// Divides `left` by `right`, or takes the remainder of the division. A
+    /// zero divisor, or a signed division overflowing as `-128 / -1` does for
+    /// `i8`, traps when it happens instead of being undefined behavior.
+    fn build_int_division(&self, left: IntValue<'ctx>, right: IntValue<'ctx>, kind: &TokenKind, signed: bool) -> IntValue<'ctx> {
+        let type_ = right.get_type();
+        let mut fails = self.builder.build_int_compare(IntPredicate::EQ, right, type_.const_zero(), "is_zero");
+
+        if signed {
+            let min = type_.const_int(1 << (type_.get_bit_width() - 1), false);
+            let is_min = self.builder.build_int_compare(IntPredicate::EQ, left, min, "is_min");
+            let is_minus_one = self.builder.build_int_compare(IntPredicate::EQ, right, type_.const_all_ones(), "is_minus_one");
+            let overflows = self.builder.build_and(is_min, is_minus_one, "overflows");
+            fails = self.builder.build_or(fails, overflows, "fails");
+        }
+
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let trap_block = self.context.append_basic_block(function, "division_trap");
+        let continue_block = self.context.append_basic_block(function, "division");
+
+        self.builder.build_conditional_branch(fails, trap_block, continue_block);
+
+        self.builder.position_at_end(trap_block);
+        let trap = Intrinsic::find("llvm.trap").unwrap().get_declaration(&self.module, &[]).unwrap();
+        self.builder.build_call(trap, &[], "trap");
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(continue_block);
+
+        match (kind, signed) {
+            (TokenKind::Slash, true) => self.builder.build_int_signed_div(left, right, "div"),
+            (TokenKind::Slash, false) => self.builder.build_int_unsigned_div(left, right, "div"),
+            (_, true) => self.builder.build_int_signed_rem(left, right, "rem"),
+            (_, false) => self.builder.build_int_unsigned_rem(left, right, "rem"),
+        }
+    }
+
+    fn build_int_to_float(&self, value: IntValue<'ctx>, signed: bool, type_: inkwell::types::FloatType<'ctx>) -> FloatValue<'ctx> {
+        if signed {
+            self.builder.build_signed_int_to_float(value, type_, "int_to_float")
+        } else {
+            self.builder.build_unsigned_int_to_float(value, type_, "int_to_float")
+        }
+    }
+
+    /// Converts an integer operand meeting a floating-point one of type
+    /// `type_`. An integer known at compile time stays one, which the type
+    /// checker has made sure `type_` holds exactly.
+    fn build_int_operand_to_float(&self, operand: IntegerValue<'ctx>, type_: type_::FloatType) -> Result<FloatingValue<'ctx>, Diagnostic> {
+        match operand {
+            IntegerValue::Int(constant) => Ok(FloatingValue::Float(value::FloatValue::from_int(constant, type_))),
+            IntegerValue::LLVMInt(operand, from) => {
+                let llvm_type = self.get_type(&Type::Literal(LiteralType::Float(type_)))?.into_float_type();
+                Ok(FloatingValue::LLVMFloat(self.build_int_to_float(operand, from.is_signed(), llvm_type), type_))
+            }
+        }
+    }
+
+    /// Emits a `const` or `static` as a global variable initialized with its
+    /// value, which the type checker made sure is known at compile time. The
+    /// global is internal, so that its name cannot clash with a symbol of the
+    /// C library the program is linked against.
+    fn generate_global(&mut self, constant: bool, name: &str, type_: &Type, expr: &dyn Expr<'ctx>) -> Result<(), Diagnostic> {
+        let value = expr.accept(&mut CompileTimeEvaluator::new())?;
+        let initializer = self.build_constant(&value, type_, expr.span())?;
+
+        let global = self.module.add_global(self.get_type(type_)?, None, name);
+        global.set_initializer(&initializer);
+        global.set_constant(constant);
+        global.set_linkage(Linkage::Internal);
+
+        let variable_info = VariableInfo {
+            type_: type_.clone(),
+            alloca: global.as_pointer_value(),
+        };
+        if let Some(scope) = self.symbol_table.last_mut() {
+            scope.insert(name.to_owned(), variable_info);
+        }
+
+        Ok(())
+    }
+
+    /// Truncates `value` to `type_`, or widens it by extending its sign when
+    /// `signed` and with zeros otherwise.
+    fn build_int_cast(&self, value: IntValue<'ctx>, signed: bool, type_: IntType) -> IntValue<'ctx> {
+        let llvm_type = self.get_int_type(type_);
+        match value.get_type().get_bit_width().cmp(&type_.bit_width()) {
+            Ordering::Greater => self.builder.build_int_truncate(value, llvm_type, "truncate"),
+            Ordering::Less if signed => self.builder.build_int_s_extend(value, llvm_type, "s_extend"),
+            Ordering::Less => self.builder.build_int_z_extend(value, llvm_type, "z_extend"),
+            Ordering::Equal => value,
+        }
+    }
+
+    /// Converts `value` to `type_`, rounding towards zero. Values out of the
+    /// range of `type_` saturate and NaN becomes zero, as they do when a cast
+    /// is folded at compile time, instead of producing a poison value.
+    fn build_float_to_int(&self, value: FloatValue<'ctx>, type_: IntType) -> IntValue<'ctx> {
+        let name = if type_.is_signed() { "llvm.fptosi.sat" } else { "llvm.fptoui.sat" };
+        let int_type = self.get_int_type(type_);
+        let function = Intrinsic::find(name).unwrap()
+            .get_declaration(&self.module, &[int_type.into(), value.get_type().into()])
+            .unwrap();
+
+        self.builder.build_call(function, &[value.into()], "float_to_int")
+            .try_as_basic_value().left().unwrap()
+            .into_int_value()
+    }
+
+    /// Returns the address of an assignable place together with the type of
+    /// the value stored there.
+    fn lvalue_address(&mut self, lvalue: &LValue<'ctx>) -> Result<(PointerValue<'ctx>, Type), Diagnostic> {
+        match lvalue {
+            LValue::Variable(variable) => {
+                let variable_info = self.get_variable_info(&variable.name).unwrap();
+                Ok((variable_info.alloca, variable_info.type_.clone()))
+            }
+            LValue::Index(index) => self.index_address(index),
+            LValue::Field(field) => self.field_address(field),
+            LValue::Deref(deref) => self.deref_address(deref),
+            LValue::Slice(_) | LValue::Value(_) => {
+                // A slice or the result of a call is a value made up on the
+                // spot, so it gets a place of its own.
+                self.expected_type = None;
+                let value = lvalue.as_expr().accept(self)?;
+                let alloca = self.build_entry_alloca(self.get_type(&value.get_type())?, "slice");
+                self.builder.build_store(alloca, value.as_llvm_basic_value_enum());
+                Ok((alloca, value.get_type()))
+            }
+        }
+    }
+
+    /// The address a pointer points to, which is the value of the pointer.
+    fn deref_address(&mut self, expr: &DerefExpr<'ctx>) -> Result<(PointerValue<'ctx>, Type), Diagnostic> {
+        self.expected_type = None;
+        let pointer = expr.pointer.accept(self)?;
+
+        match pointer.get_type() {
+            Type::Pointer(pointee, _) => Ok((pointer.as_llvm_basic_value_enum().into_pointer_value(), *pointee)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the address of the place `base` is part of. A pointer is
+    /// looked through to the place it points to, so that a list or a struct
+    /// can be used through a pointer to it as if it were at hand.
+    fn base_address(&mut self, base: &LValue<'ctx>) -> Result<(PointerValue<'ctx>, Type), Diagnostic> {
+        let (mut address, mut type_) = self.lvalue_address(base)?;
+
+        while let Type::Pointer(pointee, _) = type_ {
+            let pointer_type = self.get_type(&Type::Pointer(pointee.clone(), PointerKind::Raw))?;
+            address = self.builder.build_load(pointer_type, address, "deref").into_pointer_value();
+            type_ = *pointee;
+        }
+
+        Ok((address, type_))
+    }
+
+    /// Returns a pointer to the first element of the list or slice stored at
+    /// `address`, together with the count of its elements and their type.
+    fn slice_parts(&self, address: PointerValue<'ctx>, type_: &Type) -> Result<(PointerValue<'ctx>, IntValue<'ctx>, Type), Diagnostic> {
+        let i64_type = self.context.i64_type();
+
         match type_ {
-            Type::Literal(LiteralType::Int(type_::IntType::U8)) |
-            Type::Literal(LiteralType::Int(type_::IntType::I8)) => self.context.i8_type().into(),
-            Type::Literal(LiteralType::Int(type_::IntType::U16)) |
-            Type::Literal(LiteralType::Int(type_::IntType::I16)) => self.context.i16_type().into(),
-            Type::Literal(LiteralType::Int(type_::IntType::U32)) |
-            Type::Literal(LiteralType::Int(type_::IntType::I32)) => self.context.i32_type().into(),
-            Type::Literal(LiteralType::Int(type_::IntType::U64)) |
-            Type::Literal(LiteralType::Int(type_::IntType::I64)) => self.context.i64_type().into(),
-            Type::Literal(LiteralType::Float(type_::FloatType::F32)) => self.context.f32_type().into(),
-            Type::Literal(LiteralType::Float(type_::FloatType::F64)) => self.context.f64_type().into(),
-            _ => panic!("Unknown type {:?}", type_),
-        }
-    }
-
-    fn extract_string(&self, string: &str) -> String { 
-        if string.starts_with("\"") && string.ends_with("\"") {
-            string[1..string.len() - 1].to_owned()
+            Type::List(element, len) => {
+                let zero = i64_type.const_zero();
+                let first = unsafe {
+                    self.builder.build_gep(self.get_type(type_)?, address, &[zero, zero], "first")
+                };
+                Ok((first, i64_type.const_int(*len as u64, false), element.as_ref().clone()))
+            }
+            Type::Slice(element, _) => {
+                let slice_type = self.get_slice_type(element)?;
+                let pointer_type = self.get_type(&Type::Pointer(element.clone(), PointerKind::Raw))?;
+
+                let first = self.builder.build_struct_gep(slice_type, address, 0, "first").unwrap();
+                let first = self.builder.build_load(pointer_type, first, "first").into_pointer_value();
+                let len = self.builder.build_struct_gep(slice_type, address, 1, "len").unwrap();
+                let len = self.builder.build_load(i64_type, len, "len").into_int_value();
+
+                Ok((first, len, element.as_ref().clone()))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Evaluates an index into a list or a slice as an `i64`. Sign extending
+    /// a signed index makes a negative one fail the unsigned bounds check.
+    fn build_index(&mut self, expr: &dyn Expr<'ctx>) -> Result<IntValue<'ctx>, Diagnostic> {
+        let index = expr.accept(self)?;
+        let index_type = match index.get_type() {
+            Type::Literal(LiteralType::Int(index_type)) => index_type,
+            _ => unreachable!(),
+        };
+
+        let i64_type = self.context.i64_type();
+        let index = index.as_llvm_basic_value_enum().into_int_value();
+        Ok(if index_type.is_signed() {
+            self.builder.build_int_s_extend_or_bit_cast(index, i64_type, "index")
+        } else {
+            self.builder.build_int_z_extend_or_bit_cast(index, i64_type, "index")
+        })
+    }
+
+    /// Panics when `in_bounds` is false, with `message` formatted with the
+    /// two `values` as `printf` would. Nothing is checked when bounds checks
+    /// are turned off.
+    fn build_bounds_check(&mut self, in_bounds: IntValue<'ctx>, message: &str, values: [IntValue<'ctx>; 2], span: Span) -> Result<(), Diagnostic> {
+        if !self.bounds_checks {
+            return Ok(());
+        }
+
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let continue_block = self.context.append_basic_block(function, "continue");
+        let panic_block = self.context.append_basic_block(function, "panic");
+
+        self.builder.build_conditional_branch(in_bounds, continue_block, panic_block);
+
+        self.builder.position_at_end(panic_block);
+        let file = self.source_map.file(span.file_id);
+        let (line, column) = file.line_column(span.start);
+        let location = self.builder.build_global_string_ptr(&format!("{}:{}:{}", file.name, line + 1, column + 1), "location");
+        let message = self.builder.build_global_string_ptr(message, "message");
+
+        let panic = self.panic_function().map_err(|diagnostic| diagnostic.with_label(span, "this check calls it"))?;
+        let args = [location.as_pointer_value().into(), message.as_pointer_value().into(), values[0].into(), values[1].into()];
+        self.builder.build_call(panic, &args, "");
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(continue_block);
+        Ok(())
+    }
+
+    /// Returns `__proto_panic(location, message, a, b)`, which the program
+    /// calls when a run-time check fails. It flushes what the program has
+    /// written so far, prints the location in the source and the message,
+    /// formatted with `a` and `b`, to the standard error and aborts. It is
+    /// only added to the module by its first use.
+    fn panic_function(&self) -> Result<FunctionValue<'ctx>, Diagnostic> {
+        if let Some(function) = self.module.get_function("__proto_panic") {
+            return Ok(function);
+        }
+
+        let i8_pointer = self.context.i8_type().ptr_type(AddressSpace::default());
+        let i64_type = self.context.i64_type();
+        let function_type = self.context.void_type().fn_type(&[i8_pointer.into(), i8_pointer.into(), i64_type.into(), i64_type.into()], false);
+        let function = self.module.add_function("__proto_panic", function_type, Some(Linkage::Internal));
+        for attribute in ["noreturn", "cold", "noinline"] {
+            let attribute = self.context.create_enum_attribute(Attribute::get_named_enum_kind_id(attribute), 0);
+            function.add_attribute(AttributeLoc::Function, attribute);
+        }
+
+        let i32_type = self.context.i32_type();
+        let dprintf = self.libc_function("dprintf", i32_type.fn_type(&[i32_type.into(), i8_pointer.into()], true))?;
+        let fflush = self.libc_function("fflush", i32_type.fn_type(&[i8_pointer.into()], false))?;
+        let abort = self.libc_function("abort", self.context.void_type().fn_type(&[], false))?;
+
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(function, "entry"));
+        builder.build_call(fflush, &[i8_pointer.const_null().into()], "");
+
+        let stderr = i32_type.const_int(2, false);
+        let header = builder.build_global_string_ptr("panicked at %s:\n", "panic_header").as_pointer_value();
+        let location = function.get_nth_param(0).unwrap();
+        builder.build_call(dprintf, &[stderr.into(), header.into(), location.into()], "");
+
+        let message = function.get_nth_param(1).unwrap();
+        let a = function.get_nth_param(2).unwrap();
+        let b = function.get_nth_param(3).unwrap();
+        builder.build_call(dprintf, &[stderr.into(), message.into(), a.into(), b.into()], "");
+
+        builder.build_call(abort, &[], "");
+        builder.build_unreachable();
+
+        Ok(function)
+    }
+
+    /// Returns the C library function `name`, declaring it on first use. The
+    /// program may have declared it already, which is only an error if it did
+    /// so with another signature.
+    fn libc_function(&self, name: &str, function_type: inkwell::types::FunctionType<'ctx>) -> Result<FunctionValue<'ctx>, Diagnostic> {
+        match self.module.get_function(name) {
+            Some(function) if function.get_type() != function_type => {
+                let span = self.function_table.get(&function).unwrap().span;
+                Err(Self::libc_mismatch(name).with_span(span))
+            }
+            Some(function) => Ok(function),
+            None => Ok(self.module.add_function(name, function_type, Some(Linkage::External))),
+        }
+    }
+
+    /// Reports that the program declares `name` with a signature other than
+    /// the one the panic hook calls the C library function by.
+    fn libc_mismatch(name: &str) -> Diagnostic {
+        let prototype = match name {
+            "dprintf" => "int dprintf(int fd, const char *format, ...)",
+            "fflush" => "int fflush(FILE *stream)",
+            _ => "void abort(void)",
+        };
+        Diagnostic::error(format!("function `{}` is declared with a different signature than the C library function", name))
+            .with_note(format!("the run-time checks call it as `{}`", prototype))
+            .with_note("`--no-bounds-checks` leaves the run-time checks out")
+    }
+
+    /// Computes the address of an element of a list or a slice, checking the
+    /// index against its length.
+    fn index_address(&mut self, expr: &IndexExpr<'ctx>) -> Result<(PointerValue<'ctx>, Type), Diagnostic> {
+        let (address, type_) = self.base_address(&expr.base)?;
+        let (first, len, element) = self.slice_parts(address, &type_)?;
+        let index = self.build_index(expr.index.as_ref())?;
+
+        let comparison = self.builder.build_int_compare(IntPredicate::ULT, index, len, "comparison");
+        self.build_bounds_check(comparison, "index out of bounds: the len is %llu but the index is %lld\n", [len, index], expr.span)?;
+
+        let address = unsafe {
+            self.builder.build_gep(self.get_type(&element)?, first, &[index], "index")
+        };
+
+        Ok((address, element))
+    }
+
+    /// Builds a call of the built-in `len`. The length of a list is part of
+    /// its type, so only a slice or a `str` is looked at.
+    fn build_len(&mut self, arg: &dyn Expr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        self.expected_type = None;
+        let mut value = arg.accept(self)?;
+        let u64_type = Type::Literal(LiteralType::Int(IntType::U64));
+
+        loop {
+            match value.get_type() {
+                Type::List(_, len) => {
+                    return Ok(Value::new(self.context.i64_type().const_int(len as u64, false), u64_type));
+                }
+                Type::Pointer(pointee, _) if matches!(*pointee, Type::List(_, _)) => {
+                    value = Value::new(value.as_llvm_basic_value_enum(), *pointee);
+                }
+                Type::Pointer(pointee, _) => {
+                    let pointer = value.as_llvm_basic_value_enum().into_pointer_value();
+                    value = Value::new(self.builder.build_load(self.get_type(&pointee)?, pointer, "deref"), *pointee);
+                }
+                Type::Slice(_, _) | Type::Str => {
+                    let slice = value.as_llvm_basic_value_enum().into_struct_value();
+                    let len = self.builder.build_extract_value(slice, 1, "len").unwrap();
+                    return Ok(Value::new(len, u64_type));
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Computes the address of a field of a struct.
+    fn field_address(&mut self, expr: &FieldExpr<'ctx>) -> Result<(PointerValue<'ctx>, Type), Diagnostic> {
+        let (address, type_) = self.base_address(&expr.base)?;
+
+        let struct_info = match &type_ {
+            Type::Named(name) => &self.struct_table[name],
+            _ => unreachable!(),
+        };
+        let (index, (_, field_type)) = struct_info.fields.iter()
+            .enumerate()
+            .find(|(_, (name, _))| *name == expr.field)
+            .unwrap();
+
+        let address = self.builder.build_struct_gep(struct_info.llvm_type, address, index as u32, &expr.field).unwrap();
+
+        Ok((address, field_type.clone()))
+    }
+
+    /// Shifts `left` by `right`. The amount is converted to the type of `left`
+    /// and masked to its bit width, so an over-long shift wraps the same way it
+    /// does at compile time instead of producing a poison value.
+    fn build_shift(&self, left: IntValue<'ctx>, right: IntValue<'ctx>, kind: &TokenKind, signed: bool) -> IntValue<'ctx> {
+        let type_ = left.get_type();
+        let amount = match right.get_type().get_bit_width().cmp(&type_.get_bit_width()) {
+            Ordering::Less => self.builder.build_int_z_extend(right, type_, "z_extend"),
+            Ordering::Greater => self.builder.build_int_truncate(right, type_, "truncate"),
+            Ordering::Equal => right,
+        };
+        let mask = type_.const_int(type_.get_bit_width() as u64 - 1, false);
+        let amount = self.builder.build_and(amount, mask, "shift_amount");
+
+        match kind {
+            TokenKind::ShiftLeft => self.builder.build_left_shift(left, amount, "shl"),
+            _ => self.builder.build_right_shift(left, amount, signed, "shr"),
+        }
+    }
+
+    /// Merges the values an expression takes on the paths leading into the
+    /// current block with a phi node, giving them the type the type checker
+    /// found for them. Constant values are only built here, once that type is
+    /// known, as constants need no code of their own.
+    fn build_join(&mut self, incoming: &[(Value<'ctx>, BasicBlock<'ctx>)], expected_type: Option<Type>, name: &str, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+        let is_constant = |value: &Value| !matches!(value, Value::LLVMBasicValueEnum(_, _));
+        let all_constant = incoming.iter().all(|(value, _)| is_constant(value));
+        let joined = if all_constant && expected_type.is_some() {
+            expected_type
         } else {
-            string.to_owned()
+            incoming.iter()
+                .filter(|(value, _)| all_constant || !is_constant(value))
+                .map(|(value, _)| value.constant_type())
+                .reduce(|joined, type_| joined?.join(type_?))
+                .flatten()
+        };
+
+        let result_type = match joined {
+            Some(result_type) if result_type != Type::Void => result_type,
+            _ => return Ok(Value::new(self.context.i32_type().const_zero(), Type::Void)),
+        };
+
+        let values = incoming.iter()
+            .map(|(value, block)| {
+                let value = match value {
+                    Value::LLVMBasicValueEnum(value, _) => *value,
+                    constant => self.build_constant(constant, &result_type, span)?,
+                };
+                Ok((value, *block))
+            })
+            .collect::<Result<Vec<_>, Diagnostic>>()?;
+
+        let phi = self.builder.build_phi(self.get_type(&result_type)?, name);
+        for (value, block) in &values {
+            phi.add_incoming(&[(value, *block)]);
         }
+
+        Ok(Value::new(phi.as_basic_value(), result_type))
+    }
+
+    /// Generates the body of a loop, which `break` jumps out of to
+    /// `break_block` and `continue` to `continue_block`. Returns the values
+    /// the `break`s out of the loop give it.
+    fn generate_loop_body(&mut self, label: Option<String>, break_block: BasicBlock<'ctx>, continue_block: BasicBlock<'ctx>, body: &dyn Stmt<'ctx>) -> Result<Vec<(Value<'ctx>, BasicBlock<'ctx>)>, Diagnostic> {
+        self.loop_stack.push(LoopInfo {
+            label,
+            break_block,
+            continue_block,
+            break_values: Vec::new(),
+        });
+        let result = self.generate_stmt(body);
+        let loop_info = self.loop_stack.pop().unwrap();
+        result.map(|_| loop_info.break_values)
     }
- 
-    fn check_type_match(&self, expected: &str, actual: &str) {
-        let expected = self.extract_string(expected);
-        let actual = self.extract_string(&actual.to_string());
-        if expected != actual {
-            panic!("Expected {}, got {}", expected, actual);
+
+    /// Finds the loop a `break` or `continue` leaves: the one named `label`,
+    /// or else the innermost one.
+    fn target_loop(&self, label: &Option<String>) -> Option<usize> {
+        match label {
+            Some(label) => self.loop_stack.iter().rposition(|loop_info| loop_info.label.as_ref() == Some(label)),
+            None => self.loop_stack.len().checked_sub(1),
         }
     }
 
@@ -90,60 +795,71 @@ impl<'ctx> CodeGenerator<'ctx> {
 
 impl<'ctx> StmtVisitor<'ctx> for CodeGenerator<'ctx> {
 
-    fn visit_expr_stmt(&mut self, stmt: &ExprStmt<'ctx>) {
-        stmt.expr.accept(self);
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt<'ctx>) -> Result<(), Diagnostic> {
+        stmt.expr.accept(self)?;
+        Ok(())
     }
 
-    fn visit_var_decl_stmt(&mut self, stmt: &VarDeclStmt<'ctx>) {
+    fn visit_var_decl_stmt(&mut self, stmt: &VarDeclStmt<'ctx>) -> Result<(), Diagnostic> {
         let name = &stmt.name;
-        let value = stmt.expr.accept(self).as_llvm_basic_value_enum();
+        let value = match stmt.type_ {
+            Type::Inferred => stmt.expr.accept(self)?,
+            _ => self.build_initializer(stmt.expr.as_ref(), &stmt.type_)?,
+        };
 
-        match stmt.type_ {
-            Type::Literal(_) => {}
-            Type::Inferred => {}
-            Type::List(_, _) => {}
-            Type::Void => {}
-        }
+        let type_ = value.get_type();
+        let value = value.as_llvm_basic_value_enum();
 
-        let alloca = self.builder.build_alloca(value.get_type(), name);
+        let alloca = self.build_entry_alloca(value.get_type(), name);
         self.builder.build_store(alloca, value);
 
         let variable_info = VariableInfo {
-            type_: value.get_type(),
+            type_,
             alloca,
         };
         if let Some(scope) = self.symbol_table.last_mut() {
             scope.insert(name.to_owned(), variable_info);
         }
+
+        Ok(())
+    }
+
+    fn visit_const_decl_stmt(&mut self, stmt: &ConstDeclStmt<'ctx>) -> Result<(), Diagnostic> {
+        self.generate_global(true, &stmt.name, &stmt.type_, stmt.value.as_ref())
+    }
+
+    fn visit_static_decl_stmt(&mut self, stmt: &StaticDeclStmt<'ctx>) -> Result<(), Diagnostic> {
+        self.generate_global(false, &stmt.name, &stmt.type_, stmt.value.as_ref())
     }
 
-    fn visit_return_stmt(&mut self, stmt: &ReturnStmt<'ctx>) {
-        let value = stmt.expr.accept(self).as_llvm_basic_value_enum();
-       
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt<'ctx>) -> Result<(), Diagnostic> {
         let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-        let return_type = self.function_table.get(&function).unwrap().return_type.as_ref();
+        let return_type = self.function_table.get(&function).unwrap().return_type.clone();
 
-        if let Some(return_type) = return_type {
-            self.check_type_match(&return_type.to_string(), &value.get_type().to_string());
-        } else {
-            self.function_table.get_mut(&function).unwrap().return_type = Some(value.get_type());
+        match &stmt.expr {
+            Some(expr) => {
+                let value = self.build_initializer(expr.as_ref(), &return_type)?.as_llvm_basic_value_enum();
+                self.builder.build_return(Some(&value));
+            }
+            None => {
+                self.builder.build_return(None);
+            }
         }
 
-        self.builder.build_return(Some(&value));
-
         let end_block = self.context.append_basic_block(function, "end");
         self.builder.position_at_end(end_block);
+
+        Ok(())
     }
 
-    fn visit_block_stmt(&mut self, stmt: &BlockStmt<'ctx>) {
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt<'ctx>) -> Result<(), Diagnostic> {
         self.enter_scope();
-        for stmt in &stmt.stmts {
-            stmt.accept(self);
-        }
+        let result = stmt.stmts.iter().try_for_each(|stmt| self.generate_stmt(stmt.as_ref()));
         self.exit_scope();
+        result
     }
 
-    fn visit_if_stmt(&mut self, stmt: &IfStmt<'ctx>) {
+    fn visit_if_stmt(&mut self, stmt: &IfStmt<'ctx>) -> Result<(), Diagnostic> {
         let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
         let cond_block = self.context.append_basic_block(function, "if_cond");
         let then_block = self.context.append_basic_block(function, "then");
@@ -157,24 +873,26 @@ impl<'ctx> StmtVisitor<'ctx> for CodeGenerator<'ctx> {
 
         self.builder.build_unconditional_branch(cond_block);
         self.builder.position_at_end(cond_block);
-        let condition = stmt.cond.accept(self).as_llvm_basic_value_enum().into_int_value();
+        let condition = stmt.cond.accept(self)?.as_llvm_basic_value_enum().into_int_value();
 
         self.builder.build_conditional_branch(condition, then_block, else_block);
 
         self.builder.position_at_end(then_block);
-        stmt.then.accept(self);
+        stmt.then.accept(self)?;
         self.builder.build_unconditional_branch(end_block);
 
         if let Some(otherwise) = &stmt.otherwise {
             self.builder.position_at_end(else_block);
-            otherwise.accept(self);
+            otherwise.accept(self)?;
             self.builder.build_unconditional_branch(end_block);
         }
 
         self.builder.position_at_end(end_block);
+
+        Ok(())
     }
-    
-    fn visit_while_stmt(&mut self, stmt: &WhileStmt<'ctx>) {
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt<'ctx>) -> Result<(), Diagnostic> {
         let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
         let cond_block = self.context.append_basic_block(function, "while_cond");
         let body_block = self.context.append_basic_block(function, "body");
@@ -183,441 +901,1080 @@ impl<'ctx> StmtVisitor<'ctx> for CodeGenerator<'ctx> {
         self.builder.build_unconditional_branch(cond_block);
 
         self.builder.position_at_end(cond_block);
-        let condition = stmt.cond.accept(self).as_llvm_basic_value_enum().into_int_value(); 
+        let condition = stmt.cond.accept(self)?.as_llvm_basic_value_enum().into_int_value();
         self.builder.build_conditional_branch(condition, body_block, end_block);
 
         self.builder.position_at_end(body_block);
 
-        self.continue_block_stack.push(cond_block);
-        self.break_block_stack.push(end_block);
-        stmt.body.accept(self);
-        self.break_block_stack.pop();
-        self.continue_block_stack.pop();
+        self.generate_loop_body(stmt.label.clone(), end_block, cond_block, stmt.body.as_ref())?;
 
         self.builder.build_unconditional_branch(cond_block);
 
         self.builder.position_at_end(end_block);
+
+        Ok(())
     }
 
-    fn visit_break_stmt(&mut self, _stmt: &BreakStmt) {
-        let break_block = self.break_block_stack.last();
-        if let Some(break_block) = break_block {
-            self.builder.build_unconditional_branch(*break_block);
-            let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-            let end_block = self.context.append_basic_block(function, "break_end");
-            self.builder.position_at_end(end_block);
-        } else {
-            panic!("Break statement outside of loop");
+    fn visit_for_stmt(&mut self, stmt: &ForStmt<'ctx>) -> Result<(), Diagnostic> {
+        // Both kinds of loop count from `start` up to `end`. A list or a slice
+        // is walked by index, which stays below its length and so never needs
+        // a bounds check.
+        let (counter_type, start, end, list) = match &stmt.iterable {
+            ForIterable::Range(start, end) => {
+                let start = self.build_operand(start.as_ref())?;
+                let end = self.build_operand(end.as_ref())?;
+                let (start_type, end_type) = match (start.get_type().numeric(), end.get_type().numeric()) {
+                    (Some(LiteralType::Int(start_type)), Some(LiteralType::Int(end_type))) => (start_type, end_type),
+                    _ => unreachable!(),
+                };
+
+                let (start, end, int_type) = self.unify_ints(Self::integer(start, start_type), Self::integer(end, end_type));
+
+                (int_type, start, end, None)
+            }
+            ForIterable::List(iterable) => {
+                let value = iterable.accept(self)?;
+                let type_ = value.get_type();
+
+                let alloca = self.build_entry_alloca(self.get_type(&type_)?, "iterable");
+                self.builder.build_store(alloca, value.as_llvm_basic_value_enum());
+                let (first, len, element) = self.slice_parts(alloca, &type_)?;
+
+                (IntType::U64, self.context.i64_type().const_zero(), len, Some((first, element)))
+            }
+        };
+
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let cond_block = self.context.append_basic_block(function, "for_cond");
+        let body_block = self.context.append_basic_block(function, "body");
+        let step_block = self.context.append_basic_block(function, "for_step");
+        let end_block = self.context.append_basic_block(function, "for_end");
+
+        let llvm_counter_type = self.get_int_type(counter_type);
+        let counter = self.build_entry_alloca(llvm_counter_type, "counter");
+        self.builder.build_store(counter, start);
+        self.builder.build_unconditional_branch(cond_block);
+
+        self.builder.position_at_end(cond_block);
+        let index = self.builder.build_load(llvm_counter_type, counter, "index").into_int_value();
+        let predicate = if counter_type.is_signed() { IntPredicate::SLT } else { IntPredicate::ULT };
+        let condition = self.builder.build_int_compare(predicate, index, end, "condition");
+        self.builder.build_conditional_branch(condition, body_block, end_block);
+
+        self.builder.position_at_end(body_block);
+        let (value, type_) = match list {
+            Some((first, element)) => {
+                let address = unsafe {
+                    self.builder.build_gep(self.get_type(&element)?, first, &[index], "element")
+                };
+                (self.builder.build_load(self.get_type(&element)?, address, &stmt.name), element)
+            }
+            None => (index.into(), Type::Literal(LiteralType::Int(counter_type))),
+        };
+
+        self.enter_scope();
+        let alloca = self.build_entry_alloca(value.get_type(), &stmt.name);
+        self.builder.build_store(alloca, value);
+        if let Some(scope) = self.symbol_table.last_mut() {
+            scope.insert(stmt.name.clone(), VariableInfo { type_, alloca });
         }
+
+        let result = self.generate_loop_body(stmt.label.clone(), end_block, step_block, stmt.body.as_ref());
+        self.exit_scope();
+        result?;
+
+        self.builder.build_unconditional_branch(step_block);
+
+        self.builder.position_at_end(step_block);
+        let index = self.builder.build_load(llvm_counter_type, counter, "index").into_int_value();
+        let next = self.builder.build_int_add(index, llvm_counter_type.const_int(1, false), "next");
+        self.builder.build_store(counter, next);
+        self.builder.build_unconditional_branch(cond_block);
+
+        self.builder.position_at_end(end_block);
+
+        Ok(())
     }
 
-    fn visit_continue_stmt(&mut self, _stmt: &ContinueStmt) {
-        let continue_block = self.continue_block_stack.last();
-        if let Some(continue_block) = continue_block {
-            self.builder.build_unconditional_branch(*continue_block);
-            let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-            let end_block = self.context.append_basic_block(function, "continue_end");
-            self.builder.position_at_end(end_block);
-        } else {
-            panic!("Continue statement outside of loop");
+    fn visit_break_stmt(&mut self, stmt: &BreakStmt<'ctx>) -> Result<(), Diagnostic> {
+        let index = self.target_loop(&stmt.label).unwrap();
+
+        if let Some(value) = &stmt.value {
+            // A constant value is built once the type of the loop is known.
+            let value = match value.accept(&mut CompileTimeEvaluator::new()) {
+                Ok(constant) => constant,
+                Err(_) => value.accept(self)?,
+            };
+            let block = self.builder.get_insert_block().unwrap();
+            self.loop_stack[index].break_values.push((value, block));
         }
+
+        self.builder.build_unconditional_branch(self.loop_stack[index].break_block);
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let end_block = self.context.append_basic_block(function, "break_end");
+        self.builder.position_at_end(end_block);
+
+        Ok(())
     }
 
-    fn visit_function_decl_stmt(&mut self, stmt: &FunctionDeclStmt) {
+    fn visit_continue_stmt(&mut self, stmt: &ContinueStmt) -> Result<(), Diagnostic> {
+        let index = self.target_loop(&stmt.label).unwrap();
+
+        self.builder.build_unconditional_branch(self.loop_stack[index].continue_block);
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let end_block = self.context.append_basic_block(function, "continue_end");
+        self.builder.position_at_end(end_block);
+
+        Ok(())
+    }
+
+    fn visit_function_decl_stmt(&mut self, stmt: &FunctionDeclStmt) -> Result<(), Diagnostic> {
         let name = &stmt.name;
         let params = &stmt.params;
-        let param_types = params.iter().map(|param| self.get_type(&param.type_).into()).collect::<Vec<BasicMetadataTypeEnum>>();
-
-        let return_type = &stmt.return_type;
-        let function_type = match return_type {
-            Type::Literal(LiteralType::Int(_)) => self.get_type(return_type).into_int_type().fn_type(&param_types, false),
-            Type::Literal(LiteralType::Float(_)) => self.get_type(return_type).into_float_type().fn_type(&param_types, false),
+        let param_types = params.iter()
+            .map(|param| self.get_type(&param.type_)
+                 .map(|type_| type_.into())
+                 .map_err(|diagnostic| diagnostic.with_span(param.span)))
+            .collect::<Result<Vec<BasicMetadataTypeEnum>, Diagnostic>>()?;
+
+        let return_type = stmt.resolved_return_type();
+        let function_type = match &return_type {
             Type::Void => self.context.void_type().fn_type(&param_types, false),
-            Type::Inferred => self.context.void_type().fn_type(&param_types, false),
-            _ => panic!("Unsupported return type"),
+            return_type => self.get_type(return_type).map_err(|diagnostic| diagnostic.with_span(stmt.span))?.fn_type(&param_types, false),
         };
 
-        if self.module.get_function(name).is_some() {
-            panic!("Function `{}` already exists", name);
-        }
+        // The C library functions the panic hook calls may already have been
+        // declared for it.
+        let function = match self.module.get_function(name) {
+            Some(function) if function.get_type() != function_type => return Err(Self::libc_mismatch(name).with_span(stmt.span)),
+            Some(function) => function,
+            None => self.module.add_function(name, function_type, None),
+        };
+
+        let function_info = FunctionInfo {
+            param_types: params.iter().map(|param| param.type_.clone()).collect(),
+            return_type,
+            span: stmt.span,
+        };
+
+        self.function_table.insert(function, function_info);
 
-        self.module.add_function(name, function_type, None);
+        Ok(())
     }
 
-    fn visit_function_def_stmt(&mut self, stmt: &FunctionDefStmt<'ctx>) {
-        stmt.func_decl.accept(self);
+    fn visit_function_def_stmt(&mut self, stmt: &FunctionDefStmt<'ctx>) -> Result<(), Diagnostic> {
+        stmt.func_decl.accept(self)?;
 
         let function = self.module.get_function(&stmt.func_decl.name).unwrap();
 
-        let mut params = HashMap::new();
-        for (i, param) in function.get_param_iter().enumerate() {
-            param.set_name(&stmt.func_decl.params[i].name);
-            params.insert(stmt.func_decl.params[i].name.clone(), param);
+        let entry_block = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry_block);
+
+        // Parameters are stored in stack slots like any other variable, so that
+        // they can be assigned to and their fields and elements addressed.
+        self.enter_scope();
+        for (param, decl) in function.get_param_iter().zip(&stmt.func_decl.params) {
+            param.set_name(&decl.name);
+            let alloca = self.build_entry_alloca(param.get_type(), &decl.name);
+            self.builder.build_store(alloca, param);
+
+            let variable_info = VariableInfo {
+                type_: decl.type_.clone(),
+                alloca,
+            };
+            if let Some(scope) = self.symbol_table.last_mut() {
+                scope.insert(decl.name.clone(), variable_info);
+            }
         }
 
-        let return_type = &stmt.func_decl.return_type;
-        let return_type = match return_type {
-            Type::Literal(LiteralType::Int(_)) => Some(self.get_type(return_type)),
-            Type::Literal(LiteralType::Float(_)) => Some(self.get_type(return_type)),
-            Type::Inferred => {
-                if stmt.func_decl.name == "main" {
-                    Some(self.context.i32_type().into())
-                } else {
-                    None
-                }
+        let result = stmt.body.accept(self);
+        self.exit_scope();
+        result?;
+
+        // Running off the end of the body returns from a function without a
+        // return value. The type checker has made sure that the end of any
+        // other function is never reached, but every block needs a terminator.
+        if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+            if stmt.func_decl.resolved_return_type() == Type::Void {
+                self.builder.build_return(None);
+            } else {
+                self.builder.build_unreachable();
             }
-            _ => panic!("Unsupported return type"),
-        };
+        }
 
-        let function_info = FunctionInfo {
-            params,
-            return_type: return_type.into(), 
+        self.pass_manager.run_on(&function);
+
+        Ok(())
+    }
+
+    fn visit_enum_decl_stmt(&mut self, stmt: &EnumDeclStmt) -> Result<(), Diagnostic> {
+        let mut payload_size = 0;
+        for variant in &stmt.variants {
+            let payload_type = self.get_payload_type(&variant.fields)?;
+            payload_size = payload_size.max(self.target_data.get_abi_size(&payload_type));
+        }
+
+        // The payload is made of `i64` words so that it is aligned for the
+        // fields of every variant.
+        let i64_type = self.context.i64_type();
+        let payload_type = i64_type.array_type(payload_size.div_ceil(8) as u32);
+
+        let llvm_type = self.context.opaque_struct_type(&stmt.name);
+        llvm_type.set_body(&[self.context.i32_type().into(), payload_type.into()], false);
+
+        let enum_info = EnumInfo {
+            llvm_type,
+            variants: stmt.variants.iter().map(|variant| (variant.name.clone(), variant.fields.clone())).collect(),
         };
+        self.enum_table.insert(stmt.name.clone(), enum_info);
 
-        self.function_table.insert(function, function_info);
+        Ok(())
+    }
 
-        let entry_block = self.context.append_basic_block(function, "entry");
-        self.builder.position_at_end(entry_block);
+    fn visit_struct_decl_stmt(&mut self, stmt: &StructDeclStmt) -> Result<(), Diagnostic> {
+        let field_types = stmt.fields.iter()
+            .map(|field| self.get_type(&field.type_))
+            .collect::<Result<Vec<BasicTypeEnum>, Diagnostic>>()?;
+
+        let llvm_type = self.context.opaque_struct_type(&stmt.name);
+        llvm_type.set_body(&field_types, false);
+
+        let struct_info = StructInfo {
+            llvm_type,
+            fields: stmt.fields.iter().map(|field| (field.name.clone(), field.type_.clone())).collect(),
+        };
+        self.struct_table.insert(stmt.name.clone(), struct_info);
 
-        stmt.body.accept(self);
+        Ok(())
     }
 }
 
 impl<'ctx> ExprVisitor<'ctx> for CodeGenerator<'ctx> {
-    fn visit_call_expr(&mut self, expr: &CallExpr<'ctx>) -> Value<'ctx> {
+    fn visit_call_expr(&mut self, expr: &CallExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
         let name = &expr.callee;
 
-        let function = self.module.get_function(name).expect(&format!("Function '{}' not defined", name));
-
-        if expr.args.len() != function.count_params() as usize {
-            panic!("Function '{}' takes {} arguments, but {} were supplied", name, function.count_params(), expr.args.len());
-        }
-
-        let args = expr.args.iter().enumerate()
-            .map(|(i, arg)| {
-                let arg = arg.accept(self).as_llvm_basic_value_enum();
-                let param_type = function.get_nth_param(i as u32).unwrap().get_type();
-                let arg_type = arg.get_type();
+        // The type checker has made sure that a call of a function that does
+        // not exist is one of the built-in `len`.
+        let Some(function) = self.module.get_function(name) else {
+            return match expr.args.as_slice() {
+                [arg] if name == "len" => self.build_len(arg.as_ref()),
+                _ => Err(Diagnostic::error(format!("internal error: cannot find function `{}`", name)).with_span(expr.span)),
+            };
+        };
 
-                if param_type != arg_type {
-                    panic!("Function '{}' argument {} must be of type {}, but {} was supplied", name, i, param_type, arg_type);
-                }
+        let function_info = self.function_table.get(&function).unwrap();
+        let param_types = function_info.param_types.clone();
+        let return_type = function_info.return_type.clone();
 
-                arg.into()
-            })
-            .collect::<Vec<BasicMetadataValueEnum>>();
+        let args = expr.args.iter().zip(&param_types)
+            .map(|(arg, param_type)| Ok(self.build_initializer(arg.as_ref(), param_type)?.as_llvm_basic_value_enum().into()))
+            .collect::<Result<Vec<BasicMetadataValueEnum>, Diagnostic>>()?;
 
         let ret_value = self.builder
-            .build_call(function, &args, &name)
+            .build_call(function, &args, name)
             .try_as_basic_value().left();
 
-        Value::LLVMBasicValueEnum(ret_value.unwrap_or_else(|| self.context.i32_type().const_zero().into()))
+        Ok(Value::new(ret_value.unwrap_or_else(|| self.context.i32_type().const_zero().into()), return_type))
     }
 
-    fn visit_list_expr(&mut self, expr: &ListExpr<'ctx>) -> Value<'ctx> {
+    fn visit_list_expr(&mut self, expr: &ListExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let expected = self.expected_type.take();
+        if let Ok(constant) = expr.accept(&mut CompileTimeEvaluator::new()) {
+            if let Some(type_) = constant.constant_type() {
+                return Ok(Value::new(self.build_constant(&constant, &type_, expr.span)?, type_));
+            }
+        }
 
+        let expected = match expected {
+            Some(Type::List(element, _)) => Some(*element),
+            _ => None,
+        };
+
+        // The elements are evaluated in order, but the constant ones are only
+        // built once the type of the elements computed at run time is known.
         let mut values = Vec::new();
         for value in &expr.values {
-            let value = value.accept(self);
-            values.push(value.as_llvm_basic_value_enum());
+            let value = match (value.accept(&mut CompileTimeEvaluator::new()), &expected) {
+                (Ok(constant), _) => constant,
+                (Err(_), Some(element)) => self.build_initializer(value.as_ref(), element)?,
+                (Err(_), None) => value.accept(self)?,
+            };
+            values.push(value);
         }
 
-        let first = values.first().expect("List must have at least one value");
-        let type_ = first.get_type();
-
-        for value in &values {
-            if value.get_type() != type_ {
-                panic!("List values must all be of the same type. Expected {}, found {}", type_, value.get_type());
-            }
+        let element = expected.unwrap_or_else(|| {
+            values.iter()
+                .find(|value| matches!(value, Value::LLVMBasicValueEnum(_, _)))
+                .unwrap()
+                .get_type()
+        });
+        let list_type = Type::List(Box::new(element.clone()), values.len());
+
+        let mut list = self.get_type(&list_type)?.into_array_type().get_undef();
+        for (i, value) in values.iter().enumerate() {
+            let value = match value {
+                Value::LLVMBasicValueEnum(value, _) => *value,
+                constant => self.build_constant(constant, &element, expr.values[i].span())?,
+            };
+            list = self.builder.build_insert_value(list, value, i as u32, "element").unwrap().into_array_value();
         }
 
-        match type_ {
-            BasicTypeEnum::IntType(_) => {
-                let values = values.iter().map(|value| value.into_int_value()).collect::<Vec<IntValue>>();
-                let array = type_.into_int_type().const_array(&values);
-                Value::LLVMBasicValueEnum(array.into())
-            }
-            BasicTypeEnum::FloatType(_) => {
-                let values = values.iter().map(|value| value.into_float_value()).collect::<Vec<FloatValue>>();
-                let array = type_.into_float_type().const_array(&values);
-                Value::LLVMBasicValueEnum(array.into())
-            }
-            BasicTypeEnum::ArrayType(_) => {
-                let values = values.iter().map(|value| value.into_array_value()).collect::<Vec<ArrayValue>>();
-                let array = type_.into_array_type().const_array(&values);
-                Value::LLVMBasicValueEnum(array.into())
+        Ok(Value::new(list, list_type))
+    }
+
+    fn visit_repeat_expr(&mut self, expr: &RepeatExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let expected = self.expected_type.take();
+        if let Ok(constant) = expr.accept(&mut CompileTimeEvaluator::new()) {
+            if let Some(type_) = constant.constant_type() {
+                return Ok(Value::new(self.build_constant(&constant, &type_, expr.span)?, type_));
             }
-            _ => panic!("Unsupported list type"),
         }
-    }
 
-    fn visit_index_expr(&mut self, expr: &IndexExpr<'ctx>) -> Value<'ctx> {
+        let value = match expected {
+            Some(Type::List(element, _)) => self.build_initializer(expr.value.as_ref(), &element)?,
+            _ => expr.value.accept(self)?,
+        };
+        let element = value.get_type();
+        let list_type = Type::List(Box::new(element.clone()), expr.count);
 
-        let indices = expr.indices
-            .iter()
-            .map(|index| index.accept(self).as_llvm_basic_value_enum().into_int_value())
-            .collect::<Vec<IntValue>>();
+        // The value is stored into the elements by a loop rather than one
+        // instruction per element, which a long list would make too many.
+        let alloca = self.build_entry_alloca(self.get_type(&list_type)?, "list");
+        let (first, len, _) = self.slice_parts(alloca, &list_type)?;
 
-        let variable_info = self.get_variable_info(&expr.variable.name)
-                                .expect(format!("Variable '{}' not defined", expr.variable.name).as_str());
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let cond_block = self.context.append_basic_block(function, "repeat_cond");
+        let body_block = self.context.append_basic_block(function, "repeat_body");
+        let end_block = self.context.append_basic_block(function, "repeat_end");
 
-        let mut address = variable_info.alloca;
-        let mut type_ = variable_info.type_;
-        let mut value = self.builder.build_load(type_, address, "value");
+        let i64_type = self.context.i64_type();
+        let counter = self.build_entry_alloca(i64_type, "counter");
+        self.builder.build_store(counter, i64_type.const_zero());
+        self.builder.build_unconditional_branch(cond_block);
 
-        for index in indices {
-            if !type_.is_array_type() {
-                panic!("Cannot index non-array type");
-            }
+        self.builder.position_at_end(cond_block);
+        let index = self.builder.build_load(i64_type, counter, "index").into_int_value();
+        let condition = self.builder.build_int_compare(IntPredicate::ULT, index, len, "condition");
+        self.builder.build_conditional_branch(condition, body_block, end_block);
+
+        self.builder.position_at_end(body_block);
+        let address = unsafe {
+            self.builder.build_gep(self.get_type(&element)?, first, &[index], "element")
+        };
+        self.builder.build_store(address, value.as_llvm_basic_value_enum());
+        let next = self.builder.build_int_add(index, i64_type.const_int(1, false), "next");
+        self.builder.build_store(counter, next);
+        self.builder.build_unconditional_branch(cond_block);
+
+        self.builder.position_at_end(end_block);
+        let list = self.builder.build_load(self.get_type(&list_type)?, alloca, "list");
 
-            let array_type = type_.into_array_type();
-            let len = array_type.len();
+        Ok(Value::new(list, list_type))
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let (address, type_) = self.index_address(expr)?;
+        let value = self.builder.build_load(self.get_type(&type_)?, address, "value");
+        Ok(Value::new(value, type_))
+    }
 
-            let len = self.context.i32_type().const_int(len as u64, false);
-            let comparison = self.builder.build_int_compare(inkwell::IntPredicate::ULT, index, len, "comparison");
+    fn visit_slice_expr(&mut self, expr: &SliceExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        self.expected_type = None;
+        let (address, type_) = self.base_address(&expr.base)?;
+        let (first, len, element) = self.slice_parts(address, &type_)?;
 
-            let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-            let continue_block = self.context.append_basic_block(function, "continue");
-            let error_block = self.context.append_basic_block(function, "error");
+        let start = match &expr.start {
+            Some(start) => self.build_index(start.as_ref())?,
+            None => self.context.i64_type().const_zero(),
+        };
+        let end = match &expr.end {
+            Some(end) => self.build_index(end.as_ref())?,
+            None => len,
+        };
 
-            self.builder.build_conditional_branch(comparison, continue_block, error_block);
+        let within = self.builder.build_int_compare(IntPredicate::ULE, end, len, "within");
+        self.build_bounds_check(within, "range end index %lld out of range for slice of length %llu\n", [end, len], expr.span)?;
+        let ordered = self.builder.build_int_compare(IntPredicate::ULE, start, end, "ordered");
+        self.build_bounds_check(ordered, "slice index starts at %lld but ends at %lld\n", [start, end], expr.span)?;
 
-            self.builder.position_at_end(error_block);
-            let exit_fn = self.module.get_function("exit").unwrap();
-            self.builder.build_call(exit_fn, &[self.context.i32_type().const_int(1, false).into()], "exit");
+        let first = unsafe {
+            self.builder.build_gep(self.get_type(&element)?, first, &[start], "first")
+        };
+        let len = self.builder.build_int_sub(end, start, "len");
 
-            self.builder.build_unconditional_branch(continue_block);
+        let slice_type = self.get_slice_type(&element)?;
+        let value = self.builder.build_insert_value(slice_type.get_undef(), first, 0, "slice").unwrap();
+        let value = self.builder.build_insert_value(value, len, 1, "slice").unwrap().into_struct_value();
 
-            self.builder.position_at_end(continue_block);
+        Ok(Value::new(value, Type::Slice(Box::new(element), PointerKind::Mutable)))
+    }
 
-            type_ = array_type.get_element_type();
+    fn visit_field_expr(&mut self, expr: &FieldExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let (address, type_) = self.field_address(expr)?;
+        let value = self.builder.build_load(self.get_type(&type_)?, address, &expr.field);
+        Ok(Value::new(value, type_))
+    }
 
-            unsafe {
-                address = self.builder.build_gep(type_, address, &[index], "index");
-                value = self.builder.build_load(type_, address, "value");
-                type_ = value.get_type();
+    fn visit_struct_expr(&mut self, expr: &StructExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let type_ = Type::Named(expr.name.clone());
+        let struct_info = &self.struct_table[&expr.name];
+        let llvm_type = struct_info.llvm_type;
+        let fields = struct_info.fields.clone();
+
+        // The fields are built in the order they are written, and inserted in
+        // the order the struct declares them.
+        let mut value = llvm_type.get_undef();
+        for init in &expr.fields {
+            let index = fields.iter().position(|(name, _)| *name == init.name).unwrap();
+            let field = self.build_initializer(init.value.as_ref(), &fields[index].1)?.as_llvm_basic_value_enum();
+            value = self.builder.build_insert_value(value, field, index as u32, &init.name).unwrap().into_struct_value();
+        }
+
+        Ok(Value::new(value.as_basic_value_enum(), type_))
+    }
+
+    fn visit_variant_expr(&mut self, expr: &VariantExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let enum_info = &self.enum_table[&expr.enum_name];
+        let llvm_type = enum_info.llvm_type;
+        let index = enum_info.variants.iter().position(|(name, _)| *name == expr.variant).unwrap();
+        let fields = enum_info.variants[index].1.clone();
+
+        // The fields are stored through a pointer to the payload viewed as the
+        // variant, so the value is put together in memory.
+        let address = self.build_entry_alloca(llvm_type, "variant");
+        let tag = self.builder.build_struct_gep(llvm_type, address, 0, "tag").unwrap();
+        self.builder.build_store(tag, self.context.i32_type().const_int(index as u64, false));
+
+        if !fields.is_empty() {
+            let (payload, payload_type) = self.payload_address(&expr.enum_name, index, address)?;
+            for (i, (arg, field)) in expr.args.iter().zip(&fields).enumerate() {
+                let value = self.build_initializer(arg.as_ref(), field)?.as_llvm_basic_value_enum();
+                let field_address = self.builder.build_struct_gep(payload_type, payload, i as u32, "field").unwrap();
+                self.builder.build_store(field_address, value);
             }
         }
 
-        Value::LLVMBasicValueEnum(value)
+        let value = self.builder.build_load(llvm_type, address, &expr.variant);
+        Ok(Value::new(value, Type::Named(expr.enum_name.clone())))
     }
 
-    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Value<'ctx> {
-        match expr.value {
-            value::LiteralValue::Int(value) => {
-                match value {
-                    value::IntValue::I8(value) => self.context.i8_type().const_int(value as u64, false).into(),
-                    value::IntValue::I16(value) => self.context.i16_type().const_int(value as u64, false).into(),
-                    value::IntValue::I32(value) => self.context.i32_type().const_int(value as u64, false).into(),
-                    value::IntValue::I64(value) => self.context.i64_type().const_int(value as u64, false).into(),
+    fn visit_match_expr(&mut self, expr: &MatchExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let expected_type = self.expected_type.take();
+        let scrutinee = expr.scrutinee.accept(self)?;
+        let type_ = scrutinee.get_type();
+        let scrutinee = scrutinee.as_llvm_basic_value_enum();
+
+        // An enum is switched on its tag, and kept in memory so that the arms
+        // can bind the fields of its payload.
+        let (address, tag) = match &type_ {
+            Type::Named(name) if self.enum_table.contains_key(name) => {
+                let llvm_type = self.enum_table[name].llvm_type;
+                let address = self.build_entry_alloca(llvm_type, "scrutinee");
+                self.builder.build_store(address, scrutinee);
+                let tag = self.builder.build_struct_gep(llvm_type, address, 0, "tag").unwrap();
+                (Some(address), self.builder.build_load(self.context.i32_type(), tag, "tag").into_int_value())
+            }
+            // Only a catch-all arm can match a value that is neither an
+            // integer nor an enum, so any value does for the switch.
+            _ => match scrutinee {
+                BasicValueEnum::IntValue(scrutinee) => (None, scrutinee),
+                _ => (None, self.context.i32_type().const_zero()),
+            },
+        };
+
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let switch_block = self.builder.get_insert_block().unwrap();
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        let mut arm_blocks = Vec::new();
+        for arm in &expr.arms {
+            let block = self.context.append_basic_block(function, "match_arm");
+            match &arm.pattern {
+                Pattern::Any(_, _) => default = Some(block),
+                Pattern::Literal(value, span) => {
+                    let value = self.build_constant(&Value::Literal(*value), &type_, *span)?.into_int_value();
+                    cases.push((value, block));
+                }
+                Pattern::Variant { enum_name, variant, .. } => {
+                    let index = self.enum_table[enum_name].variants.iter().position(|(name, _)| name == variant).unwrap();
+                    cases.push((self.context.i32_type().const_int(index as u64, false), block));
                 }
             }
-            value::LiteralValue::Float(value) => {
-                match value {
-                    value::FloatValue::F32(value) => self.context.f32_type().const_float(value as f64).into(),
-                    value::FloatValue::F64(value) => self.context.f64_type().const_float(value).into(),
+            arm_blocks.push(block);
+        }
+
+        let end_block = self.context.append_basic_block(function, "match_end");
+
+        // Without a catch-all arm the type checker has made sure that the
+        // cases cover every value.
+        let default = match default {
+            Some(default) => default,
+            None => {
+                let block = self.context.append_basic_block(function, "match_unreachable");
+                self.builder.position_at_end(block);
+                self.builder.build_unreachable();
+                block
+            }
+        };
+
+        self.builder.position_at_end(switch_block);
+        self.builder.build_switch(tag, default, &cases);
+
+        let mut has_value = true;
+        let mut incoming = Vec::new();
+        for (arm, block) in expr.arms.iter().zip(arm_blocks) {
+            self.builder.position_at_end(block);
+            self.enter_scope();
+
+            match &arm.pattern {
+                Pattern::Any(Some(name), _) => {
+                    let alloca = self.build_entry_alloca(scrutinee.get_type(), name);
+                    self.builder.build_store(alloca, scrutinee);
+                    let variable_info = VariableInfo {
+                        type_: type_.clone(),
+                        alloca,
+                    };
+                    if let Some(scope) = self.symbol_table.last_mut() {
+                        scope.insert(name.clone(), variable_info);
+                    }
+                }
+                Pattern::Variant { enum_name, variant, fields, .. } if !fields.is_empty() => {
+                    let index = self.enum_table[enum_name].variants.iter().position(|(name, _)| name == variant).unwrap();
+                    let types = self.enum_table[enum_name].variants[index].1.clone();
+                    let (payload, payload_type) = self.payload_address(enum_name, index, address.unwrap())?;
+
+                    // The names are bound to the fields of the copy of the
+                    // scrutinee, which the arm may change freely.
+                    for (i, ((name, _), field_type)) in fields.iter().zip(types).enumerate() {
+                        if let Some(name) = name {
+                            let alloca = self.builder.build_struct_gep(payload_type, payload, i as u32, name).unwrap();
+                            let variable_info = VariableInfo {
+                                type_: field_type,
+                                alloca,
+                            };
+                            if let Some(scope) = self.symbol_table.last_mut() {
+                                scope.insert(name.clone(), variable_info);
+                            }
+                        }
+                    }
                 }
+                _ => {}
             }
-            value::LiteralValue::Bool(value) => self.context.bool_type().const_int(value as u64, false).into(),
-            value::LiteralValue::Char(value) => self.context.i8_type().const_int(value as u64, false).into(),
+
+            let result = match &arm.body {
+                ArmBody::Expr(body) => match body.accept(&mut CompileTimeEvaluator::new()) {
+                    // Constant arms are built once the type of the match is
+                    // known, as constants need no code of their own.
+                    Ok(constant) => Ok(Some((constant, self.builder.get_insert_block().unwrap()))),
+                    Err(_) => body.accept(self).map(|value| Some((value, self.builder.get_insert_block().unwrap()))),
+                },
+                ArmBody::Block(body) => {
+                    has_value = false;
+                    self.generate_stmt(body.as_ref()).map(|_| None)
+                }
+            };
+            self.exit_scope();
+            incoming.extend(result?);
+
+            self.builder.build_unconditional_branch(end_block);
+        }
+
+        self.builder.position_at_end(end_block);
+
+        if !has_value {
+            return Ok(Value::new(self.context.i32_type().const_zero(), Type::Void));
         }
+
+        self.build_join(&incoming, expected_type, "match", expr.span)
     }
 
-    fn visit_variable_expr(&mut self, expr: &VariableExpr) -> Value<'ctx> {
-        let name = &expr.name;
+    fn visit_block_expr(&mut self, expr: &BlockExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let expected_type = self.expected_type.take();
+
+        self.enter_scope();
+        let result = expr.stmts.iter()
+            .try_for_each(|stmt| self.generate_stmt(stmt.as_ref()))
+            .and_then(|_| match (&expr.value, expected_type) {
+                (Some(value), Some(expected_type)) => self.build_initializer(value.as_ref(), &expected_type),
+                (Some(value), None) => value.accept(self),
+                (None, _) => Ok(Value::new(self.context.i32_type().const_zero(), Type::Void)),
+            });
+        self.exit_scope();
+
+        result
+    }
+
+    fn visit_if_expr(&mut self, expr: &IfExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let expected_type = self.expected_type.take();
+
+        let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let then_block = self.context.append_basic_block(function, "then");
+        let end_block = self.context.append_basic_block(function, "if_end");
+        let else_block = if expr.otherwise.is_some() {
+            self.context.append_basic_block(function, "else")
+        } else {
+            end_block
+        };
+
+        let condition = expr.cond.accept(self)?.as_llvm_basic_value_enum().into_int_value();
+        self.builder.build_conditional_branch(condition, then_block, else_block);
 
-        if let Some(variable_info) = self.get_variable_info(name) {
-            let alloca = variable_info.alloca;
-            return self.builder.build_load(variable_info.type_, alloca, &name).into();
+        let mut incoming = Vec::new();
+        for (branch, block) in std::iter::once(&expr.then).chain(&expr.otherwise).zip([then_block, else_block]) {
+            self.builder.position_at_end(block);
+            // Without a required type, constant branches are built once the
+            // type of the `if` is known, as they are for a `match`.
+            let value = match &expected_type {
+                Some(expected_type) => self.build_initializer(branch.as_ref(), expected_type)?,
+                None => match branch.accept(&mut CompileTimeEvaluator::new()) {
+                    Ok(constant) => constant,
+                    Err(_) => branch.accept(self)?,
+                },
+            };
+            incoming.push((value, self.builder.get_insert_block().unwrap()));
+            self.builder.build_unconditional_branch(end_block);
         }
 
+        self.builder.position_at_end(end_block);
+
+        if expr.otherwise.is_none() {
+            return Ok(Value::new(self.context.i32_type().const_zero(), Type::Void));
+        }
+
+        self.build_join(&incoming, expected_type, "if", expr.span)
+    }
+
+    fn visit_loop_expr(&mut self, expr: &LoopExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let expected_type = self.expected_type.take();
+
         let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-        if let Some(param) = self.function_table.get(&function).unwrap().params.get(name) {
-            return param.clone().into();
+        let body_block = self.context.append_basic_block(function, "loop");
+        let end_block = self.context.append_basic_block(function, "loop_end");
+
+        self.builder.build_unconditional_branch(body_block);
+        self.builder.position_at_end(body_block);
+
+        let break_values = self.generate_loop_body(expr.label.clone(), end_block, body_block, expr.body.as_ref())?;
+        self.builder.build_unconditional_branch(body_block);
+
+        self.builder.position_at_end(end_block);
+
+        self.build_join(&break_values, expected_type, "loop", expr.span)
+    }
+
+    fn visit_address_of_expr(&mut self, expr: &AddressOfExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        self.expected_type = None;
+        let (address, type_) = self.lvalue_address(&expr.target)?;
+        let kind = if expr.mutable { PointerKind::Mutable } else { PointerKind::Shared };
+
+        Ok(Value::new(address, Type::Pointer(Box::new(type_), kind)))
+    }
+
+    fn visit_deref_expr(&mut self, expr: &DerefExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let (address, type_) = self.deref_address(expr)?;
+        let value = self.builder.build_load(self.get_type(&type_)?, address, "deref");
+
+        Ok(Value::new(value, type_))
+    }
+
+    fn visit_cast_expr(&mut self, expr: &CastExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        if let Ok(constant) = expr.accept(&mut CompileTimeEvaluator::new()) {
+            return Ok(Value::new(self.build_constant(&constant, &expr.type_, expr.span)?, expr.type_.clone()));
         }
 
-        panic!("Variable '{}' not found in current scope", name);
+        self.expected_type = None;
+        let value = expr.expr.accept(self)?;
+        let found = value.get_type();
+        let value = value.as_llvm_basic_value_enum();
+        let type_ = expr.type_.clone();
+
+        let value: BasicValueEnum = match (found.numeric(), type_.numeric()) {
+            _ if found == type_ => value,
+            // A `bool` is an `i1`, which zero extends to 0 or 1.
+            (None, Some(LiteralType::Int(int_type))) => self.build_int_cast(value.into_int_value(), false, int_type).into(),
+            (Some(LiteralType::Int(from)), Some(LiteralType::Int(to))) => self.build_int_cast(value.into_int_value(), from.is_signed(), to).into(),
+            (Some(LiteralType::Int(from)), Some(LiteralType::Float(_))) => {
+                let float_type = self.get_type(&type_)?.into_float_type();
+                self.build_int_to_float(value.into_int_value(), from.is_signed(), float_type).into()
+            }
+            (Some(LiteralType::Float(_)), Some(LiteralType::Int(to))) => self.build_float_to_int(value.into_float_value(), to).into(),
+            (Some(LiteralType::Float(from)), Some(LiteralType::Float(to))) => {
+                let float_type = self.get_type(&type_)?.into_float_type();
+                if from == type_::FloatType::F32 && to == type_::FloatType::F64 {
+                    self.builder.build_float_ext(value.into_float_value(), float_type, "float_ext").into()
+                } else {
+                    self.builder.build_float_trunc(value.into_float_value(), float_type, "float_trunc").into()
+                }
+            }
+            _ => return Err(Diagnostic::error(format!("cannot cast `{}` as `{}`", found, type_)).with_span(expr.span)),
+        };
+
+        Ok(Value::new(value, type_))
+    }
+
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<Value<'ctx>, Diagnostic> {
+        self.build_folded(&Value::Literal(expr.value), expr.span)
     }
-    
-    fn visit_var_assign_expr(&mut self, expr: &VarAssignExpr<'ctx>) -> Value<'ctx> {
+
+    /// Builds a string literal as a global constant array of its bytes. The
+    /// bytes are followed by a terminating zero, which is not counted in the
+    /// length of the `str`, so that they can also be handed to C functions
+    /// such as `puts`.
+    fn visit_string_expr(&mut self, expr: &StringExpr) -> Result<Value<'ctx>, Diagnostic> {
+        let pointer = self.builder.build_global_string_ptr(&expr.value, "str").as_pointer_value();
+        let len = self.context.i64_type().const_int(expr.value.len() as u64, false);
+        let value = self.context.const_struct(&[pointer.into(), len.into()], false);
+
+        Ok(Value::new(value, Type::Str))
+    }
+
+    fn visit_variable_expr(&mut self, expr: &VariableExpr) -> Result<Value<'ctx>, Diagnostic> {
         let name = &expr.name;
-        let value = expr.value.accept(self);
+        let variable_info = self.get_variable_info(name).unwrap();
+        let value = self.builder.build_load(self.get_type(&variable_info.type_)?, variable_info.alloca, name);
 
-        for scope in self.symbol_table.iter().rev() {
-            if let Some(variable_info) = scope.get(name) {
-                let alloca = variable_info.alloca;
-                self.builder.build_store(alloca, value.as_llvm_basic_value_enum());
-                return value;
+        Ok(Value::new(value, variable_info.type_.clone()))
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let (address, type_) = self.lvalue_address(&expr.target)?;
+
+        let value = match expr.op.kind.compound_operator() {
+            Some(kind) => {
+                let current = self.builder.build_load(self.get_type(&type_)?, address, "current");
+                let value = self.build_operand(expr.value.as_ref())?;
+                let op = Token::new(kind, expr.op.span);
+                self.build_binary(Value::new(current, type_.clone()), value, &op, expr.span)?
             }
-        }
+            None => self.build_initializer(expr.value.as_ref(), &type_)?,
+        };
+
+        self.builder.build_store(address, value.as_llvm_basic_value_enum());
 
-        panic!("Variable '{}' not found in current scope", name);
+        Ok(value)
     }
 
-    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Value<'ctx> {
-        let operand = expr.right.accept(self);
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        if let Ok(constant) = expr.accept(&mut CompileTimeEvaluator::new()) {
+            return self.build_folded(&constant, expr.span);
+        }
 
-        match operand {
-            Value::LLVMBasicValueEnum(BasicValueEnum::IntValue(value)) => self.visit_unary_expr_int(value.into(), expr),
-            Value::LLVMBasicValueEnum(BasicValueEnum::FloatValue(value)) => self.visit_unary_expr_float(value.into(), expr),
-            _ => panic!("Unexpected token"),
+        let operand = expr.right.accept(self)?;
+        let type_ = operand.get_type();
+        let value = operand.as_llvm_basic_value_enum();
+
+        if type_ == Type::Literal(LiteralType::Bool) {
+            return Ok(Value::new(self.builder.build_not(value.into_int_value(), "not"), type_));
+        }
+
+        match type_.numeric() {
+            Some(LiteralType::Int(int_type)) => self.visit_unary_expr_int(IntegerValue::LLVMInt(value.into_int_value(), int_type), expr),
+            Some(LiteralType::Float(float_type)) => self.visit_unary_expr_float(FloatingValue::LLVMFloat(value.into_float_value(), float_type), expr),
+            _ => unreachable!(),
         }
     }
 
-    fn visit_unary_expr_int(&mut self, value: IntegerValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Value<'ctx> {
+    fn visit_unary_expr_int(&mut self, value: IntegerValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
 
+        let int_type = value.get_type();
+        let type_ = Type::Literal(LiteralType::Int(int_type));
         let value: IntValue = value.into();
 
         match expr.op.kind {
-            TokenKind::Minus => self.builder.build_int_neg(value, "neg").into(),
-            TokenKind::Plus => value.into(),
-            _ => panic!("Unexpected token"),
+            TokenKind::Minus => Ok(Value::new(self.builder.build_int_neg(value, "neg"), type_)),
+            TokenKind::Plus => Ok(Value::new(value, type_)),
+            TokenKind::Bang | TokenKind::Tilde => Ok(Value::new(self.builder.build_not(value, "not"), type_)),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", expr.op.kind)).with_span(expr.span)),
         }
     }
 
-    fn visit_unary_expr_float(&mut self, value: FloatingValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Value<'ctx> {
+    fn visit_unary_expr_float(&mut self, value: FloatingValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
 
+        let type_ = Type::Literal(LiteralType::Float(value.get_type()));
         let value: FloatValue = value.into();
 
         match expr.op.kind {
-            TokenKind::Minus => self.builder.build_float_neg(value, "neg").into(),
-            TokenKind::Plus => value.into(),
-            _ => panic!("Unexpected token"),
+            TokenKind::Minus => Ok(Value::new(self.builder.build_float_neg(value, "neg"), type_)),
+            TokenKind::Plus => Ok(Value::new(value, type_)),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", expr.op.kind)).with_span(expr.span)),
         }
     }
 
-    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Value<'ctx> {
-        let left = expr.left.accept(self).as_llvm_basic_value_enum();
-        let right = expr.right.accept(self).as_llvm_basic_value_enum();
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        if let Ok(constant) = expr.accept(&mut CompileTimeEvaluator::new()) {
+            return self.build_folded(&constant, expr.span);
+        }
 
-        match (left, right) {
-            (BasicValueEnum::IntValue(left), BasicValueEnum::IntValue(right)) => self.visit_binary_expr_int_int(left.into(), right.into(), expr),
-            (BasicValueEnum::IntValue(left), BasicValueEnum::FloatValue(right)) => self.visit_binary_expr_int_float(left.into(), right.into(), expr),
-            (BasicValueEnum::FloatValue(left), BasicValueEnum::IntValue(right)) => self.visit_binary_expr_float_int(left.into(), right.into(), expr),
-            (BasicValueEnum::FloatValue(left), BasicValueEnum::FloatValue(right)) => self.visit_binary_expr_float_float(left.into(), right.into(), expr),
-            _ => panic!("Unexpected token: left: {:?}, right: {:?}", left, right),
+        let left = self.build_operand(expr.left.as_ref())?;
+        let right = self.build_operand(expr.right.as_ref())?;
+
+        self.build_binary(left, right, &expr.op, expr.span)
+    }
+
+    fn visit_binary_expr_int_int(&mut self, left: IntegerValue<'ctx>, right: IntegerValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+
+        // The result of a shift has the type of its left operand, so the
+        // operands are not unified first. `>>` is an arithmetic shift on
+        // signed integers and a logical one on unsigned integers.
+        if let TokenKind::ShiftLeft | TokenKind::ShiftRight = op.kind {
+            let left_type = left.get_type();
+            let left = self.build_integer(left, left_type);
+            let right = self.build_integer(right, right.get_type());
+            let value = self.build_shift(left, right, &op.kind, left_type.is_signed());
+            return Ok(Value::new(value, Type::Literal(LiteralType::Int(left_type))));
+        }
+
+        let (left, right, int_type) = self.unify_ints(left, right);
+        let signed = int_type.is_signed();
+        let type_ = Type::Literal(LiteralType::Int(int_type));
+        let bool_type = Type::Literal(LiteralType::Bool);
+
+        let predicate = |signed_predicate, unsigned_predicate| if signed { signed_predicate } else { unsigned_predicate };
+
+        match op.kind {
+            TokenKind::Plus => Ok(Value::new(self.builder.build_int_add(left, right, "add"), type_)),
+            TokenKind::Minus => Ok(Value::new(self.builder.build_int_sub(left, right, "sub"), type_)),
+            TokenKind::Asterisk => Ok(Value::new(self.builder.build_int_mul(left, right, "mul"), type_)),
+            TokenKind::Slash | TokenKind::Remainder => Ok(Value::new(self.build_int_division(left, right, &op.kind, signed), type_)),
+            TokenKind::Ampersand => Ok(Value::new(self.builder.build_and(left, right, "and"), type_)),
+            TokenKind::Pipe => Ok(Value::new(self.builder.build_or(left, right, "or"), type_)),
+            TokenKind::Caret => Ok(Value::new(self.builder.build_xor(left, right, "xor"), type_)),
+            TokenKind::Greater => Ok(Value::new(self.builder.build_int_compare(predicate(IntPredicate::SGT, IntPredicate::UGT), left, right, "gt"), bool_type)),
+            TokenKind::GreaterEqual => Ok(Value::new(self.builder.build_int_compare(predicate(IntPredicate::SGE, IntPredicate::UGE), left, right, "ge"), bool_type)),
+            TokenKind::Less => Ok(Value::new(self.builder.build_int_compare(predicate(IntPredicate::SLT, IntPredicate::ULT), left, right, "lt"), bool_type)),
+            TokenKind::LessEqual => Ok(Value::new(self.builder.build_int_compare(predicate(IntPredicate::SLE, IntPredicate::ULE), left, right, "le"), bool_type)),
+            TokenKind::Equal => Ok(Value::new(self.builder.build_int_compare(IntPredicate::EQ, left, right, "eq"), bool_type)),
+            TokenKind::NotEqual => Ok(Value::new(self.builder.build_int_compare(IntPredicate::NE, left, right, "ne"), bool_type)),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", op.kind)).with_span(span)),
         }
     }
 
-    fn visit_binary_expr_int_int(&mut self, left: IntegerValue<'ctx>, right: IntegerValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> {
+    fn visit_binary_expr_int_float(&mut self, left: IntegerValue<'ctx>, right: FloatingValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> {
 
-        let left: IntValue = left.into();
-        let right: IntValue = right.into();
+        let left = self.build_int_operand_to_float(left, right.get_type())?;
 
-        if left.get_type() != right.get_type() {
-            if left.get_type().get_bit_width() > right.get_type().get_bit_width() {
-                let right = self.builder.build_int_z_extend(right, left.get_type(), "z_extend");
-                return self.visit_binary_expr_int_int(left.into(), right.into(), expr);
-            } else {
-                let left = self.builder.build_int_z_extend(left, right.get_type(), "z_extend");
-                return self.visit_binary_expr_int_int(left.into(), right.into(), expr);
+        self.visit_binary_expr_float_float(left, right, op, span)
+    }
+
+    fn visit_binary_expr_float_int(&mut self, left: FloatingValue<'ctx>, right: IntegerValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+
+        let right = self.build_int_operand_to_float(right, left.get_type())?;
+
+        self.visit_binary_expr_float_float(left, right, op, span)
+    }
+
+    fn visit_binary_expr_float_float(&mut self, left: FloatingValue<'ctx>, right: FloatingValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+
+        // A constant meeting an `f32` that is not one is rounded to `f32`, as
+        // the type checker decides.
+        let narrows = |constant: &FloatingValue<'ctx>, other: &FloatingValue<'ctx>| {
+            matches!(other, FloatingValue::LLVMFloat(..))
+                && matches!(constant, FloatingValue::Float(constant) if (constant.as_f64() as f32).is_finite())
+        };
+        let float_type = match (left.get_type(), right.get_type()) {
+            (type_::FloatType::F64, type_::FloatType::F32) if narrows(&left, &right) => type_::FloatType::F32,
+            (type_::FloatType::F32, type_::FloatType::F64) if narrows(&right, &left) => type_::FloatType::F32,
+            (type_::FloatType::F64, _) | (_, type_::FloatType::F64) => type_::FloatType::F64,
+            _ => type_::FloatType::F32,
+        };
+        let type_ = Type::Literal(LiteralType::Float(float_type));
+        let bool_type = Type::Literal(LiteralType::Bool);
+
+        let llvm_type = self.get_type(&type_)?.into_float_type();
+        let convert = |value: FloatingValue<'ctx>| -> FloatValue<'ctx> {
+            match (value, float_type) {
+                (FloatingValue::Float(constant), _) => llvm_type.const_float(constant.as_f64()),
+                (FloatingValue::LLVMFloat(value, from), to) if from == to => value,
+                (FloatingValue::LLVMFloat(value, _), type_::FloatType::F64) => self.builder.build_float_ext(value, llvm_type, "float_ext"),
+                (FloatingValue::LLVMFloat(value, _), _) => self.builder.build_float_trunc(value, llvm_type, "float_trunc"),
             }
+        };
+        let left = convert(left);
+        let right = convert(right);
+
+        match op.kind {
+            TokenKind::Plus => Ok(Value::new(self.builder.build_float_add(left, right, "add"), type_)),
+            TokenKind::Minus => Ok(Value::new(self.builder.build_float_sub(left, right, "sub"), type_)),
+            TokenKind::Asterisk => Ok(Value::new(self.builder.build_float_mul(left, right, "mul"), type_)),
+            TokenKind::Slash => Ok(Value::new(self.builder.build_float_div(left, right, "div"), type_)),
+            TokenKind::Remainder => Ok(Value::new(self.builder.build_float_rem(left, right, "rem"), type_)),
+            TokenKind::Greater => Ok(Value::new(self.builder.build_float_compare(FloatPredicate::OGT, left, right, "gt"), bool_type)),
+            TokenKind::GreaterEqual => Ok(Value::new(self.builder.build_float_compare(FloatPredicate::OGE, left, right, "ge"), bool_type)),
+            TokenKind::Less => Ok(Value::new(self.builder.build_float_compare(FloatPredicate::OLT, left, right, "lt"), bool_type)),
+            TokenKind::LessEqual => Ok(Value::new(self.builder.build_float_compare(FloatPredicate::OLE, left, right, "le"), bool_type)),
+            TokenKind::Equal => Ok(Value::new(self.builder.build_float_compare(FloatPredicate::OEQ, left, right, "eq"), bool_type)),
+            TokenKind::NotEqual => Ok(Value::new(self.builder.build_float_compare(FloatPredicate::UNE, left, right, "ne"), bool_type)),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", op.kind)).with_span(span)),
         }
+    }
 
-        match expr.op.kind {
-            TokenKind::Plus => self.builder.build_int_add(left, right, "add").into(),
-            TokenKind::Minus => self.builder.build_int_sub(left, right, "sub").into(),
-            TokenKind::Asterisk => self.builder.build_int_mul(left, right, "mul").into(),
-            TokenKind::Slash => {
-                let left = self.builder.build_signed_int_to_float(left, self.context.f64_type(), "int_to_float");
-                let right = self.builder.build_signed_int_to_float(right, self.context.f64_type(), "int_to_float");
-
-                if right.is_null() {
-                    panic!("Division by zero");
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let bool_type = self.context.bool_type();
+        let type_ = Type::Literal(LiteralType::Bool);
+
+        // A constant left operand either decides the result on its own or
+        // reduces the expression to its right operand, so no branch is needed.
+        if let Ok(Value::Literal(LiteralValue::Bool(left))) = expr.left.accept(&mut CompileTimeEvaluator::new()) {
+            return match (&expr.op.kind, left) {
+                (TokenKind::And, false) | (TokenKind::Or, true) => Ok(Value::new(bool_type.const_int(left as u64, false), type_)),
+                _ => {
+                    expr.right.accept(self)
                 }
+            };
+        }
 
-                self.builder.build_float_div(left, right, "div").into()
+        let left = expr.left.accept(self)?.as_llvm_basic_value_enum().into_int_value();
+
+        let left_block = self.builder.get_insert_block().unwrap();
+        let function = left_block.get_parent().unwrap();
+        let right_block = self.context.append_basic_block(function, "logical_rhs");
+        let end_block = self.context.append_basic_block(function, "logical_end");
+
+        let short_circuit = match expr.op.kind {
+            TokenKind::And => {
+                self.builder.build_conditional_branch(left, right_block, end_block);
+                bool_type.const_zero()
+            }
+            TokenKind::Or => {
+                self.builder.build_conditional_branch(left, end_block, right_block);
+                bool_type.const_all_ones()
             }
-            TokenKind::Remainder => self.builder.build_int_signed_rem(left, right, "rem").into(),
-            TokenKind::Greater => self.builder.build_int_compare(inkwell::IntPredicate::SGT, left, right, "gt").into(),
-            TokenKind::GreaterEqual => self.builder.build_int_compare(inkwell::IntPredicate::SGE, left, right, "ge").into(),
-            TokenKind::Less => self.builder.build_int_compare(inkwell::IntPredicate::SLT, left, right, "lt").into(),
-            TokenKind::LessEqual => self.builder.build_int_compare(inkwell::IntPredicate::SLE, left, right, "le").into(),
-            TokenKind::Equal => self.builder.build_int_compare(inkwell::IntPredicate::EQ, left, right, "eq").into(),
-            TokenKind::NotEqual => self.builder.build_int_compare(inkwell::IntPredicate::NE, left, right, "ne").into(),
-            _ => panic!("Unexpected token"),
+            _ => return Err(Diagnostic::error(format!("unexpected operator {}", expr.op.kind)).with_span(expr.span)),
+        };
+
+        self.builder.position_at_end(right_block);
+        let right = expr.right.accept(self)?.as_llvm_basic_value_enum().into_int_value();
+        // The right operand may have branched itself, so the phi has to name
+        // the block it finished in rather than `right_block`.
+        let right_block = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end_block);
+
+        self.builder.position_at_end(end_block);
+        let phi = self.builder.build_phi(bool_type, "logical");
+        phi.add_incoming(&[(&short_circuit, left_block), (&right, right_block)]);
+
+        Ok(Value::new(phi.as_basic_value(), type_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use inkwell::context::Context;
+
+    use crate::frontend::{lexer::Lexer, parser::Parser, source_map::SourceMap};
+    use crate::semantic::TypeChecker;
+
+    use super::CodeGenerator;
+
+    /// Generates the module of `source`, which has to type check, and
+    /// returns its IR, or the messages of the diagnostics.
+    fn generate(source: &str, bounds_checks: bool) -> Result<String, Vec<String>> {
+        let context = Context::create();
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file("test.pr".to_string(), source.to_string());
+
+        let mut parser = Parser::new(Lexer::new(source.to_string(), file_id));
+        let (stmts, diagnostics) = parser.parse();
+        assert!(diagnostics.is_empty());
+        assert!(TypeChecker::new().check(&stmts).is_ok());
+
+        let module = context.create_module("test");
+        let mut generator = CodeGenerator::new(&context, module, context.create_builder(), source_map, bounds_checks);
+        let diagnostics: Vec<_> = stmts.iter()
+            .filter_map(|stmt| generator.generate_code(stmt.as_ref()).err())
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
         }
+
+        let module = generator.get_module();
+        module.verify().map_err(|error| vec![error.to_string()])?;
+        Ok(module.print_to_string().to_string())
     }
 
-    fn visit_binary_expr_int_float(&mut self, left: IntegerValue<'ctx>, right: FloatingValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> {
+    /// The IR of the function `name` in `source`.
+    fn function(source: &str, name: &str) -> String {
+        let ir = generate(source, true).unwrap();
+        let start = ir.find(&format!("@{}(", name)).unwrap();
+        let end = ir[start..].find("\n}\n").unwrap();
+        ir[start..start + end].to_string()
+    }
 
-        let left: IntValue = left.into();
-        let right: FloatValue = right.into();
+    #[test]
+    fn promotes_variables_to_registers() {
+        let ir = function("fn f(a: i32) -> i32 { let b = a + 1; b *= 2; return b; }", "f");
+        assert!(!ir.contains("alloca"), "{}", ir);
+    }
 
-        let left = self.builder.build_signed_int_to_float(left, right.get_type(), "int_to_float");
+    #[test]
+    fn folds_constant_expressions() {
+        assert!(function("fn f() -> i32 { return 1 + 2 * 3; }", "f").contains("ret i32 7"));
+        assert!(function("fn f() -> f64 { return 1.0 / 0.0; }", "f").contains("ret double 0x7FF0000000000000"));
+    }
 
-        match expr.op.kind {
-            TokenKind::Plus => self.builder.build_float_add(left, right, "add").into(),
-            TokenKind::Minus => self.builder.build_float_sub(left, right, "sub").into(),
-            TokenKind::Asterisk => self.builder.build_float_mul(left, right, "mul").into(),
-            TokenKind::Slash => {
-                if right.is_null() {
-                    panic!("Division by zero");
-                }
-                self.builder.build_float_div(left, right, "div").into()
-            }
-            TokenKind::Remainder => self.builder.build_float_rem(left, right, "rem").into(),
-            TokenKind::Greater => self.builder.build_float_compare(FloatPredicate::OGT, left, right, "gt").into(),
-            TokenKind::GreaterEqual => self.builder.build_float_compare(FloatPredicate::OGE, left, right, "ge").into(),
-            TokenKind::Less => self.builder.build_float_compare(FloatPredicate::OLT, left, right, "lt").into(),
-            TokenKind::LessEqual => self.builder.build_float_compare(FloatPredicate::OLE, left, right, "le").into(),
-            TokenKind::Equal => self.builder.build_float_compare(FloatPredicate::OEQ, left, right, "eq").into(),
-            TokenKind::NotEqual => self.builder.build_float_compare(FloatPredicate::ONE, left, right, "ne").into(),
-            _ => panic!("Unexpected token"),
-        } 
+    #[test]
+    fn evaluates_the_right_operand_of_a_logical_operator_only_when_needed() {
+        let ir = function("fn g() -> bool; fn f(a: bool) -> bool { return a && g(); }", "f");
+        let branch = ir.find("br i1 %").unwrap();
+        let call = ir.find("call i1 @g()").unwrap();
+        assert!(branch < call, "{}", ir);
+        assert!(ir.contains("phi i1"), "{}", ir);
     }
 
-    fn visit_binary_expr_float_int(&mut self, left: FloatingValue<'ctx>, right: IntegerValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> {
+    #[test]
+    fn follows_the_signedness_of_the_operands() {
+        let signed = function("fn f(a: i32, b: i32) -> bool { return a / b > a >> b; }", "f");
+        assert!(signed.contains("sdiv") && signed.contains("ashr") && signed.contains("icmp sgt"), "{}", signed);
 
-        let left: FloatValue = left.into();
-        let right: IntValue = right.into();
+        let unsigned = function("fn f(a: u32, b: u32) -> bool { return a / b > a >> b; }", "f");
+        assert!(unsigned.contains("udiv") && unsigned.contains("lshr") && unsigned.contains("icmp ugt"), "{}", unsigned);
+    }
 
-        let right = self.builder.build_signed_int_to_float(right, left.get_type(), "int_to_float");
+    #[test]
+    fn traps_on_integer_division_by_zero() {
+        let signed = function("fn f(a: i8, b: i8) -> i8 { return a % b; }", "f");
+        assert!(signed.contains("call void @llvm.trap()") && signed.contains("is_min"), "{}", signed);
 
-        match expr.op.kind {
-            TokenKind::Plus => self.builder.build_float_add(left, right, "add").into(),
-            TokenKind::Minus => self.builder.build_float_sub(left, right, "sub").into(),
-            TokenKind::Asterisk => self.builder.build_float_mul(left, right, "mul").into(),
-            TokenKind::Slash => {
-                if right.is_null() {
-                    panic!("Division by zero");
-                }
-                self.builder.build_float_div(left, right, "div").into()
-            }
-            TokenKind::Remainder => self.builder.build_float_rem(left, right, "rem").into(),
-            TokenKind::Greater => self.builder.build_float_compare(FloatPredicate::OGT, left, right, "gt").into(),
-            TokenKind::GreaterEqual => self.builder.build_float_compare(FloatPredicate::OGE, left, right, "ge").into(),
-            TokenKind::Less => self.builder.build_float_compare(FloatPredicate::OLT, left, right, "lt").into(),
-            TokenKind::LessEqual => self.builder.build_float_compare(FloatPredicate::OLE, left, right, "le").into(),
-            TokenKind::Equal => self.builder.build_float_compare(FloatPredicate::OEQ, left, right, "eq").into(),
-            TokenKind::NotEqual => self.builder.build_float_compare(FloatPredicate::ONE, left, right, "ne").into(),
-            _ => panic!("Unexpected token"),
-        } 
+        let unsigned = function("fn f(a: u8, b: u8) -> u8 { return a / b; }", "f");
+        assert!(unsigned.contains("call void @llvm.trap()") && !unsigned.contains("is_min"), "{}", unsigned);
+
+        let float = function("fn f(a: f64, b: f64) -> f64 { return a / b; }", "f");
+        assert!(!float.contains("trap"), "{}", float);
     }
 
-    fn visit_binary_expr_float_float(&mut self, left: FloatingValue<'ctx>, right: FloatingValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> {
+    #[test]
+    fn converts_with_casts() {
+        let ir = function("fn f(a: f64, b: u8, c: i64) -> f32 { return (a as i32) as f32 + b as f32 + (c as i8) as f32; }", "f");
+        assert!(ir.contains("fptosi") && ir.contains("uitofp") && ir.contains("sitofp") && ir.contains("trunc"), "{}", ir);
+    }
 
-        let left: FloatValue = left.into();
-        let right: FloatValue = right.into();
-        
-        match expr.op.kind {
-            TokenKind::Plus => self.builder.build_float_add(left, right, "add").into(),
-            TokenKind::Minus => self.builder.build_float_sub(left, right, "sub").into(),
-            TokenKind::Asterisk => self.builder.build_float_mul(left, right, "mul").into(),
-            TokenKind::Slash => {
-                if right.is_null() {
-                    panic!("Division by zero");
-                }
-                self.builder.build_float_div(left, right, "div").into()
-            }
-            TokenKind::Remainder => self.builder.build_float_rem(left, right, "rem").into(),
-            TokenKind::Greater => self.builder.build_float_compare(FloatPredicate::OGT, left, right, "gt").into(),
-            TokenKind::GreaterEqual => self.builder.build_float_compare(FloatPredicate::OGE, left, right, "ge").into(),
-            TokenKind::Less => self.builder.build_float_compare(FloatPredicate::OLT, left, right, "lt").into(),
-            TokenKind::LessEqual => self.builder.build_float_compare(FloatPredicate::OLE, left, right, "le").into(),
-            TokenKind::Equal => self.builder.build_float_compare(FloatPredicate::OEQ, left, right, "eq").into(),
-            TokenKind::NotEqual => self.builder.build_float_compare(FloatPredicate::ONE, left, right, "ne").into(),
-            _ => panic!("Unexpected token"),
-        } 
+    #[test]
+    fn checks_indices_against_the_length() {
+        let source = "fn f(xs: [i32], i: i64) -> i32 {\n    return xs[i];\n}";
+
+        let ir = generate(source, true).unwrap();
+        assert!(ir.contains("call void @__proto_panic"), "{}", ir);
+        assert!(ir.contains("test.pr:2:12"), "{}", ir);
+        assert!(ir.contains("index out of bounds: the len is %llu but the index is %lld"), "{}", ir);
+
+        let ir = generate(source, false).unwrap();
+        assert!(!ir.contains("__proto_panic"), "{}", ir);
+    }
+
+    #[test]
+    fn reports_declarations_that_clash_with_the_panic_hook() {
+        let source = "fn abort(code: i32); fn f(xs: [i32], i: i64) -> i32 { return xs[i]; }";
+        assert_eq!(generate(source, true).unwrap_err(), [
+            "function `abort` is declared with a different signature than the C library function",
+        ]);
+        assert!(generate(source, false).is_ok());
+        assert!(generate("fn abort(); fn f(xs: [i32], i: i64) -> i32 { return xs[i]; }", true).is_ok());
+    }
+
+    #[test]
+    fn returns_nothing_from_a_bare_return() {
+        assert!(function("fn f(a: bool) { if a { return; } }", "f").contains("ret void"));
     }
 }
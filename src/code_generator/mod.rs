@@ -2,18 +2,51 @@ pub mod code_generator;
 
 use std::collections::HashMap;
 
-use inkwell::{builder::Builder, context::Context, values::{PointerValue, BasicValueEnum, FunctionValue}, types::BasicTypeEnum, basic_block::BasicBlock, module::Module};
+use inkwell::{builder::Builder, context::Context, values::{PointerValue, FunctionValue}, types::StructType, basic_block::BasicBlock, module::Module, passes::PassManager, targets::TargetData};
+
+use crate::frontend::source_map::SourceMap;
+use crate::frontend::span::Span;
+use crate::frontend::type_::Type;
+use crate::frontend::value::Value;
 
 #[derive(Eq, PartialEq)]
 pub struct VariableInfo<'ctx> {
-    type_: BasicTypeEnum<'ctx>,
+    type_: Type,
+    /// Where the variable is stored: an alloca for a local variable, or a
+    /// global variable for a `const` or `static`.
     alloca: PointerValue<'ctx>,
 }
 
 #[derive(Eq, PartialEq)]
-pub struct FunctionInfo<'ctx> {
-    params: HashMap<String, BasicValueEnum<'ctx>>,
-    return_type: Option<BasicTypeEnum<'ctx>>,
+pub struct FunctionInfo {
+    param_types: Vec<Type>,
+    return_type: Type,
+    span: Span,
+}
+
+#[derive(Eq, PartialEq)]
+pub struct StructInfo<'ctx> {
+    llvm_type: StructType<'ctx>,
+    fields: Vec<(String, Type)>,
+}
+
+/// An enum is laid out as an `i32` tag holding the index of the variant,
+/// followed by a payload large enough for the fields of any variant.
+#[derive(Eq, PartialEq)]
+pub struct EnumInfo<'ctx> {
+    llvm_type: StructType<'ctx>,
+    variants: Vec<(String, Vec<Type>)>,
+}
+
+/// A loop around the code being generated, which `break` and `continue`
+/// jump out of.
+pub struct LoopInfo<'ctx> {
+    label: Option<String>,
+    break_block: BasicBlock<'ctx>,
+    continue_block: BasicBlock<'ctx>,
+    /// The values the `break`s out of a `loop` give it, each with the block
+    /// it comes from.
+    break_values: Vec<(Value<'ctx>, BasicBlock<'ctx>)>,
 }
 
 pub struct CodeGenerator<'ctx> {
@@ -21,7 +54,20 @@ pub struct CodeGenerator<'ctx> {
     module: Module<'ctx>,
     builder: Builder<'ctx>,
     symbol_table: Vec<HashMap<String, VariableInfo<'ctx>>>,
-    function_table: HashMap<FunctionValue<'ctx>, FunctionInfo<'ctx>>,
-    break_block_stack: Vec<BasicBlock<'ctx>>,
-    continue_block_stack: Vec<BasicBlock<'ctx>>,
+    function_table: HashMap<FunctionValue<'ctx>, FunctionInfo>,
+    struct_table: HashMap<String, StructInfo<'ctx>>,
+    enum_table: HashMap<String, EnumInfo<'ctx>>,
+    target_data: TargetData,
+    /// The sources being compiled, which name the place a run-time check
+    /// failed at.
+    source_map: SourceMap,
+    /// Whether indices are checked against the length of what they index.
+    bounds_checks: bool,
+    /// The passes run over each function once it is generated, which turn
+    /// the stack slots of variables into SSA values.
+    pass_manager: PassManager<FunctionValue<'ctx>>,
+    /// The type required of the expression being built as an initializer,
+    /// which a `match` or `loop` whose values are all constant takes as its own.
+    expected_type: Option<Type>,
+    loop_stack: Vec<LoopInfo<'ctx>>,
 }
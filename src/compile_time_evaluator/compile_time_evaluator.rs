@@ -1,4 +1,7 @@
-use crate::frontend::{visitor::ExprVisitor, expr::{LiteralExpr, UnaryExpr, BinaryExpr, VariableExpr, VarAssignExpr, CallExpr, ListExpr, IndexExpr}, value::{Value, LiteralValue, IntegerValue, FloatingValue, IntValue, FloatValue}, token::TokenKind};
+use crate::diagnostic::Diagnostic;
+use crate::frontend::span::Span;
+use crate::frontend::type_::{Type, LiteralType, IntType};
+use crate::frontend::{visitor::ExprVisitor, expr::{LiteralExpr, UnaryExpr, BinaryExpr, LogicalExpr, VariableExpr, AssignExpr, CallExpr, ListExpr, RepeatExpr, IndexExpr, FieldExpr, StructExpr, VariantExpr, MatchExpr, LoopExpr, BlockExpr, IfExpr, StringExpr, CastExpr, AddressOfExpr, DerefExpr, SliceExpr}, value::{Value, LiteralValue, IntegerValue, FloatingValue, IntValue, FloatValue}, token::{Token, TokenKind}};
 
 use super::CompileTimeEvaluator;
 
@@ -6,143 +9,269 @@ impl CompileTimeEvaluator {
     pub fn new() -> Self {
         Self
     }
+
+    fn not_constant(span: Span) -> Diagnostic {
+        Diagnostic::error("expression cannot be evaluated at compile time").with_span(span)
+    }
+
+    /// The result of an operation on operands of type `type_`, which must
+    /// hold it.
+    fn typed<'ctx>(result: Option<i128>, type_: IntType, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+        match result {
+            Some(result) if IntValue::fits(result, type_) => Ok(IntValue::from_i128(result, type_).into()),
+            result => {
+                let error = Diagnostic::error("this arithmetic operation will overflow").with_span(span);
+                Err(match result {
+                    Some(result) => error.with_note(format!("the result `{}` does not fit into the type `{}` whose range is `{}..={}`", result, type_, type_.min_value(), type_.max_value())),
+                    None => error,
+                })
+            }
+        }
+    }
+
+    /// The result of an operation on untyped operands, which some integer
+    /// type must hold.
+    fn untyped<'ctx>(result: Option<i128>, span: Span) -> Result<Value<'ctx>, Diagnostic> {
+        match result.filter(|result| IntValue::fits_any(*result)) {
+            Some(result) => Ok(IntValue::Untyped(result).into()),
+            None => Err(Diagnostic::error("this arithmetic operation will overflow")
+                        .with_span(span)
+                        .with_note("the result does not fit into any integer type")),
+        }
+    }
+
+    fn as_bool(value: Value, span: Span) -> Result<bool, Diagnostic> {
+        match value {
+            Value::Literal(LiteralValue::Bool(value)) => Ok(value),
+            _ => Err(Diagnostic::error("mismatched types: expected `bool`").with_span(span)),
+        }
+    }
 }
 
 impl<'ctx> ExprVisitor<'ctx> for CompileTimeEvaluator {
 
-    fn visit_variable_expr(&mut self, _expr: &VariableExpr) -> Value<'ctx> { unimplemented!("CompileTimeEvaluator::visit_variable_expr") }
-    fn visit_var_assign_expr(&mut self, _expr: &VarAssignExpr<'ctx>) -> Value<'ctx> { unimplemented!("CompileTimeEvaluator::visit_var_assign_expr") }
+    fn visit_variable_expr(&mut self, expr: &VariableExpr) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_string_expr(&mut self, expr: &StringExpr) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_assign_expr(&mut self, expr: &AssignExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
 
-    fn visit_call_expr(&mut self, _expr: &CallExpr<'ctx>) -> Value<'ctx> { unimplemented!("CompileTimeEvaluator::visit_call_expr") }
-    fn visit_list_expr(&mut self, _expr: &ListExpr<'ctx>) -> Value<'ctx> { unimplemented!("CompileTimeEvaluator::visit_list_expr") }
-    fn visit_index_expr(&mut self, _expr: &IndexExpr<'ctx>) -> Value<'ctx> { unimplemented!("CompileTimeEvaluator::visit_index_expr") }
+    fn visit_call_expr(&mut self, expr: &CallExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_list_expr(&mut self, expr: &ListExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let values = expr.values.iter()
+            .map(|value| value.accept(self))
+            .collect::<Result<Vec<Value>, Diagnostic>>()?;
+        Ok(Value::List(values))
+    }
+    fn visit_repeat_expr(&mut self, expr: &RepeatExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let value = expr.value.accept(self)?;
+        Ok(Value::List(vec![value; expr.count]))
+    }
+    fn visit_index_expr(&mut self, expr: &IndexExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_field_expr(&mut self, expr: &FieldExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_struct_expr(&mut self, expr: &StructExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_variant_expr(&mut self, expr: &VariantExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_match_expr(&mut self, expr: &MatchExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_loop_expr(&mut self, expr: &LoopExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_address_of_expr(&mut self, expr: &AddressOfExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_deref_expr(&mut self, expr: &DerefExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+    fn visit_slice_expr(&mut self, expr: &SliceExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { Err(Self::not_constant(expr.span)) }
+
+    fn visit_block_expr(&mut self, expr: &BlockExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        match &expr.value {
+            Some(value) if expr.stmts.is_empty() => value.accept(self),
+            _ => Err(Self::not_constant(expr.span)),
+        }
+    }
+
+    /// An `if` is constant when its condition and both of its branches are,
+    /// so that the branch not taken is still one the type checker accepts.
+    fn visit_if_expr(&mut self, expr: &IfExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let cond = Self::as_bool(expr.cond.accept(self)?, expr.cond.span())?;
+        let otherwise = expr.otherwise.as_ref().ok_or_else(|| Self::not_constant(expr.span))?;
+
+        let then = expr.then.accept(self)?;
+        let otherwise = otherwise.accept(self)?;
+        if then.constant_type().zip(otherwise.constant_type()).and_then(|(then, otherwise)| then.join(otherwise)).is_none() {
+            return Err(Self::not_constant(expr.span));
+        }
+
+        Ok(if cond { then } else { otherwise })
+    }
+
+    /// Folds a cast between primitive types the way it is done at run time.
+    /// Any other cast is left for the type checker to reject.
+    fn visit_cast_expr(&mut self, expr: &CastExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let value = match expr.expr.accept(self)? {
+            Value::Literal(value) => value,
+            _ => return Err(Self::not_constant(expr.span)),
+        };
+
+        let value = match (value, &expr.type_) {
+            (LiteralValue::Int(value), Type::Literal(LiteralType::Int(type_))) => LiteralValue::Int(value.cast(*type_)),
+            (LiteralValue::Char(value), Type::Literal(LiteralType::Int(type_))) => LiteralValue::Int(IntValue::from_i128(value as i128, *type_)),
+            (LiteralValue::Bool(value), Type::Literal(LiteralType::Int(type_))) => LiteralValue::Int(IntValue::from_i128(value as i128, *type_)),
+            (LiteralValue::Int(IntValue::U8(value)), Type::Literal(LiteralType::Char)) => LiteralValue::Char(value as char),
+            (LiteralValue::Int(value), Type::Literal(LiteralType::Float(type_))) => LiteralValue::Float(FloatValue::from_int(value, *type_)),
+            (LiteralValue::Float(value), Type::Literal(LiteralType::Int(type_))) => LiteralValue::Int(IntValue::from_float(value, *type_)),
+            (LiteralValue::Float(value), Type::Literal(LiteralType::Float(type_))) => LiteralValue::Float(value.cast(*type_)),
+            (value, type_) if Value::Literal(value).get_type() == *type_ => value,
+            _ => return Err(Self::not_constant(expr.span)),
+        };
 
-    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Value<'ctx> { 
-        expr.value.into()
+        Ok(Value::Literal(value))
     }
 
-    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Value<'ctx> { 
-        let operand = expr.right.accept(self);
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<Value<'ctx>, Diagnostic> { 
+        Ok(expr.value.into())
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { 
+        let operand = expr.right.accept(self)?;
 
         match operand {
+            Value::Literal(LiteralValue::Bool(value)) if expr.op.kind == TokenKind::Bang => Ok((!value).into()),
             Value::Literal(LiteralValue::Int(value)) => self.visit_unary_expr_int(value.into(), expr),
             Value::Literal(LiteralValue::Float(value)) => self.visit_unary_expr_float(value.into(), expr),
-            _ => panic!("Unexpected token"),
+            _ => Err(Diagnostic::error(format!("cannot apply unary {} to this operand", expr.op.kind)).with_span(expr.span)),
         }
     }
 
-    fn visit_unary_expr_int(&mut self, value: IntegerValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Value<'ctx> { 
+    fn visit_unary_expr_int(&mut self, value: IntegerValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { 
 
-        match expr.op.kind {
-            TokenKind::Minus => {
-                let value: IntValue = value.into();
-                let value = -value;
-                value.into()
+        let value: IntValue = value.into();
+        match (&expr.op.kind, value) {
+            (TokenKind::Minus, IntValue::Untyped(value)) => Self::untyped(value.checked_neg(), expr.span),
+            (TokenKind::Minus, value) => {
+                if !value.get_type().is_signed() {
+                    return Err(Diagnostic::error(format!("cannot apply unary `-` to type `{}`", value.get_type())).with_span(expr.span));
+                }
+                Self::typed(value.as_i128().checked_neg(), value.get_type(), expr.span)
             }
-            TokenKind::Plus => value.into(),
-            _ => panic!("Unexpected token"),
+            (TokenKind::Plus, value) => Ok(value.into()),
+            (TokenKind::Bang | TokenKind::Tilde, value) => Ok((!value).into()),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", expr.op.kind)).with_span(expr.span)),
         }
     }
 
-    fn visit_unary_expr_float(&mut self, value: FloatingValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Value<'ctx> { 
+    fn visit_unary_expr_float(&mut self, value: FloatingValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { 
 
         match expr.op.kind {
             TokenKind::Minus => {
                 match value {
-                    FloatingValue::Float(FloatValue::F32(value)) => Value::Literal(LiteralValue::Float(FloatValue::F32(-value))),
-                    FloatingValue::Float(FloatValue::F64(value)) => Value::Literal(LiteralValue::Float(FloatValue::F64(-value))),
-                    _ => panic!("Unexpected type"),
+                    FloatingValue::Float(FloatValue::F32(value)) => Ok(Value::Literal(LiteralValue::Float(FloatValue::F32(-value)))),
+                    FloatingValue::Float(FloatValue::F64(value)) => Ok(Value::Literal(LiteralValue::Float(FloatValue::F64(-value)))),
+                    _ => unreachable!(),
                 }
             }
-            TokenKind::Plus => value.into(),
-            _ => panic!("Unexpected token"),
+            TokenKind::Plus => Ok(value.into()),
+            TokenKind::Bang | TokenKind::Tilde => Err(Diagnostic::error(format!("cannot apply unary {} to a floating-point operand", expr.op.kind)).with_span(expr.span)),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", expr.op.kind)).with_span(expr.span)),
         }
     }
 
-    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Value<'ctx> { 
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> { 
+
+        let (left, right) = match (expr.left.accept(self)?, expr.right.accept(self)?) {
+            (Value::Literal(left), Value::Literal(right)) => (left, right),
+            _ => return Err(Diagnostic::error(format!("cannot apply {} to lists", expr.op.kind)).with_span(expr.span)),
+        };
 
-        let left = expr.left.accept(self).as_literal();
-        let right = expr.right.accept(self).as_literal();
+        if expr.op.kind.is_bitwise() && (matches!(left, LiteralValue::Float(_)) || matches!(right, LiteralValue::Float(_))) {
+            return Err(Diagnostic::error(format!("cannot apply {} to floating-point operands", expr.op.kind)).with_span(expr.span));
+        }
 
         match (left, right) {
-            (LiteralValue::Int(left), LiteralValue::Int(right)) => self.visit_binary_expr_int_int(left.into(), right.into(), expr),
-            (LiteralValue::Int(left), LiteralValue::Float(right)) => self.visit_binary_expr_int_float(left.into(), right.into(), expr),
-            (LiteralValue::Float(left), LiteralValue::Int(right)) => self.visit_binary_expr_float_int(left.into(), right.into(), expr),
-            (LiteralValue::Float(left), LiteralValue::Float(right)) => self.visit_binary_expr_float_float(left.into(), right.into(), expr),
-            _ => panic!("Unexpected token: left: {:?}, right: {:?}", left, right),
+            (LiteralValue::Int(left), LiteralValue::Int(right)) => self.visit_binary_expr_int_int(left.into(), right.into(), &expr.op, expr.span),
+            (LiteralValue::Int(left), LiteralValue::Float(right)) => self.visit_binary_expr_int_float(left.into(), right.into(), &expr.op, expr.span),
+            (LiteralValue::Float(left), LiteralValue::Int(right)) => self.visit_binary_expr_float_int(left.into(), right.into(), &expr.op, expr.span),
+            (LiteralValue::Float(left), LiteralValue::Float(right)) => self.visit_binary_expr_float_float(left.into(), right.into(), &expr.op, expr.span),
+            _ => Err(Diagnostic::error(format!("cannot apply {} to {:?} and {:?}", expr.op.kind, left, right)).with_span(expr.span)),
         }
     }
 
-    fn visit_binary_expr_int_int(&mut self, left: IntegerValue<'ctx>, right: IntegerValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> { 
-
-        let left_: IntValue = left.into();
-        let right_: IntValue = right.into();
-
-        let left = match (left_, right_) {
-            (IntValue::I8(left), IntValue::I16(_)) => IntValue::I16(left as i16),
-            (IntValue::I8(left), IntValue::I32(_)) => IntValue::I32(left as i32),
-            (IntValue::I8(left), IntValue::I64(_)) => IntValue::I64(left as i64),
-            (IntValue::I16(left), IntValue::I32(_)) => IntValue::I32(left as i32),
-            (IntValue::I16(left), IntValue::I64(_)) => IntValue::I64(left as i64),
-            (IntValue::I32(left), IntValue::I64(_)) => IntValue::I64(left as i64),
-            _ => left_,
-        };
+    fn visit_binary_expr_int_int(&mut self, left: IntegerValue<'ctx>, right: IntegerValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> { 
+
+        let left: IntValue = left.into();
+        let right: IntValue = right.into();
+
+        // The result of a shift has the type of its left operand, so the
+        // operands are not unified first. An untyped one is shifted at full
+        // width and only narrowed where it is used.
+        match (&op.kind, left) {
+            (TokenKind::ShiftLeft | TokenKind::ShiftRight, IntValue::Untyped(value)) => {
+                let amount = right.as_shift_amount();
+                let result = match op.kind {
+                    TokenKind::ShiftLeft => value.checked_shl(amount).filter(|result| result >> amount == value),
+                    _ => Some(value.checked_shr(amount).unwrap_or(if value < 0 { -1 } else { 0 })),
+                };
+                return Self::untyped(result, span);
+            }
+            (TokenKind::ShiftLeft | TokenKind::ShiftRight, _) => {
+                // Over-long shifts wrap the amount the same way at run time.
+                let type_ = left.get_type();
+                let amount = right.as_shift_amount() % type_.bit_width();
+                let result = match op.kind {
+                    TokenKind::ShiftLeft => left.as_i128() << amount,
+                    _ => left.as_i128() >> amount,
+                };
+                return Ok(IntValue::from_i128(result, type_).into());
+            }
+            _ => {}
+        }
 
-        let right = match (left_, right_) {
-            (IntValue::I16(_), IntValue::I8(right)) => IntValue::I16(right as i16),
-            (IntValue::I32(_), IntValue::I8(right)) => IntValue::I32(right as i32),
-            (IntValue::I64(_), IntValue::I8(right)) => IntValue::I64(right as i64),
-            (IntValue::I32(_), IntValue::I16(right)) => IntValue::I32(right as i32),
-            (IntValue::I64(_), IntValue::I16(right)) => IntValue::I64(right as i64),
-            (IntValue::I64(_), IntValue::I32(right)) => IntValue::I64(right as i64),
-            _ => right_,
+        // An untyped operand takes the type of the other one when it fits,
+        // as the type checker decides.
+        let type_ = match (left, right) {
+            (IntValue::Untyped(_), IntValue::Untyped(_)) => None,
+            (IntValue::Untyped(value), other) | (other, IntValue::Untyped(value)) if IntValue::fits(value, other.get_type()) => Some(other.get_type()),
+            (left, right) => Some(left.get_type().common(right.get_type())),
         };
 
-        match expr.op.kind {
-            TokenKind::Plus => (left + right).into(),
-            TokenKind::Minus => (left - right).into(),
-            TokenKind::Asterisk => (left * right).into(),
-            TokenKind::Slash => {
-                if right.is_zero() {
-                    panic!("Division by zero");
-                }
+        let (left, right) = (left.as_i128(), right.as_i128());
+        if matches!(op.kind, TokenKind::Slash | TokenKind::Remainder) && right == 0 {
+            return Err(Diagnostic::error("division by zero").with_span(span));
+        }
 
-                (left / right).into()
-            }
-            TokenKind::Remainder => {
-                if right.is_zero() {
-                    panic!("Division by zero");
-                }
+        let result = match op.kind {
+            TokenKind::Plus => left.checked_add(right),
+            TokenKind::Minus => left.checked_sub(right),
+            TokenKind::Asterisk => left.checked_mul(right),
+            TokenKind::Slash => left.checked_div(right),
+            TokenKind::Remainder => left.checked_rem(right),
+            TokenKind::Ampersand => Some(left & right),
+            TokenKind::Pipe => Some(left | right),
+            TokenKind::Caret => Some(left ^ right),
+            TokenKind::Greater => return Ok((left > right).into()),
+            TokenKind::GreaterEqual => return Ok((left >= right).into()),
+            TokenKind::Less => return Ok((left < right).into()),
+            TokenKind::LessEqual => return Ok((left <= right).into()),
+            TokenKind::Equal => return Ok((left == right).into()),
+            TokenKind::NotEqual => return Ok((left != right).into()),
+            _ => return Err(Diagnostic::error(format!("unexpected operator {}", op.kind)).with_span(span)),
+        };
 
-                (left % right).into()
-            }
-            TokenKind::Greater => (left > right).into(),
-            TokenKind::GreaterEqual => (left >= right).into(),
-            TokenKind::Less => (left < right).into(),
-            TokenKind::LessEqual => (left <= right).into(),
-            TokenKind::Equal => (left == right).into(),
-            TokenKind::NotEqual => (left != right).into(),
-            _ => panic!("Unexpected token"),
+        match type_ {
+            Some(type_) => Self::typed(result, type_, span),
+            None => Self::untyped(result, span),
         }
     }
 
-    fn visit_binary_expr_int_float(&mut self, left: IntegerValue<'ctx>, right: FloatingValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> { 
+    /// The integer is converted to the type of the floating-point operand, as
+    /// it is at run time.
+    fn visit_binary_expr_int_float(&mut self, left: IntegerValue<'ctx>, right: FloatingValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> { 
 
-        let left: FloatingValue = left.into();
-        let right: FloatingValue = right.into();
+        let left = FloatingValue::Float(FloatValue::from_int(left.into(), right.get_type()));
 
-        return self.visit_binary_expr_float_float(left.into(), right.into(), expr);
+        self.visit_binary_expr_float_float(left, right, op, span)
     }
 
-    fn visit_binary_expr_float_int(&mut self, left: FloatingValue<'ctx>, right: IntegerValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> { 
+    fn visit_binary_expr_float_int(&mut self, left: FloatingValue<'ctx>, right: IntegerValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> { 
 
-        let left: FloatingValue = left.into();
-        let right: FloatingValue = right.into();
+        let right = FloatingValue::Float(FloatValue::from_int(right.into(), left.get_type()));
 
-        return self.visit_binary_expr_float_float(left.into(), right.into(), expr);
+        self.visit_binary_expr_float_float(left, right, op, span)
     }
 
-    fn visit_binary_expr_float_float(&mut self, left: FloatingValue<'ctx>, right: FloatingValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx> { 
+    fn visit_binary_expr_float_float(&mut self, left: FloatingValue<'ctx>, right: FloatingValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic> { 
 
         let left_: FloatValue = left.into();
         let right_: FloatValue = right.into();
@@ -157,31 +286,37 @@ impl<'ctx> ExprVisitor<'ctx> for CompileTimeEvaluator {
             _ => right_,
         };
 
-        match expr.op.kind {
-            TokenKind::Plus => (left + right).into(),
-            TokenKind::Minus => (left - right).into(),
-            TokenKind::Asterisk => (left * right).into(),
-            TokenKind::Slash => {
-                if right.is_zero() {
-                    panic!("Division by zero");
-                }
-
-                (left / right).into()
-            }
-            TokenKind::Remainder => {
-                if right.is_zero() {
-                    panic!("Division by zero");
-                }
+        match op.kind {
+            TokenKind::Plus => Ok((left + right).into()),
+            TokenKind::Minus => Ok((left - right).into()),
+            TokenKind::Asterisk => Ok((left * right).into()),
+            // Dividing a float by zero gives an infinity or NaN, as it does at
+            // run time.
+            TokenKind::Slash => Ok((left / right).into()),
+            TokenKind::Remainder => Ok((left % right).into()),
+            TokenKind::Greater => Ok((left > right).into()),
+            TokenKind::GreaterEqual => Ok((left >= right).into()),
+            TokenKind::Less => Ok((left < right).into()),
+            TokenKind::LessEqual => Ok((left <= right).into()),
+            TokenKind::Equal => Ok((left == right).into()),
+            TokenKind::NotEqual => Ok((left != right).into()),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", op.kind)).with_span(span)),
+        }
+    }
 
-                (left % right).into()
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic> {
+        let left = Self::as_bool(expr.left.accept(self)?, expr.left.span())?;
+
+        // The right operand is only looked at when the left one does not
+        // decide the result, so `false && f()` still folds to `false`.
+        match (&expr.op.kind, left) {
+            (TokenKind::And, false) => Ok(false.into()),
+            (TokenKind::Or, true) => Ok(true.into()),
+            (TokenKind::And, true) | (TokenKind::Or, false) => {
+                let right = Self::as_bool(expr.right.accept(self)?, expr.right.span())?;
+                Ok(right.into())
             }
-            TokenKind::Greater => (left > right).into(),
-            TokenKind::GreaterEqual => (left >= right).into(),
-            TokenKind::Less => (left < right).into(),
-            TokenKind::LessEqual => (left <= right).into(),
-            TokenKind::Equal => (left == right).into(),
-            TokenKind::NotEqual => (left != right).into(),
-            _ => panic!("Unexpected token"),
+            _ => Err(Diagnostic::error(format!("unexpected operator {}", expr.op.kind)).with_span(expr.span)),
         }
     }
 }
@@ -2,49 +2,70 @@ use std::path::Path;
 
 use inkwell::{context::Context, targets::{FileType, InitializationConfig, RelocMode, CodeModel, Target, TargetMachine}, OptimizationLevel};
 
-use crate::frontend::{lexer::Lexer, parser::Parser};
+use crate::frontend::{lexer::Lexer, parser::Parser, source_map::SourceMap};
 use crate::code_generator::CodeGenerator;
+use crate::diagnostic::Diagnostic;
+use crate::semantic::TypeChecker;
 
 use std::fs::read_to_string;
 
-use crate::Compiler;
+use crate::compiler::Compiler;
 
 impl<'ctx> Compiler<'ctx> {
 
-    pub fn new(context: &'ctx Context, source_file: &String) -> Self {
+    pub fn new(context: &'ctx Context, source_file: &String, bounds_checks: bool) -> Self {
 
         let source = read_to_string(source_file).unwrap();
-        let lexer = Lexer::new(source);
+
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(source_file.to_owned(), source.clone());
+
+        let lexer = Lexer::new(source, file_id);
         let parser = Parser::new(lexer);
 
+        // The module is built for the host, whose data layout the code
+        // generator needs to lay out enums.
+        let target_machine = Self::create_target_machine().unwrap();
+
         let module = context.create_module(source_file);
+        module.set_triple(&target_machine.get_triple());
+        module.set_data_layout(&target_machine.get_target_data().get_data_layout());
         let builder = context.create_builder();
+        let generator = CodeGenerator::new(context, module, builder, source_map.clone(), bounds_checks);
 
         Self {
+            source_map,
             parser,
-            generator: CodeGenerator::new(context, module, builder),
+            generator,
+            target_machine,
         }
 
     }
 
-    pub fn compile(&mut self) -> Result<(), String> {
+    pub fn compile(&mut self) -> Result<(), Vec<Diagnostic>> {
 
-        let stmts = self.parser.parse();
+        let (stmts, diagnostics) = self.parser.parse();
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
 
+        TypeChecker::new().check(&stmts)?;
+
+        let mut diagnostics = Vec::new();
         for stmt in stmts {
-            self.generator.generate_code(stmt.as_ref());
+            if let Err(diagnostic) = self.generator.generate_code(stmt.as_ref()) {
+                diagnostics.push(diagnostic);
+            }
         }
         
-        Ok(())
-    }
-
-    pub fn generate_output(&mut self, output_filename: &Path, filetype: FileType, verbose: bool) -> Result<(), String> {
-
-        if verbose {
-            println!("Generated LLVM IR:");
-            println!("{}", self.generator.get_module().print_to_string().to_string());
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
         }
+    }
 
+    fn create_target_machine() -> Result<TargetMachine, String> {
         Target::initialize_all(&InitializationConfig::default());
 
         let target_triple = TargetMachine::get_default_triple();
@@ -53,16 +74,33 @@ impl<'ctx> Compiler<'ctx> {
 
         let target = Target::from_triple(&target_triple).map_err(|e| e.to_string())?;
 
+        // Position independent code links into the PIE executables C
+        // compilers produce by default, which the absolute addresses of the
+        // jump tables built for a `match` would not.
         let target_machine = target.create_target_machine(
             &target_triple,
             &cpu,
             &features,
             OptimizationLevel::None,
-            RelocMode::Default,
+            RelocMode::PIC,
             CodeModel::Default,
         ).ok_or("Could not create target machine")?;
 
-        target_machine
+        Ok(target_machine)
+    }
+
+    pub fn render_diagnostic(&self, diagnostic: &Diagnostic) -> String {
+        diagnostic.render(&self.source_map)
+    }
+
+    pub fn generate_output(&mut self, output_filename: &Path, filetype: FileType, verbose: bool) -> Result<(), String> {
+
+        if verbose {
+            println!("Generated LLVM IR:");
+            println!("{}", self.generator.get_module().print_to_string().to_string());
+        }
+
+        self.target_machine
             .write_to_file(&self.generator.get_module(),
                            filetype, 
                            output_filename)
@@ -1,8 +1,12 @@
 pub mod compiler;
 
-use crate::{code_generator::CodeGenerator, frontend::parser::Parser};
+use inkwell::targets::TargetMachine;
+
+use crate::{code_generator::CodeGenerator, frontend::{parser::Parser, source_map::SourceMap}};
 
 pub struct Compiler<'ctx> {
+    source_map: SourceMap,
     parser: Parser,
     generator: CodeGenerator<'ctx>,
+    target_machine: TargetMachine,
 }
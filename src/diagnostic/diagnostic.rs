@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::frontend::{source_map::SourceMap, span::Span};
+
+use super::{Diagnostic, Label, Severity};
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            span: None,
+            labels: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Renders the diagnostic in the usual compiler layout: a header, the
+    /// location, and the offending source line with the span underlined.
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        let mut output = format!("{}: {}\n", self.severity, self.message);
+
+        let mut snippets = Vec::new();
+        if let Some(span) = self.span {
+            snippets.push((span, '^', String::new()));
+        }
+        for label in &self.labels {
+            snippets.push((label.span, '-', label.message.clone()));
+        }
+
+        let gutter = snippets.iter()
+            .map(|(span, _, _)| (source_map.file(span.file_id).line_index(span.start) + 1).to_string().len())
+            .max()
+            .unwrap_or(1);
+
+        let mut previous_line = None;
+        for (span, marker, message) in &snippets {
+            let file = source_map.file(span.file_id);
+            let (line, column) = file.line_column(span.start);
+            let text = file.line(line);
+
+            if previous_line.is_none() {
+                output += &format!("{:gutter$}--> {}:{}:{}\n", "", file.name, line + 1, column + 1);
+                output += &format!("{:gutter$} |\n", "");
+            }
+
+            if previous_line != Some((span.file_id, line)) {
+                output += &format!("{:>gutter$} | {}\n", line + 1, text);
+            }
+            previous_line = Some((span.file_id, line));
+
+            let (end_line, end_column) = file.line_column(span.end);
+            let end_column = if end_line == line { end_column } else { text.chars().count() };
+            let width = end_column.saturating_sub(column).max(1);
+
+            // The indent keeps the tabs of the line, so that the marker lines
+            // up with it however wide a tab is shown.
+            let indent: String = text.chars().take(column).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+            let underline = format!("{:gutter$} | {}{} {}",
+                                    "",
+                                    indent,
+                                    marker.to_string().repeat(width),
+                                    message);
+            output += underline.trim_end();
+            output += "\n";
+        }
+
+        for note in &self.notes {
+            output += &format!("{:gutter$} = note: {}\n", "", note);
+        }
+
+        output
+    }
+}
@@ -1,12 +1,18 @@
-use crate::frontend::visitor::ExprVisitor;
+use crate::diagnostic::Diagnostic;
+use crate::frontend::visitor::{ExprVisitor, ExprTypeVisitor};
 use crate::frontend::token::Token;
+use crate::frontend::stmt::Stmt;
+use crate::frontend::type_::Type;
 
 use proto_rs_macros::Expr;
 
+use super::span::Span;
 use super::value::{LiteralValue, Value};
 
 pub trait Expr<'ctx> {
-    fn accept(&self, visitor: &mut dyn ExprVisitor<'ctx>) -> Value<'ctx>;
+    fn accept(&self, visitor: &mut dyn ExprVisitor<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn accept_type(&self, visitor: &mut dyn ExprTypeVisitor<'ctx>) -> Result<Type, Diagnostic>;
+    fn span(&self) -> Span;
 }
 
 #[derive(Expr)]
@@ -14,43 +20,282 @@ pub struct BinaryExpr<'ctx> {
     pub left: Box<dyn Expr<'ctx> + 'ctx>,
     pub op: Token,
     pub right: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+/// A short-circuiting `&&` or `||`. Kept apart from `BinaryExpr` because the
+/// right operand must only be evaluated when the left one does not already
+/// decide the result.
+#[derive(Expr)]
+pub struct LogicalExpr<'ctx> {
+    pub left: Box<dyn Expr<'ctx> + 'ctx>,
+    pub op: Token,
+    pub right: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
 }
 
 #[derive(Expr)]
 pub struct LiteralExpr {
     pub value: LiteralValue,
+    pub span: Span,
+}
+
+/// A string literal, with its escape sequences already replaced.
+#[derive(Expr)]
+pub struct StringExpr {
+    pub value: String,
+    pub span: Span,
 }
 
 #[derive(Expr)]
 pub struct UnaryExpr<'ctx> {
     pub op: Token,
     pub right: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
 }
 
 #[derive(Expr)]
 pub struct VariableExpr {
     pub name: String,
+    pub span: Span,
 }
 
+/// A place that can be assigned to. Places nest, so `a[i].x` is a field of an
+/// element of a variable.
+pub enum LValue<'ctx> {
+    Variable(VariableExpr),
+    Index(IndexExpr<'ctx>),
+    Field(FieldExpr<'ctx>),
+    Deref(DerefExpr<'ctx>),
+    Slice(SliceExpr<'ctx>),
+    /// A value that is not a place, such as the result of a call, which can
+    /// still be indexed and have its fields read.
+    Value(Box<dyn Expr<'ctx> + 'ctx>),
+}
+
+impl<'ctx> LValue<'ctx> {
+    pub fn span(&self) -> Span {
+        self.as_expr().span()
+    }
+
+    /// The place read as an ordinary expression.
+    pub fn as_expr(&self) -> &dyn Expr<'ctx> {
+        match self {
+            LValue::Variable(variable) => variable,
+            LValue::Index(index) => index,
+            LValue::Field(field) => field,
+            LValue::Deref(deref) => deref,
+            LValue::Slice(slice) => slice,
+            LValue::Value(value) => value.as_ref(),
+        }
+    }
+
+    pub fn into_expr(self) -> Box<dyn Expr<'ctx> + 'ctx> {
+        match self {
+            LValue::Variable(variable) => Box::new(variable),
+            LValue::Index(index) => Box::new(index),
+            LValue::Field(field) => Box::new(field),
+            LValue::Deref(deref) => Box::new(deref),
+            LValue::Slice(slice) => Box::new(slice),
+            LValue::Value(value) => value,
+        }
+    }
+}
+
+/// `target = value`, or a compound assignment such as `target += value` when
+/// `op` is one of the compound assignment tokens.
 #[derive(Expr)]
-pub struct VarAssignExpr<'ctx> {
-    pub name: String,
+pub struct AssignExpr<'ctx> {
+    pub target: LValue<'ctx>,
+    pub op: Token,
     pub value: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
 }
 
 #[derive(Expr)]
 pub struct CallExpr<'ctx> {
     pub callee: String,
     pub args: Vec<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
 }
 
 #[derive(Expr)]
 pub struct ListExpr<'ctx> {
     pub values: Vec<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
+}
+
+/// `[value; count]`, a list of `count` copies of `value`.
+#[derive(Expr)]
+pub struct RepeatExpr<'ctx> {
+    pub value: Box<dyn Expr<'ctx> + 'ctx>,
+    pub count: usize,
+    pub span: Span,
 }
 
 #[derive(Expr)]
 pub struct IndexExpr<'ctx> {
-    pub variable: VariableExpr,
-    pub indices: Vec<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub base: Box<LValue<'ctx>>,
+    pub index: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+/// `base.field`, reading a field of a struct.
+/// `base[start..end]`, a slice of the elements of a list or of another
+/// slice. A missing `start` is the first element and a missing `end` is the
+/// end of `base`.
+#[derive(Expr)]
+pub struct SliceExpr<'ctx> {
+    pub base: Box<LValue<'ctx>>,
+    pub start: Option<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub end: Option<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
+}
+
+#[derive(Expr)]
+pub struct FieldExpr<'ctx> {
+    pub base: Box<LValue<'ctx>>,
+    pub field: String,
+    pub span: Span,
+}
+
+pub struct FieldInit<'ctx> {
+    pub name: String,
+    pub value: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+impl<'ctx> FieldInit<'ctx> {
+    pub fn new(name: String, value: Box<dyn Expr<'ctx> + 'ctx>, span: Span) -> Self {
+        Self {
+            name,
+            value,
+            span,
+        }
+    }
+}
+
+/// A struct literal such as `Point { x: 1.0, y: 2.0 }`. The fields may be
+/// given in any order.
+#[derive(Expr)]
+pub struct StructExpr<'ctx> {
+    pub name: String,
+    pub fields: Vec<FieldInit<'ctx>>,
+    pub span: Span,
+}
+
+/// `Enum::Variant` or `Enum::Variant(args)`, building a value of an enum.
+#[derive(Expr)]
+pub struct VariantExpr<'ctx> {
+    pub enum_name: String,
+    pub variant: String,
+    pub args: Vec<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
+}
+
+/// What a `match` arm compares the scrutinee against.
+pub enum Pattern {
+    /// `_` or a name, matching any value. A name binds the value.
+    Any(Option<String>, Span),
+    Literal(LiteralValue, Span),
+    /// `Enum::Variant` or `Enum::Variant(a, _)`, binding each field of the
+    /// variant to a name unless it is `_`.
+    Variant {
+        enum_name: String,
+        variant: String,
+        fields: Vec<(Option<String>, Span)>,
+        span: Span,
+    },
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Any(_, span) | Pattern::Literal(_, span) | Pattern::Variant { span, .. } => *span,
+        }
+    }
+}
+
+/// The body of a `match` arm. An expression gives the arm a value; a block
+/// is run for its effect only.
+pub enum ArmBody<'ctx> {
+    Expr(Box<dyn Expr<'ctx> + 'ctx>),
+    Block(Box<dyn Stmt<'ctx> + 'ctx>),
+}
+
+pub struct MatchArm<'ctx> {
+    pub pattern: Pattern,
+    pub body: ArmBody<'ctx>,
+    pub span: Span,
+}
+
+impl<'ctx> MatchArm<'ctx> {
+    pub fn new(pattern: Pattern, body: ArmBody<'ctx>, span: Span) -> Self {
+        Self {
+            pattern,
+            body,
+            span,
+        }
+    }
+}
+
+/// `match scrutinee { pattern => body, ... }`. The first arm whose pattern
+/// matches is taken. The match has a value when every arm body is an
+/// expression, and `()` otherwise.
+#[derive(Expr)]
+pub struct MatchExpr<'ctx> {
+    pub scrutinee: Box<dyn Expr<'ctx> + 'ctx>,
+    pub arms: Vec<MatchArm<'ctx>>,
+    pub span: Span,
+}
+
+/// `{ stmts; value }`, running its statements in a scope of their own. The
+/// block has the value of its final expression, or `()` without one.
+#[derive(Expr)]
+pub struct BlockExpr<'ctx> {
+    pub stmts: Vec<Box<dyn Stmt<'ctx> + 'ctx>>,
+    pub value: Option<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
+}
+
+/// `if cond { ... } else { ... }`, where `then` is a block and `otherwise`
+/// is a block or another `if`. Without an `else`, the `if` has no value.
+#[derive(Expr)]
+pub struct IfExpr<'ctx> {
+    pub cond: Box<dyn Expr<'ctx> + 'ctx>,
+    pub then: Box<dyn Expr<'ctx> + 'ctx>,
+    pub otherwise: Option<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
+}
+
+/// `loop { ... }`, running its body until a `break` leaves it. The loop has
+/// the value given by its `break`s, or `()` if they give none.
+#[derive(Expr)]
+pub struct LoopExpr<'ctx> {
+    pub label: Option<String>,
+    pub body: Box<dyn Stmt<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+/// `expr as type_`, converting a value of one primitive type to another.
+#[derive(Expr)]
+pub struct CastExpr<'ctx> {
+    pub expr: Box<dyn Expr<'ctx> + 'ctx>,
+    pub type_: Type,
+    pub span: Span,
+}
+
+/// `&target` or `&mut target`, the address of a place.
+#[derive(Expr)]
+pub struct AddressOfExpr<'ctx> {
+    pub mutable: bool,
+    pub target: LValue<'ctx>,
+    pub span: Span,
+}
+
+/// `*pointer`, the place a pointer points to.
+#[derive(Expr)]
+pub struct DerefExpr<'ctx> {
+    pub pointer: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
 }
@@ -1,104 +1,187 @@
+use crate::diagnostic::Diagnostic;
+use crate::frontend::span::Span;
 use crate::frontend::token::Token;
 use crate::frontend::token::TokenKind;
 
+/// Splits the source into tokens. The lexer walks the input once with a byte
+/// cursor, so `start` and `current` are byte offsets that always sit on
+/// character boundaries.
 pub struct Lexer {
     input: String,
+    file_id: usize,
     start: usize,
     current: usize,
-    line: usize,
-    column: usize,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Lexer { 
-    pub fn new(input: String) -> Lexer {
+    pub fn new(input: String, file_id: usize) -> Lexer {
         Lexer {
-            input: input.trim().to_string(),
+            input,
+            file_id,
             start: 0,
             current: 0,
-            line: 1,
-            column: 1,
+            diagnostics: vec![],
         }
     }
 
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// The token returned once the input is exhausted.
+    pub fn eof(&self) -> Token {
+        let end = self.input.len();
+        Token::new(TokenKind::Default, Span::new(self.file_id, end, end))
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.file_id, self.start, self.current)
+    }
+
+    fn make_token(&self, kind: TokenKind) -> Option<Token> {
+        Some(Token::new(kind, self.span()))
+    }
+
+    fn error(&self, message: &str) -> Diagnostic {
+        Diagnostic::error(message).with_span(self.span())
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current >= self.input.len()
+    }
+
     pub fn peek_char(&self) -> char {
-        self.input.chars().nth(self.current).unwrap_or('\0')
+        self.input[self.current..].chars().next().unwrap_or('\0')
     }
 
-    #[allow(dead_code)]
     pub fn peek_next(&self) -> char {
-        self.input.chars().nth(self.current + 1).unwrap_or('\0')
+        self.input[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     pub fn advance(&mut self) -> char {
         let c = self.peek_char();
-        self.current += 1;
-        self.column += 1;
+        if !self.is_at_end() {
+            self.current += c.len_utf8();
+        }
         c
     }
 
+    /// Skips whitespace and comments.
     fn skip_whitespace(&mut self) {
         loop {
             match self.peek_char() {
-                ' ' | '\t' | '\r' => { self.advance(); }
-                '\n' => {
-                    self.line += 1;
-                    self.column = 1;
+                ' ' | '\t' | '\r' | '\n' => {
                     self.advance();
-                },
-                _ => break,
+                }
+                '/' if self.peek_next() == '/' => {
+                    while !self.is_at_end() && self.peek_char() != '\n' {
+                        self.advance();
+                    }
+                }
+                '/' if self.peek_next() == '*' => {
+                    self.start = self.current;
+                    self.advance();
+                    self.advance();
+                    while !self.is_at_end() && (self.peek_char() != '*' || self.peek_next() != '/') {
+                        self.advance();
+                    }
+                    if self.is_at_end() {
+                        self.diagnostics.push(self.error("unterminated block comment"));
+                        return;
+                    }
+                    self.advance();
+                    self.advance();
+                }
+                _ => return,
             }
         }
     }
     
+    /// Lexes the escape sequence after a `\` in a character or string
+    /// literal.
+    fn escape(&mut self) -> char {
+        match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            '"' => '"',
+            _ => {
+                self.diagnostics.push(self.error("invalid escape sequence")
+                    .with_note("valid escape sequences are \\n, \\t, \\r, \\0, \\\\, \\' and \\\""));
+                '\0'
+            }
+        }
+    }
+
     fn character(&mut self) -> Option<Token> {
 
         let kind = if self.peek_char() == '\\' {
             self.advance();
-            match self.advance() {
-                'n' => TokenKind::Char('\n'),
-                't' => TokenKind::Char('\t'),
-                'r' => TokenKind::Char('\r'),
-                '\\' => TokenKind::Char('\\'),
-                '\'' => TokenKind::Char('\''),
-                '"' => TokenKind::Char('"'),
-                _ => {
-                    println!("invalid escape sequence");
-                    return None;
-                }
-            }
+            TokenKind::Char(self.escape())
         } else {
-            TokenKind::Char(self.advance())
+            let c = self.advance();
+            // A `char` is the single byte holding its code, so only ASCII
+            // characters fit into one.
+            if !c.is_ascii() {
+                self.diagnostics.push(self.error("non-ASCII character in character literal")
+                    .with_note("a `char` is a single byte, so it can only hold an ASCII character"));
+            }
+            TokenKind::Char(c)
         };
 
+        if self.peek_char() != '\'' {
+            self.diagnostics.push(self.error("unterminated character literal"));
+            return self.make_token(kind);
+        }
+
         self.advance();
 
-        Some(Token::new(kind, self.line, self.column))
+        self.make_token(kind)
+    }
+
+    /// Lexes a loop label such as `'outer`, whose leading `'` has already
+    /// been consumed.
+    fn label(&mut self) -> Option<Token> {
+        while self.peek_char().is_alphanumeric() || self.peek_char() == '_' {
+            self.advance();
+        }
+
+        let name = self.input[self.start + 1..self.current].to_string();
+        self.make_token(TokenKind::Label(name))
     }
 
     fn string(&mut self) -> Option<Token> {
         let mut string = String::new();
 
         while self.peek_char() != '"' {
-            if self.current == self.input.len() {
-                println!("unterminated string");
+            if self.is_at_end() {
+                self.diagnostics.push(self.error("unterminated string"));
                 return None;
             }
-            string.push(self.advance());
+            match self.advance() {
+                '\\' => string.push(self.escape()),
+                c => string.push(c),
+            }
         }
 
         self.advance();
 
-        Some(Token::new(TokenKind::String(string), self.line, self.column))
+        self.make_token(TokenKind::String(string))
     }
 
     fn number(&mut self) -> Option<Token> {
-        while self.peek_char().is_digit(10) {
+        while self.peek_char().is_ascii_digit() {
             self.advance();
         }
 
-        if self.peek_char() == '.' {
+        // The `..` of a range such as `0..n` is left for its own token.
+        if self.peek_char() == '.' && self.peek_next() != '.' {
             self.advance();
-            while self.peek_char().is_digit(10) {
+            while self.peek_char().is_ascii_digit() {
                 self.advance();
             }
         }
@@ -108,7 +191,7 @@ impl Lexer {
             if self.peek_char() == '+' || self.peek_char() == '-' {
                 self.advance();
             }
-            while self.peek_char().is_digit(10) {
+            while self.peek_char().is_ascii_digit() {
                 self.advance();
             }
         }
@@ -121,18 +204,15 @@ impl Lexer {
             TokenKind::Int(lexeme)
         };
 
-        Some(Token::new(kind, self.line, self.column))
+        self.make_token(kind)
     }
 
     fn identifier(&mut self) -> Option<Token> {
-        while self.peek_char().is_alphabetic() || self.peek_char().is_digit(10) || self.peek_char() == '_' {
+        while self.peek_char().is_alphanumeric() || self.peek_char() == '_' {
             self.advance();
         }
 
-        let lexeme = self.input.chars()
-                        .skip(self.start)
-                        .take(self.current - self.start)
-                        .collect::<String>();
+        let lexeme = self.input[self.start..self.current].to_string();
 
         let kind = match lexeme.as_str() {
             "true" => TokenKind::True,
@@ -142,9 +222,23 @@ impl Lexer {
             "if" => TokenKind::If,
             "else" => TokenKind::Else,
             "while" => TokenKind::While,
+            "loop" => TokenKind::Loop,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "as" => TokenKind::As,
+            "const" => TokenKind::Const,
+            "static" => TokenKind::Static,
+            "mut" => TokenKind::Mut,
             "break" => TokenKind::Break,
             "continue" => TokenKind::Continue,
             "fn" => TokenKind::Function,
+            "struct" => TokenKind::Struct,
+            "enum" => TokenKind::Enum,
+            "match" => TokenKind::Match,
+
+            "bool" => TokenKind::Bool,
+            "char" => TokenKind::CharType,
+            "str" => TokenKind::Str,
 
             "u8" => TokenKind::U8,
             "u16" => TokenKind::U16,
@@ -161,7 +255,7 @@ impl Lexer {
             _ => TokenKind::Ident(lexeme),
         };
 
-        Some(Token::new(kind, self.line, self.column))
+        self.make_token(kind)
     }
 }
 
@@ -170,12 +264,12 @@ impl Iterator for Lexer {
 
     fn next(&mut self) -> Option<Self::Item> {
 
-        if self.current >= self.input.len() {
+        self.skip_whitespace();
+
+        if self.is_at_end() {
             return None;
         }
 
-        self.skip_whitespace();
-
         self.start = self.current;
 
         let current = self.advance();
@@ -184,85 +278,160 @@ impl Iterator for Lexer {
             '-' => {
                 if self.peek_char() == '>' {
                     self.advance();
-                    Some(Token::new(TokenKind::RightArrow, self.line, self.column))
+                    self.make_token(TokenKind::RightArrow)
+                } else if self.peek_char() == '=' {
+                    self.advance();
+                    self.make_token(TokenKind::MinusAssign)
                 } else {
-                    Some(Token::new(TokenKind::Minus, self.line, self.column))
+                    self.make_token(TokenKind::Minus)
+                }
+            }
+            '+' => {
+                if self.peek_char() == '=' {
+                    self.advance();
+                    self.make_token(TokenKind::PlusAssign)
+                } else {
+                    self.make_token(TokenKind::Plus)
+                }
+            }
+            '*' => {
+                if self.peek_char() == '=' {
+                    self.advance();
+                    self.make_token(TokenKind::AsteriskAssign)
+                } else {
+                    self.make_token(TokenKind::Asterisk)
                 }
             }
-            '+' => Some(Token::new(TokenKind::Plus, self.line, self.column)),
-            '*' => Some(Token::new(TokenKind::Asterisk, self.line, self.column)),
             '/' => {
-                if self.peek_char() == '/' {
-                    while self.peek_char() != '\n' && self.current < self.input.len() {
-                        self.advance();
-                    }
-                    self.line += 1;
-                    self.column = 1;
-                    self.next()
-                } else if self.peek_char() == '*' {
+                if self.peek_char() == '=' {
                     self.advance();
-                    while self.current < self.input.len() && (self.peek_char() != '*' || self.peek_next() != '/') {
-                        self.advance();
-                        self.column += 1;
-                        self.skip_whitespace();
-                    }
+                    self.make_token(TokenKind::SlashAssign)
+                } else {
+                    self.make_token(TokenKind::Slash)
+                }
+            }
+            '%' => {
+                if self.peek_char() == '=' {
+                    self.advance();
+                    self.make_token(TokenKind::RemainderAssign)
+                } else {
+                    self.make_token(TokenKind::Remainder)
+                }
+            }
+            '(' => self.make_token(TokenKind::LeftParen),
+            ')' => self.make_token(TokenKind::RightParen),
+            '{' => self.make_token(TokenKind::LeftBrace),
+            '}' => self.make_token(TokenKind::RightBrace),
+            '[' => self.make_token(TokenKind::LeftBracket),
+            ']' => self.make_token(TokenKind::RightBracket),
+            ',' => self.make_token(TokenKind::Comma),
+            '.' => {
+                if self.peek_char() == '.' {
                     self.advance();
+                    self.make_token(TokenKind::DotDot)
+                } else {
+                    self.make_token(TokenKind::Dot)
+                }
+            }
+            ':' => {
+                if self.peek_char() == ':' {
                     self.advance();
-                    self.next()
+                    self.make_token(TokenKind::ColonColon)
                 } else {
-                    Some(Token::new(TokenKind::Slash, self.line, self.column))
+                    self.make_token(TokenKind::Colon)
                 }
             }
-            '%' => Some(Token::new(TokenKind::Remainder, self.line, self.column)),
-            '(' => Some(Token::new(TokenKind::LeftParen, self.line, self.column)),
-            ')' => Some(Token::new(TokenKind::RightParen, self.line, self.column)),
-            '{' => Some(Token::new(TokenKind::LeftBrace, self.line, self.column)),
-            '}' => Some(Token::new(TokenKind::RightBrace, self.line, self.column)),
-            '[' => Some(Token::new(TokenKind::LeftBracket, self.line, self.column)),
-            ']' => Some(Token::new(TokenKind::RightBracket, self.line, self.column)),
-            ',' => Some(Token::new(TokenKind::Comma, self.line, self.column)),
-            ':' => Some(Token::new(TokenKind::Colon, self.line, self.column)),
-            ';' => Some(Token::new(TokenKind::Semicolon, self.line, self.column)),
+            ';' => self.make_token(TokenKind::Semicolon),
             '<' => {
                 if self.peek_char() == '=' {
                     self.advance();
-                    Some(Token::new(TokenKind::LessEqual, self.line, self.column))
+                    self.make_token(TokenKind::LessEqual)
+                } else if self.peek_char() == '<' {
+                    self.advance();
+                    if self.peek_char() == '=' {
+                        self.advance();
+                        self.make_token(TokenKind::ShiftLeftAssign)
+                    } else {
+                        self.make_token(TokenKind::ShiftLeft)
+                    }
                 } else {
-                    Some(Token::new(TokenKind::Less, self.line, self.column))
+                    self.make_token(TokenKind::Less)
                 }
             }
             '>' => {
                 if self.peek_char() == '=' {
                     self.advance();
-                    Some(Token::new(TokenKind::GreaterEqual, self.line, self.column))
+                    self.make_token(TokenKind::GreaterEqual)
+                } else if self.peek_char() == '>' {
+                    self.advance();
+                    if self.peek_char() == '=' {
+                        self.advance();
+                        self.make_token(TokenKind::ShiftRightAssign)
+                    } else {
+                        self.make_token(TokenKind::ShiftRight)
+                    }
                 } else {
-                    Some(Token::new(TokenKind::Greater, self.line, self.column))
+                    self.make_token(TokenKind::Greater)
                 }
             }
             '=' => {
                 if self.peek_char() == '=' {
                     self.advance();
-                    Some(Token::new(TokenKind::Equal, self.line, self.column))
+                    self.make_token(TokenKind::Equal)
+                } else if self.peek_char() == '>' {
+                    self.advance();
+                    self.make_token(TokenKind::FatArrow)
                 } else {
-                    Some(Token::new(TokenKind::Assign, self.line, self.column))
+                    self.make_token(TokenKind::Assign)
                 }
             },
             '!' => {
                 if self.peek_char() == '=' {
                     self.advance();
-                    Some(Token::new(TokenKind::NotEqual, self.line, self.column))
+                    self.make_token(TokenKind::NotEqual)
                 } else {
-                    Some(Token::new(TokenKind::Bang, self.line, self.column))
+                    self.make_token(TokenKind::Bang)
                 }
             },
+            '&' => {
+                if self.peek_char() == '&' {
+                    self.advance();
+                    self.make_token(TokenKind::And)
+                } else if self.peek_char() == '=' {
+                    self.advance();
+                    self.make_token(TokenKind::AmpersandAssign)
+                } else {
+                    self.make_token(TokenKind::Ampersand)
+                }
+            },
+            '|' => {
+                if self.peek_char() == '|' {
+                    self.advance();
+                    self.make_token(TokenKind::Or)
+                } else if self.peek_char() == '=' {
+                    self.advance();
+                    self.make_token(TokenKind::PipeAssign)
+                } else {
+                    self.make_token(TokenKind::Pipe)
+                }
+            },
+            '^' => {
+                if self.peek_char() == '=' {
+                    self.advance();
+                    self.make_token(TokenKind::CaretAssign)
+                } else {
+                    self.make_token(TokenKind::Caret)
+                }
+            }
+            '~' => self.make_token(TokenKind::Tilde),
+            // A `'` followed by a name is a label unless the name is a single
+            // character closed by another `'`, as in `'a'`.
+            '\'' if (self.peek_char().is_alphabetic() || self.peek_char() == '_') && self.peek_next() != '\'' => self.label(),
             '\'' => self.character(),
             '\"' => self.string(),
             '0'..='9' => self.number(),
             'a'..='z' | 'A'..='Z' | '_' => self.identifier(),
-            _ => {
-                self.advance();
-                Some(Token::new(TokenKind::Illegal(current.to_string()), self.line, self.column))
-            },
+            _ => self.make_token(TokenKind::Illegal(current.to_string())),
         }
     }
 }
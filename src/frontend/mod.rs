@@ -6,3 +6,5 @@ pub mod visitor;
 pub mod token;
 pub mod type_;
 pub mod value;
+pub mod span;
+pub mod source_map;
@@ -1,397 +1,1309 @@
-use crate::frontend::expr::{BinaryExpr, Expr, LiteralExpr, UnaryExpr};
+
+use crate::diagnostic::Diagnostic;
+use crate::frontend::expr::{BinaryExpr, Expr, LiteralExpr, LogicalExpr, UnaryExpr};
 use crate::frontend::lexer::Lexer;
+use crate::frontend::span::Span;
 use crate::frontend::token::Token;
 use crate::frontend::token::TokenKind;
 
-use std::iter::Peekable;
-
-use super::expr::{VariableExpr, VarAssignExpr, CallExpr, ListExpr, IndexExpr};
-use super::stmt::{Stmt, ExprStmt, VarDeclStmt, ReturnStmt, BlockStmt, IfStmt, WhileStmt, BreakStmt, ContinueStmt, FunctionDeclStmt, FunctionDefStmt, Param};
-use super::type_::{Type, LiteralType, IntType, FloatType};
+use super::expr::{VariableExpr, AssignExpr, LValue, CallExpr, ListExpr, RepeatExpr, IndexExpr, FieldExpr, StructExpr, FieldInit, VariantExpr, MatchExpr, LoopExpr, BlockExpr, IfExpr, StringExpr, CastExpr, AddressOfExpr, DerefExpr, SliceExpr, MatchArm, ArmBody, Pattern};
+use super::stmt::{Stmt, ExprStmt, VarDeclStmt, ConstDeclStmt, StaticDeclStmt, ReturnStmt, BlockStmt, IfStmt, WhileStmt, ForStmt, ForIterable, BreakStmt, ContinueStmt, FunctionDeclStmt, FunctionDefStmt, Param, StructDeclStmt, Field, EnumDeclStmt, Variant};
+use super::type_::{Type, LiteralType, IntType, FloatType, PointerKind};
 use super::value::{LiteralValue, FloatValue, IntValue};
 
+type StmtResult<'ctx> = Result<Box<dyn Stmt<'ctx> + 'ctx>, Diagnostic>;
+type ExprResult<'ctx> = Result<Box<dyn Expr<'ctx> + 'ctx>, Diagnostic>;
+
+/// A struct or enum declaration together with the names of the types its
+/// fields use.
+struct TypeDecl<'ctx> {
+    name: String,
+    uses: Vec<String>,
+    stmt: Box<dyn Stmt<'ctx> + 'ctx>,
+}
+
 pub struct Parser {
-    lexer: Peekable<Lexer>,
+    lexer: Lexer,
+    current: Token,
+    previous: Span,
+    diagnostics: Vec<Diagnostic>,
+    /// Set while parsing the condition of an `if` or a `while`, the iterable
+    /// of a `for` or the scrutinee of a `match`. `Name {` then starts the
+    /// block after it rather than a struct literal, so that `if x == y { ... }`
+    /// still parses as an `if`.
+    condition: bool,
+    /// Set while parsing the pointer of `*pointer`, so that in `*p = value`
+    /// the place `*p` is assigned to rather than `p`.
+    deref_operand: bool,
 }
 
 impl<'ctx> Parser {
 
-    pub fn new(lexer: Lexer) -> Self {
+    pub fn new(mut lexer: Lexer) -> Self {
+        let current = lexer.next().unwrap_or_else(|| lexer.eof());
+        let diagnostics = lexer.take_diagnostics();
         Self {
-            lexer: lexer.peekable(),
+            lexer,
+            previous: Span::new(current.span.file_id, 0, 0),
+            current,
+            diagnostics,
+            condition: false,
+            deref_operand: false,
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Box<dyn Stmt<'ctx> + 'ctx>> {
+    /// Parses the whole input, recovering from syntax errors so that every one
+    /// of them is reported. The returned statements are those that parsed
+    /// successfully and remain usable even when diagnostics were produced.
+    pub fn parse(&mut self) -> (Vec<Box<dyn Stmt<'ctx> + 'ctx>>, Vec<Diagnostic>) {
+        let mut types = Vec::new();
         let mut statements = Vec::new();
 
-        while let Some(_) = self.lexer.peek() {
-            statements.push(self.statement());
+        while !self.check(&TokenKind::Default) {
+            if self.check(&TokenKind::RightBrace) {
+                let token = self.advance();
+                self.diagnostics.push(Diagnostic::error("unexpected closing delimiter `}`").with_span(token.span));
+            } else if self.check(&TokenKind::Struct) || self.check(&TokenKind::Enum) {
+                types.extend(self.recovering(Self::type_declaration));
+            } else if let Some(statement) = self.recovering_statement() {
+                statements.push(statement);
+            }
+        }
+
+        // Structs and enums come before everything else, so that they can be
+        // used ahead of their declarations.
+        let mut ordered = Self::order_types(types);
+        ordered.extend(statements);
+        (ordered, std::mem::take(&mut self.diagnostics))
+    }
+
+    /// Orders type declarations so that each comes after the types its fields
+    /// use, keeping the source order otherwise. Types that contain each other
+    /// are left for the type checker to reject.
+    fn order_types(types: Vec<TypeDecl<'ctx>>) -> Vec<Box<dyn Stmt<'ctx> + 'ctx>> {
+        fn place<'ctx>(i: usize, types: &mut [Option<TypeDecl<'ctx>>], ordered: &mut Vec<Box<dyn Stmt<'ctx> + 'ctx>>) {
+            let Some(decl) = types[i].take() else { return };
+            for name in &decl.uses {
+                if let Some(used) = types.iter().position(|type_| type_.as_ref().is_some_and(|type_| type_.name == *name)) {
+                    place(used, types, ordered);
+                }
+            }
+            ordered.push(decl.stmt);
         }
 
-        statements
+        let mut types: Vec<Option<TypeDecl>> = types.into_iter().map(Some).collect();
+        let mut ordered = Vec::new();
+        for i in 0..types.len() {
+            place(i, &mut types, &mut ordered);
+        }
+        ordered
+    }
+
+    fn peek(&self) -> &Token {
+        &self.current
     }
 
-    fn statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        match self.lexer.peek().unwrap_or(&Token::default()).kind {
+    fn advance(&mut self) -> Token {
+        let next = self.lexer.next().unwrap_or_else(|| self.lexer.eof());
+        self.diagnostics.append(&mut self.lexer.take_diagnostics());
+        let token = std::mem::replace(&mut self.current, next);
+        self.previous = token.span;
+        token
+    }
+
+    /// Returns the span from `start` up to the end of the last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous)
+    }
+
+    /// Parses a statement. If it is malformed, the diagnostic is recorded and
+    /// tokens are skipped up to the next statement boundary.
+    fn recovering_statement(&mut self) -> Option<Box<dyn Stmt<'ctx> + 'ctx>> {
+        self.recovering(Self::statement)
+    }
+
+    fn recovering<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, Diagnostic>) -> Option<T> {
+        match parse(self) {
+            Ok(parsed) => Some(parsed),
+            Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    /// Skips tokens until just after a `;`, or until a `{`, `}` or a keyword
+    /// that starts a statement.
+    fn synchronize(&mut self) {
+        loop {
+            match self.peek().kind {
+                TokenKind::Semicolon => {
+                    self.advance();
+                    return;
+                }
+                TokenKind::Default |
+                TokenKind::LeftBrace |
+                TokenKind::RightBrace |
+                TokenKind::Let |
+                TokenKind::Const |
+                TokenKind::Static |
+                TokenKind::Function |
+                TokenKind::Struct |
+                TokenKind::Enum |
+                TokenKind::Match |
+                TokenKind::If |
+                TokenKind::While |
+                TokenKind::Loop |
+                TokenKind::For |
+                TokenKind::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn check(&self, kind: &TokenKind) -> bool {
+        &self.peek().kind == kind
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        let token = self.peek();
+        Diagnostic::error(format!("expected {}, found {}", expected, token.kind))
+            .with_span(token.span)
+    }
+
+    fn statement(&mut self) -> StmtResult<'ctx> {
+        match self.peek().kind {
             TokenKind::Let => self.var_decl_statement(),
+            TokenKind::Const | TokenKind::Static => self.global_decl_statement(),
             TokenKind::Return => self.return_statement(),
             TokenKind::LeftBrace => self.block_statement(),
             TokenKind::If => self.if_statement(),
-            TokenKind::While => self.while_statement(),
+            TokenKind::Label(_) => self.labeled_statement(),
+            TokenKind::While => self.while_statement(self.peek().span, None),
+            TokenKind::For => self.for_statement(self.peek().span, None),
+            TokenKind::Loop => self.loop_statement(self.peek().span, None),
             TokenKind::Break => self.break_statement(),
             TokenKind::Continue => self.continue_statement(),
             TokenKind::Function => self.function_decl_def_statement(),
+            TokenKind::Struct => Ok(Box::new(self.struct_decl_statement()?)),
+            TokenKind::Enum => Ok(Box::new(self.enum_decl_statement()?)),
+            TokenKind::Match => self.match_statement(),
             _ => self.expression_statement(),
         }
     }
 
-    fn consume(&mut self, kind: TokenKind) {
-        if self.lexer.peek().unwrap_or(&Token::default()).kind == kind {
-            self.lexer.next();
+    fn consume(&mut self, kind: TokenKind) -> Result<Token, Diagnostic> {
+        if self.check(&kind) {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(&kind.to_string()))
+        }
+    }
+
+    /// Consumes a closing delimiter, pointing back at the opening one if it is missing.
+    fn consume_closing(&mut self, kind: TokenKind, open: &Token) -> Result<Token, Diagnostic> {
+        self.consume(kind)
+            .map_err(|diagnostic| diagnostic.with_label(open.span, format!("unclosed {}", open.kind)))
+    }
+
+    fn identifier(&mut self) -> Result<String, Diagnostic> {
+        if let TokenKind::Ident(_) = self.peek().kind {
+            match self.advance().kind {
+                TokenKind::Ident(name) => Ok(name),
+                _ => unreachable!(),
+            }
         } else {
-            panic!("Expected {:?} but got {:?}", kind, self.lexer.peek().unwrap_or(&Token::default()).kind);
+            Err(self.unexpected("identifier"))
         }
     }
 
-    fn try_parse_type(&mut self) -> Option<Type> {
-        match self.lexer.peek().unwrap_or(&Token::default()).kind {
+    fn parse_type(&mut self) -> Result<Type, Diagnostic> {
+        let type_ = match self.peek().kind {
             TokenKind::LeftParen => {
-                self.lexer.next();
-                self.consume(TokenKind::RightParen);
-                Some(Type::Void)
+                let open = self.advance();
+                self.consume_closing(TokenKind::RightParen, &open)?;
+                return Ok(Type::Void);
             }
-            TokenKind::U8 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::U8)))
-            },
-            TokenKind::U16 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::U16)))
-            },
-            TokenKind::U32 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::U32)))
-            },
-            TokenKind::U64 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::U64)))
-            },
-            TokenKind::I8 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::I8)))
-            },
-            TokenKind::I16 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::I16)))
-            },
-            TokenKind::I32 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::I32)))
-            },
-            TokenKind::I64 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Int(IntType::I64)))
-            },
-            TokenKind::F32 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Float(FloatType::F32)))
-            },
-            TokenKind::F64 => {
-                self.lexer.next();
-                Some(Type::Literal(LiteralType::Float(FloatType::F64)))
-            },
-            _ => None,
+            TokenKind::LeftBracket => return self.list_type(),
+            TokenKind::Ampersand | TokenKind::Asterisk => return self.pointer_type(),
+            TokenKind::Bool => Type::Literal(LiteralType::Bool),
+            TokenKind::CharType => Type::Literal(LiteralType::Char),
+            TokenKind::Str => Type::Str,
+            TokenKind::U8 => Type::Literal(LiteralType::Int(IntType::U8)),
+            TokenKind::U16 => Type::Literal(LiteralType::Int(IntType::U16)),
+            TokenKind::U32 => Type::Literal(LiteralType::Int(IntType::U32)),
+            TokenKind::U64 => Type::Literal(LiteralType::Int(IntType::U64)),
+            TokenKind::I8 => Type::Literal(LiteralType::Int(IntType::I8)),
+            TokenKind::I16 => Type::Literal(LiteralType::Int(IntType::I16)),
+            TokenKind::I32 => Type::Literal(LiteralType::Int(IntType::I32)),
+            TokenKind::I64 => Type::Literal(LiteralType::Int(IntType::I64)),
+            TokenKind::F32 => Type::Literal(LiteralType::Float(FloatType::F32)),
+            TokenKind::F64 => Type::Literal(LiteralType::Float(FloatType::F64)),
+            TokenKind::Ident(_) => {
+                // Whether the name is a struct or an enum is only known once
+                // every declaration has been seen.
+                return Ok(Type::Named(self.identifier()?));
+            }
+            _ => return Err(self.unexpected("type")),
+        };
+
+        self.advance();
+        Ok(type_)
+    }
+
+    /// Parses a pointer type: `&T`, `&mut T` or `*T`. A slice is already a
+    /// view of the elements, so `&[T]` is a slice that can only be read
+    /// through and `&mut [T]` is the same as `[T]`.
+    fn pointer_type(&mut self) -> Result<Type, Diagnostic> {
+        let kind = match self.advance().kind {
+            TokenKind::Asterisk => PointerKind::Raw,
+            _ if self.check(&TokenKind::Mut) => {
+                self.advance();
+                PointerKind::Mutable
+            }
+            _ => PointerKind::Shared,
+        };
+
+        match (self.parse_type()?, kind) {
+            (Type::Slice(element, _), PointerKind::Shared | PointerKind::Mutable) => Ok(Type::Slice(element, kind)),
+            (pointee, kind) => Ok(Type::Pointer(Box::new(pointee), kind)),
         }
     }
 
-    fn var_decl_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::Let);
-        if let TokenKind::Ident(name) = self.lexer.next().unwrap().kind {
+    /// Parses a list type such as `[u8; 5]`, or a slice type such as `[u8]`.
+    fn list_type(&mut self) -> Result<Type, Diagnostic> {
+        let open = self.consume(TokenKind::LeftBracket)?;
+        let element = self.parse_type()?;
+        if self.check(&TokenKind::RightBracket) {
+            self.advance();
+            return Ok(Type::Slice(Box::new(element), PointerKind::Mutable));
+        }
+        self.consume(TokenKind::Semicolon)?;
+        let len = self.list_length()?;
 
-            let type_ = if self.lexer.peek().unwrap_or(&Token::default()).kind == TokenKind::Colon {
-                self.lexer.next();
-                self.try_parse_type()
-                        .expect(format!(
-                                "Expected type but got {:?}", 
-                                self.lexer.peek()
-                                    .unwrap_or(&Token::default()).kind)
-                                    .as_str())
-            } else {
-                Type::Inferred
-            };
+        self.consume_closing(TokenKind::RightBracket, &open)?;
+        Ok(Type::List(Box::new(element), len))
+    }
+
+    /// Parses the length after the `;` of a list type or of `[value; count]`.
+    fn list_length(&mut self) -> Result<usize, Diagnostic> {
+        let len = match &self.peek().kind {
+            TokenKind::Int(len) => len.parse::<u32>().map_err(|_| {
+                Diagnostic::error("list length is too large").with_span(self.peek().span)
+            })?,
+            _ => return Err(self.unexpected("list length")),
+        };
+        self.advance();
+
+        Ok(len as usize)
+    }
 
-            self.consume(TokenKind::Assign);
-            let initializer = self.expression();
-            self.consume(TokenKind::Semicolon);
-            Box::new(VarDeclStmt::new(name, type_, initializer) as VarDeclStmt<'ctx>)
+    fn var_decl_statement(&mut self) -> StmtResult<'ctx> {
+        let start = self.consume(TokenKind::Let)?.span;
+        let name = self.identifier()?;
+
+        let type_ = if self.check(&TokenKind::Colon) {
+            self.advance();
+            self.parse_type()?
         } else {
-            panic!("Expected identifier but got {:?}", self.lexer.peek().unwrap_or(&Token::default()).kind);
+            Type::Inferred
+        };
+
+        self.consume(TokenKind::Assign)?;
+        let initializer = self.expression()?;
+        self.consume(TokenKind::Semicolon)?;
+        Ok(Box::new(VarDeclStmt::new(name, type_, initializer, self.span_from(start)) as VarDeclStmt<'ctx>))
+    }
+
+    /// `const NAME: T = value;` or `static NAME: T = value;`. Unlike a
+    /// `let`, the type cannot be left out.
+    fn global_decl_statement(&mut self) -> StmtResult<'ctx> {
+        let keyword = self.advance();
+        let name = self.identifier()?;
+        self.consume(TokenKind::Colon)?;
+        let type_ = self.parse_type()?;
+
+        self.consume(TokenKind::Assign)?;
+        let value = self.expression()?;
+        self.consume(TokenKind::Semicolon)?;
+
+        let span = self.span_from(keyword.span);
+        if keyword.kind == TokenKind::Const {
+            Ok(Box::new(ConstDeclStmt::new(name, type_, value, span)))
+        } else {
+            Ok(Box::new(StaticDeclStmt::new(name, type_, value, span)))
         }
     }
 
-    fn block_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::LeftBrace);
+    fn block_statement(&mut self) -> StmtResult<'ctx> {
+        let open = self.consume(TokenKind::LeftBrace)?;
         let mut statements = Vec::new();
 
-        while self.lexer.peek().unwrap_or(&Token::default()).kind != TokenKind::RightBrace {
-            statements.push(self.statement());
+        while !self.check(&TokenKind::RightBrace) && !self.check(&TokenKind::Default) {
+            if let Some(statement) = self.recovering_statement() {
+                statements.push(statement);
+            }
         }
 
-        self.consume(TokenKind::RightBrace);
-        Box::new(BlockStmt::new(statements) as BlockStmt<'ctx>)
+        self.consume_closing(TokenKind::RightBrace, &open)?;
+        Ok(Box::new(BlockStmt::new(statements, self.span_from(open.span)) as BlockStmt<'ctx>))
     }
 
-    fn if_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::If);
-        let condition = self.expression();
-        let then_branch = self.statement();
-        let else_branch = if self.lexer.peek().unwrap_or(&Token::default()).kind == TokenKind::Else {
-            self.lexer.next();
-            Some(self.statement())
+    fn if_statement(&mut self) -> StmtResult<'ctx> {
+        let start = self.consume(TokenKind::If)?.span;
+        let condition = self.condition()?;
+        let then_branch = self.statement()?;
+        let else_branch = if self.check(&TokenKind::Else) {
+            self.advance();
+            Some(self.statement()?)
         } else {
             None
         };
 
-        Box::new(IfStmt::new(condition, then_branch, else_branch) as IfStmt<'ctx>)
+        Ok(Box::new(IfStmt::new(condition, then_branch, else_branch, self.span_from(start)) as IfStmt<'ctx>))
     }
 
-    fn while_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::While);
-        let condition = self.expression();
-        let body = self.statement();
+    /// Parses the label in front of a loop, such as `'outer:`.
+    fn label(&mut self) -> Result<String, Diagnostic> {
+        let name = match self.peek().kind {
+            TokenKind::Label(_) => match self.advance().kind {
+                TokenKind::Label(name) => name,
+                _ => unreachable!(),
+            },
+            _ => return Err(self.unexpected("label")),
+        };
+        self.consume(TokenKind::Colon)?;
+        Ok(name)
+    }
+
+    fn labeled_statement(&mut self) -> StmtResult<'ctx> {
+        let start = self.peek().span;
+        let label = self.label()?;
+
+        match self.peek().kind {
+            TokenKind::While => self.while_statement(start, Some(label)),
+            TokenKind::For => self.for_statement(start, Some(label)),
+            TokenKind::Loop => self.loop_statement(start, Some(label)),
+            _ => Err(self.unexpected("`while`, `for` or `loop` after a label")),
+        }
+    }
+
+    /// The loop statements take the `start` of their span and their `label`
+    /// from `labeled_statement` when they have a label.
+    fn while_statement(&mut self, start: Span, label: Option<String>) -> StmtResult<'ctx> {
+        self.consume(TokenKind::While)?;
+        let condition = self.condition()?;
+        let body = self.statement()?;
+
+        Ok(Box::new(WhileStmt::new(label, condition, body, self.span_from(start)) as WhileStmt<'ctx>))
+    }
 
-        Box::new(WhileStmt::new(condition, body) as WhileStmt<'ctx>)
+    fn for_statement(&mut self, start: Span, label: Option<String>) -> StmtResult<'ctx> {
+        self.consume(TokenKind::For)?;
+        let name = self.identifier()?;
+        self.consume(TokenKind::In)?;
+
+        let expr = self.condition()?;
+        let iterable = if self.check(&TokenKind::DotDot) {
+            self.advance();
+            ForIterable::Range(expr, self.condition()?)
+        } else {
+            ForIterable::List(expr)
+        };
+
+        let body = self.statement()?;
+
+        Ok(Box::new(ForStmt::new(label, name, iterable, body, self.span_from(start)) as ForStmt<'ctx>))
     }
 
-    fn break_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::Break);
-        self.consume(TokenKind::Semicolon);
-        Box::new(BreakStmt::new() as BreakStmt)
+    /// A `loop` in statement position, which needs no `;` after it.
+    fn loop_statement(&mut self, start: Span, label: Option<String>) -> StmtResult<'ctx> {
+        let expr = self.loop_expression(start, label)?;
+        if self.check(&TokenKind::Semicolon) {
+            self.advance();
+        }
+        let span = self.span_from(start);
+        Ok(Box::new(ExprStmt::new(expr, span) as ExprStmt<'ctx>))
     }
 
-    fn continue_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::Continue);
-        self.consume(TokenKind::Semicolon);
-        Box::new(ContinueStmt::new() as ContinueStmt)
+    /// The label a `break` or `continue` names, if any.
+    fn jump_label(&mut self) -> Option<String> {
+        match self.peek().kind {
+            TokenKind::Label(_) => match self.advance().kind {
+                TokenKind::Label(name) => Some(name),
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
     }
 
-    fn function_decl_def_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::Function);
-        if let TokenKind::Ident(name) = self.lexer.next().unwrap_or_default().kind {
-            self.consume(TokenKind::LeftParen);
-            let mut params = Vec::new();
-            while self.lexer.peek().unwrap_or(&Token::default()).kind != TokenKind::RightParen {
-                let name = match self.lexer.next().map(|token| token.kind) {
-                    Some(TokenKind::Ident(name)) => name,
-                    _ => panic!("Expected identifier but got {:?}", self.lexer.peek().unwrap_or(&Token::default()).kind),
-                };
+    fn break_statement(&mut self) -> StmtResult<'ctx> {
+        let start = self.consume(TokenKind::Break)?.span;
+        let label = self.jump_label();
+        let value = if self.check(&TokenKind::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenKind::Semicolon)?;
+        Ok(Box::new(BreakStmt::new(label, value, self.span_from(start)) as BreakStmt<'ctx>))
+    }
+
+    fn continue_statement(&mut self) -> StmtResult<'ctx> {
+        let start = self.consume(TokenKind::Continue)?.span;
+        let label = self.jump_label();
+        self.consume(TokenKind::Semicolon)?;
+        Ok(Box::new(ContinueStmt::new(label, self.span_from(start)) as ContinueStmt))
+    }
+
+    fn function_decl_def_statement(&mut self) -> StmtResult<'ctx> {
+        let start = self.consume(TokenKind::Function)?.span;
+        let name = self.identifier()?;
+
+        let open = self.consume(TokenKind::LeftParen)?;
+        let mut params = Vec::new();
+        while !self.check(&TokenKind::RightParen) {
+            let param_start = self.peek().span;
+            let name = self.identifier()?;
+            self.consume(TokenKind::Colon)?;
+            let type_ = self.parse_type()?;
+
+            params.push(Param::new(name, type_, self.span_from(param_start)));
+
+            if !self.check(&TokenKind::RightParen) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume_closing(TokenKind::RightParen, &open)?;
+
+        let return_type = if self.check(&TokenKind::RightArrow) {
+            self.advance();
+            self.parse_type()?
+        } else {
+            Type::Inferred
+        };
+
+        if self.check(&TokenKind::LeftBrace) {
+            let func_decl = FunctionDeclStmt::new(name, params, return_type, self.span_from(start));
+            let body = self.block_statement()?;
+            Ok(Box::new(FunctionDefStmt::new(func_decl, body, self.span_from(start)) as FunctionDefStmt<'ctx>))
+        } else {
+            self.consume(TokenKind::Semicolon)?;
+            Ok(Box::new(FunctionDeclStmt::new(name, params, return_type, self.span_from(start))))
+        }
+    }
 
-                self.consume(TokenKind::Colon);
+    fn type_declaration(&mut self) -> Result<TypeDecl<'ctx>, Diagnostic> {
+        let (name, uses, stmt): (String, Vec<String>, Box<dyn Stmt>) = match self.peek().kind {
+            TokenKind::Struct => {
+                let decl = self.struct_decl_statement()?;
+                let uses = decl.fields.iter().flat_map(|field| field.type_.names()).map(str::to_owned).collect();
+                (decl.name.clone(), uses, Box::new(decl))
+            }
+            _ => {
+                let decl = self.enum_decl_statement()?;
+                let uses = decl.variants.iter().flat_map(|variant| &variant.fields).flat_map(Type::names).map(str::to_owned).collect();
+                (decl.name.clone(), uses, Box::new(decl))
+            }
+        };
+
+        Ok(TypeDecl { name, uses, stmt })
+    }
+
+    fn struct_decl_statement(&mut self) -> Result<StructDeclStmt, Diagnostic> {
+        let start = self.consume(TokenKind::Struct)?.span;
+        let name = self.identifier()?;
+
+        let open = self.consume(TokenKind::LeftBrace)?;
+        let mut fields = Vec::new();
+        while !self.check(&TokenKind::RightBrace) {
+            let field_start = self.peek().span;
+            let name = self.identifier()?;
+            self.consume(TokenKind::Colon)?;
+            let type_ = self.parse_type()?;
+
+            fields.push(Field::new(name, type_, self.span_from(field_start)));
+
+            if !self.check(&TokenKind::RightBrace) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume_closing(TokenKind::RightBrace, &open)?;
+
+        Ok(StructDeclStmt::new(name, fields, self.span_from(start)))
+    }
 
-                let type_ = self.try_parse_type()
-                                .expect(format!(
-                                        "Expected type but got {:?}", 
-                                        self.lexer.peek()
-                                            .unwrap_or(&Token::default()).kind)
-                                            .as_str()
-                                );
+    fn enum_decl_statement(&mut self) -> Result<EnumDeclStmt, Diagnostic> {
+        let start = self.consume(TokenKind::Enum)?.span;
+        let name = self.identifier()?;
 
-                params.push(Param::new(name, type_));
+        let open = self.consume(TokenKind::LeftBrace)?;
+        let mut variants = Vec::new();
+        while !self.check(&TokenKind::RightBrace) {
+            let variant_start = self.peek().span;
+            let name = self.identifier()?;
 
-                if self.lexer.peek().unwrap_or(&Token::default()).kind != TokenKind::RightParen {
-                    self.consume(TokenKind::Comma);
+            let mut fields = Vec::new();
+            if self.check(&TokenKind::LeftParen) {
+                let open = self.advance();
+                while !self.check(&TokenKind::RightParen) {
+                    fields.push(self.parse_type()?);
+                    if !self.check(&TokenKind::RightParen) {
+                        self.consume(TokenKind::Comma)?;
+                    }
                 }
+                self.consume_closing(TokenKind::RightParen, &open)?;
             }
-            self.consume(TokenKind::RightParen);
-
-            let return_type = if self.lexer.peek().unwrap_or(&Token::default()).kind == TokenKind::RightArrow {
-                self.lexer.next();
-                self.try_parse_type()
-                        .expect(format!(
-                                "Expected type but got {:?}", 
-                                self.lexer.peek()
-                                    .unwrap_or(&Token::default()).kind)
-                                    .as_str()
-                        )
-            } else {
-                Type::Inferred
-            };
 
-            let func_decl = FunctionDeclStmt::new(name, params, return_type);
+            variants.push(Variant::new(name, fields, self.span_from(variant_start)));
 
-            if let TokenKind::LeftBrace = self.lexer.peek().unwrap_or(&Token::default()).kind {
-                let body = self.block_statement();
-                Box::new(FunctionDefStmt::new(func_decl, body) as FunctionDefStmt<'ctx>)
-            } else {
-                self.consume(TokenKind::Semicolon);
-                Box::new(func_decl)
+            if !self.check(&TokenKind::RightBrace) {
+                self.consume(TokenKind::Comma)?;
             }
+        }
+        self.consume_closing(TokenKind::RightBrace, &open)?;
+
+        Ok(EnumDeclStmt::new(name, variants, self.span_from(start)))
+    }
+
+    /// A `match` in statement position, which needs no `;` after it.
+    fn match_statement(&mut self) -> StmtResult<'ctx> {
+        let expr = self.match_expression()?;
+        if self.check(&TokenKind::Semicolon) {
+            self.advance();
+        }
+        let span = self.span_from(expr.span());
+        Ok(Box::new(ExprStmt::new(expr, span) as ExprStmt<'ctx>))
+    }
+
+    fn expression_statement(&mut self) -> StmtResult<'ctx> {
+        let expr = self.expression()?;
+        self.consume(TokenKind::Semicolon)?;
+        let span = self.span_from(expr.span());
+        Ok(Box::new(ExprStmt::new(expr, span) as ExprStmt<'ctx>))
+    }
+
+    fn return_statement(&mut self) -> StmtResult<'ctx> {
+        let start = self.consume(TokenKind::Return)?.span;
+        let expr = if self.check(&TokenKind::Semicolon) {
+            None
         } else {
-            panic!("Expected identifier but got {:?}", self.lexer.peek().unwrap_or(&Token::default()).kind);
+            Some(self.expression()?)
+        };
+        self.consume(TokenKind::Semicolon)?;
+        Ok(Box::new(ReturnStmt::new(expr, self.span_from(start)) as ReturnStmt<'ctx>))
+    }
+
+    fn expression(&mut self) -> ExprResult<'ctx> {
+        self.logic_or()
+    }
+
+    fn logic_or(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.logic_and()?;
+
+        while let TokenKind::Or = self.peek().kind {
+            let op = self.advance();
+            let right = self.logic_and()?;
+            let span = left.span().to(right.span());
+            left = Box::new(LogicalExpr::new(left, op, right, span) as LogicalExpr<'ctx>);
         }
+        Ok(left)
     }
 
-    fn expression_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        let expr = self.expression();
-        self.consume(TokenKind::Semicolon);
-        Box::new(ExprStmt::new(expr) as ExprStmt<'ctx>)
+    fn logic_and(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.equality()?;
+
+        while let TokenKind::And = self.peek().kind {
+            let op = self.advance();
+            let right = self.equality()?;
+            let span = left.span().to(right.span());
+            left = Box::new(LogicalExpr::new(left, op, right, span) as LogicalExpr<'ctx>);
+        }
+        Ok(left)
     }
 
-    fn return_statement(&mut self) -> Box<dyn Stmt<'ctx> + 'ctx> {
-        self.consume(TokenKind::Return);
-        let expr = self.expression();
-        self.consume(TokenKind::Semicolon);
-        Box::new(ReturnStmt::new(expr) as ReturnStmt<'ctx>)
+    fn equality(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.comparison()?;
+
+        while let TokenKind::NotEqual | TokenKind::Equal = self.peek().kind {
+            let op = self.advance();
+            let right = self.comparison()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
+        }
+        Ok(left)
     }
 
-    fn expression(&mut self) -> Box<dyn Expr<'ctx> + 'ctx> {
-        self.equality()
+    fn comparison(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.bit_or()?;
+
+        while let TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual = self.peek().kind {
+            let op = self.advance();
+            let right = self.bit_or()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
+        }
+        Ok(left)
+    }
+
+    fn bit_or(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.bit_xor()?;
+
+        while let TokenKind::Pipe = self.peek().kind {
+            let op = self.advance();
+            let right = self.bit_xor()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
+        }
+        Ok(left)
+    }
+
+    fn bit_xor(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.bit_and()?;
+
+        while let TokenKind::Caret = self.peek().kind {
+            let op = self.advance();
+            let right = self.bit_and()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
+        }
+        Ok(left)
     }
 
-    fn equality(&mut self) -> Box<dyn Expr<'ctx> + 'ctx> {
-        let mut left = self.comparison();
+    fn bit_and(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.shift()?;
 
-        while let TokenKind::NotEqual | TokenKind::Equal = self.lexer.peek().unwrap_or(&Token::default()).kind {
-            let op = self.lexer.next().unwrap();
-            let right = self.comparison();
-            left = Box::new(BinaryExpr::new(left, op, right) as BinaryExpr<'ctx>);
+        while let TokenKind::Ampersand = self.peek().kind {
+            let op = self.advance();
+            let right = self.shift()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
         }
-        left
+        Ok(left)
     }
 
-    fn comparison(&mut self) -> Box<dyn Expr<'ctx> + 'ctx> {
-        let mut left = self.term();
+    fn shift(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.term()?;
 
-        while let TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual = self.lexer.peek().unwrap_or(&Token::default()).kind {
-            let op = self.lexer.next().unwrap();
-            let right = self.term();
-            left = Box::new(BinaryExpr::new(left, op, right) as BinaryExpr<'ctx>);
+        while let TokenKind::ShiftLeft | TokenKind::ShiftRight = self.peek().kind {
+            let op = self.advance();
+            let right = self.term()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
         }
-        left
+        Ok(left)
     }
 
-    fn term(&mut self) -> Box<dyn Expr<'ctx> + 'ctx> {
-        let mut left = self.factor();
+    fn term(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.factor()?;
 
-        while let TokenKind::Plus | TokenKind::Minus = self.lexer.peek().unwrap_or(&Token::default()).kind {
-            let op = self.lexer.next().unwrap();
-            let right = self.factor();
-            left = Box::new(BinaryExpr::new(left, op, right) as BinaryExpr<'ctx>);
+        while let TokenKind::Plus | TokenKind::Minus = self.peek().kind {
+            let op = self.advance();
+            let right = self.factor()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
         }
-        left
+        Ok(left)
     }
 
-    fn factor(&mut self) -> Box<dyn Expr<'ctx> + 'ctx> {
-        let mut left = self.unary(); 
-        while let TokenKind::Asterisk | TokenKind::Slash | TokenKind::Remainder = self.lexer.peek().unwrap_or(&Token::default()).kind {
-            let op = self.lexer.next().unwrap();
-            let right = self.unary();
-            left = Box::new(BinaryExpr::new(left, op, right) as BinaryExpr<'ctx>);
+    fn factor(&mut self) -> ExprResult<'ctx> {
+        let mut left = self.cast()?;
+        while let TokenKind::Asterisk | TokenKind::Slash | TokenKind::Remainder = self.peek().kind {
+            let op = self.advance();
+            let right = self.cast()?;
+            let span = left.span().to(right.span());
+            left = Box::new(BinaryExpr::new(left, op, right, span) as BinaryExpr<'ctx>);
         }
-        left
+        Ok(left)
     }
 
-    fn unary(&mut self) -> Box<dyn Expr<'ctx> + 'ctx> {
-        if let TokenKind::Minus | TokenKind::Plus = self.lexer.peek().unwrap_or(&Token::default()).kind {
-            let op = self.lexer.next().unwrap();
-            let right = self.unary();
-            Box::new(UnaryExpr::new(op, right) as UnaryExpr<'ctx>)
+    /// `as` binds tighter than any binary operator but looser than the unary
+    /// ones, so `-x as u8` casts `-x`.
+    fn cast(&mut self) -> ExprResult<'ctx> {
+        let mut expr = self.unary()?;
+        while self.peek().kind == TokenKind::As {
+            self.advance();
+            let type_ = self.parse_type()?;
+            let span = self.span_from(expr.span());
+            expr = Box::new(CastExpr::new(expr, type_, span));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> ExprResult<'ctx> {
+        if self.check(&TokenKind::Asterisk) {
+            // A `*` that is itself the pointer of another one cannot be
+            // assigned to, as the assignment belongs to the outer one.
+            let assignable = !std::mem::take(&mut self.deref_operand);
+            let star = self.advance();
+            let target = self.deref(star.span)?;
+            return self.assignment(target, star.span, assignable);
+        }
+
+        if self.check(&TokenKind::Ampersand) {
+            self.deref_operand = false;
+            self.address_of()
+        } else if let TokenKind::Minus | TokenKind::Plus | TokenKind::Bang | TokenKind::Tilde = self.peek().kind {
+            self.deref_operand = false;
+            let op = self.advance();
+            let right = self.unary()?;
+            let span = op.span.to(right.span());
+            Ok(Box::new(UnaryExpr::new(op, right, span) as UnaryExpr<'ctx>))
         } else {
             self.primary()
         }
     }
 
-    fn number(&mut self, token: &Token) -> Result<Box<dyn Expr<'ctx> + 'ctx>, String> {
+    /// Parses the pointer of `*pointer`, after the `*`.
+    fn deref(&mut self, start: Span) -> Result<LValue<'ctx>, Diagnostic> {
+        self.deref_operand = true;
+        let pointer = self.unary()?;
+        Ok(LValue::Deref(DerefExpr::new(pointer, self.span_from(start))))
+    }
+
+    /// Parses `&place` or `&mut place`. Only a place has an address, so the
+    /// operand is a variable, a part of one, or a dereferenced pointer.
+    fn address_of(&mut self) -> ExprResult<'ctx> {
+        let start = self.consume(TokenKind::Ampersand)?.span;
+        let mutable = self.check(&TokenKind::Mut);
+        if mutable {
+            self.advance();
+        }
+
+        let target = match self.peek().kind {
+            TokenKind::Ident(_) => {
+                let span = self.peek().span;
+                let name = self.identifier()?;
+                self.lvalue(LValue::Variable(VariableExpr::new(name, span)))?
+            }
+            TokenKind::Asterisk => {
+                let star = self.advance();
+                self.deref(star.span)?
+            }
+            _ => return Err(self.unexpected("a place to borrow")),
+        };
+
+        Ok(Box::new(AddressOfExpr::new(mutable, target, self.span_from(start))))
+    }
+
+    /// Parses an assignment to `target` if one follows it and `assignable`
+    /// allows it, or else uses `target` as an expression.
+    fn assignment(&mut self, target: LValue<'ctx>, start: Span, assignable: bool) -> ExprResult<'ctx> {
+        if assignable && self.peek().kind.is_assignment() {
+            let op = self.advance();
+            let value = self.expression()?;
+            return Ok(Box::new(AssignExpr::new(target, op, value, self.span_from(start)) as AssignExpr<'ctx>));
+        }
+
+        Ok(target.into_expr())
+    }
+
+    fn number(&mut self, token: &Token) -> ExprResult<'ctx> {
+        Ok(Box::new(LiteralExpr::new(Self::number_value(token)?, token.span)))
+    }
+
+    fn number_value(token: &Token) -> Result<LiteralValue, Diagnostic> {
         match &token.kind {
             TokenKind::Int(value) => {
-                let value = value.parse::<u64>().unwrap();
+                let value = value.parse::<u64>().map_err(|_| {
+                    Diagnostic::error("integer literal is too large").with_span(token.span)
+                })?;
+                // The type is decided by where the literal is used, so that it
+                // can be range checked against the declared type.
+                Ok(LiteralValue::Int(IntValue::Untyped(value as i128)))
+            }
+            TokenKind::Float(value) => {
+                let value = value.parse::<f64>().map_err(|_| {
+                    Diagnostic::error("invalid float literal").with_span(token.span)
+                })?;
+                // Kept as `f64` and only rounded when converted to `f32`, so
+                // that an `f64` gets the literal at its own precision.
                 match value {
-                    value if value <= u8::MAX as u64 => Ok(Box::new(LiteralExpr::new(LiteralValue::Int(IntValue::I8(value as i8))))),
-                    value if value <= u16::MAX as u64 => Ok(Box::new(LiteralExpr::new(LiteralValue::Int(IntValue::I16(value as i16))))),
-                    value if value <= u32::MAX as u64 => Ok(Box::new(LiteralExpr::new(LiteralValue::Int(IntValue::I32(value as i32))))),
-                    value if value <= u64::MAX as u64 => Ok(Box::new(LiteralExpr::new(LiteralValue::Int(IntValue::I64(value as i64))))),
-                    _ => Err("Integer literal out of range".to_string()),
+                    value if value.is_finite() => Ok(LiteralValue::Float(FloatValue::F64(value))),
+                    _ => Err(Diagnostic::error("float literal out of range").with_span(token.span)),
                 }
             }
-            _ => Err(format!("Expected integer literal but got {:?}", token.kind)),
+            _ => Err(Diagnostic::error(format!("expected number, found {}", token.kind)).with_span(token.span)),
         }
     }
 
-    fn primary(&mut self) -> Box<dyn Expr<'ctx> + 'ctx> {
+    /// Parses the indexing and field accesses that may follow a variable or
+    /// another value. The result is used as an assignment target when an
+    /// assignment operator follows it, and is read as an ordinary expression
+    /// otherwise.
+    fn lvalue(&mut self, base: LValue<'ctx>) -> Result<LValue<'ctx>, Diagnostic> {
+        let start = base.span();
+        let mut lvalue = base;
 
-        let token = self.lexer.next().unwrap_or(Token::default());
+        loop {
+            match self.peek().kind {
+                TokenKind::LeftBracket => {
+                    let open = self.advance();
+                    let index = if self.check(&TokenKind::DotDot) { None } else { Some(self.enclosed_expression()?) };
 
-        match token.kind {
-            TokenKind::Char(value) => Box::new(LiteralExpr::new(LiteralValue::Char(value))),
-            TokenKind::Int(_) => self.number(&token).unwrap(),
-            TokenKind::Float(value) => {
-                let value = value.parse::<f64>().unwrap();
-                match value {
-                    value if value <= f32::MAX as f64 => Box::new(LiteralExpr::new(LiteralValue::Float(FloatValue::F32(value as f32)))), 
-                    value if value <= f64::MAX as f64 => Box::new(LiteralExpr::new(LiteralValue::Float(FloatValue::F64(value as f64)))),
-                    _ => panic!("Float literal out of range"),
+                    lvalue = match index {
+                        Some(index) if !self.check(&TokenKind::DotDot) => {
+                            self.consume_closing(TokenKind::RightBracket, &open)?;
+                            LValue::Index(IndexExpr::new(Box::new(lvalue), index, self.span_from(start)))
+                        }
+                        slice_start => {
+                            self.advance();
+                            let end = if self.check(&TokenKind::RightBracket) { None } else { Some(self.enclosed_expression()?) };
+                            self.consume_closing(TokenKind::RightBracket, &open)?;
+                            LValue::Slice(SliceExpr::new(Box::new(lvalue), slice_start, end, self.span_from(start)))
+                        }
+                    };
                 }
-            },
+                TokenKind::Dot => {
+                    self.advance();
+                    let field = self.identifier()?;
+                    lvalue = LValue::Field(FieldExpr::new(Box::new(lvalue), field, self.span_from(start)));
+                }
+                _ => return Ok(lvalue),
+            }
+        }
+    }
+
+    /// Parses the indexing and field accesses that may follow a value that is
+    /// not a variable, such as the result of a call.
+    fn postfix(&mut self, expr: Box<dyn Expr<'ctx> + 'ctx>, deref_operand: bool) -> ExprResult<'ctx> {
+        if !matches!(self.peek().kind, TokenKind::Dot | TokenKind::LeftBracket) {
+            return Ok(expr);
+        }
+
+        let start = expr.span();
+        let lvalue = self.lvalue(LValue::Value(expr))?;
+        self.assignment(lvalue, start, !deref_operand)
+    }
+
+    /// Parses the condition of an `if` or a `while`, the iterable of a `for`
+    /// or the scrutinee of a `match`, which a block follows.
+    fn condition(&mut self) -> ExprResult<'ctx> {
+        let outer = std::mem::replace(&mut self.condition, true);
+        let expr = self.expression();
+        self.condition = outer;
+        expr
+    }
+
+    /// Parses an expression enclosed in parentheses or brackets, which may be
+    /// a struct literal even within a condition.
+    fn enclosed_expression(&mut self) -> ExprResult<'ctx> {
+        let outer = std::mem::replace(&mut self.condition, false);
+        let expr = self.expression();
+        self.condition = outer;
+        expr
+    }
+
+    /// Parses the fields of a struct literal, after its name.
+    fn struct_literal(&mut self, name: String, start: Span) -> ExprResult<'ctx> {
+        let open = self.consume(TokenKind::LeftBrace)?;
+        let mut fields = Vec::new();
+        while !self.check(&TokenKind::RightBrace) {
+            let field_start = self.peek().span;
+            let name = self.identifier()?;
+            self.consume(TokenKind::Colon)?;
+            let value = self.expression()?;
+
+            fields.push(FieldInit::new(name, value, self.span_from(field_start)));
+
+            if !self.check(&TokenKind::RightBrace) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume_closing(TokenKind::RightBrace, &open)?;
+
+        Ok(Box::new(StructExpr::new(name, fields, self.span_from(start)) as StructExpr<'ctx>))
+    }
+
+    /// Parses the path and arguments of a variant after the enum name.
+    fn variant_expression(&mut self, enum_name: String, start: Span) -> ExprResult<'ctx> {
+        self.consume(TokenKind::ColonColon)?;
+        let variant = self.identifier()?;
+
+        let mut args = Vec::new();
+        if self.check(&TokenKind::LeftParen) {
+            let open = self.advance();
+            while !self.check(&TokenKind::RightParen) {
+                args.push(self.enclosed_expression()?);
+                if !self.check(&TokenKind::RightParen) {
+                    self.consume(TokenKind::Comma)?;
+                }
+            }
+            self.consume_closing(TokenKind::RightParen, &open)?;
+        }
+
+        Ok(Box::new(VariantExpr::new(enum_name, variant, args, self.span_from(start)) as VariantExpr<'ctx>))
+    }
+
+    /// Parses a block used as an expression, whose final expression, if it
+    /// is not followed by a `;`, gives the block its value.
+    fn block_expression(&mut self) -> ExprResult<'ctx> {
+        let open = self.consume(TokenKind::LeftBrace)?;
+        let mut stmts = Vec::new();
+        let mut value = None;
+
+        while !self.check(&TokenKind::RightBrace) && !self.check(&TokenKind::Default) {
+            let expr = match self.peek().kind {
+                TokenKind::Let |
+                TokenKind::Const |
+                TokenKind::Static |
+                TokenKind::Return |
+                TokenKind::While |
+                TokenKind::For |
+                TokenKind::Label(_) |
+                TokenKind::Break |
+                TokenKind::Continue |
+                TokenKind::Function |
+                TokenKind::Struct |
+                TokenKind::Enum => {
+                    stmts.push(self.statement()?);
+                    continue;
+                }
+                // An expression ending in a block needs no `;` to be used as a
+                // statement.
+                TokenKind::If | TokenKind::Match | TokenKind::Loop | TokenKind::LeftBrace => {
+                    let expr = self.primary()?;
+                    if self.check(&TokenKind::Semicolon) {
+                        self.advance();
+                    } else if self.check(&TokenKind::RightBrace) {
+                        value = Some(expr);
+                        break;
+                    }
+                    expr
+                }
+                _ => {
+                    let expr = self.expression()?;
+                    if self.check(&TokenKind::RightBrace) {
+                        value = Some(expr);
+                        break;
+                    }
+                    self.consume(TokenKind::Semicolon)?;
+                    expr
+                }
+            };
+
+            let span = self.span_from(expr.span());
+            stmts.push(Box::new(ExprStmt::new(expr, span)));
+        }
+
+        self.consume_closing(TokenKind::RightBrace, &open)?;
+        Ok(Box::new(BlockExpr::new(stmts, value, self.span_from(open.span))))
+    }
+
+    fn if_expression(&mut self) -> ExprResult<'ctx> {
+        let start = self.consume(TokenKind::If)?.span;
+        let cond = self.condition()?;
+        let then = self.block_expression()?;
+
+        let otherwise = if self.check(&TokenKind::Else) {
+            self.advance();
+            if self.check(&TokenKind::If) {
+                Some(self.if_expression()?)
+            } else {
+                Some(self.block_expression()?)
+            }
+        } else {
+            None
+        };
+
+        Ok(Box::new(IfExpr::new(cond, then, otherwise, self.span_from(start))))
+    }
+
+    fn loop_expression(&mut self, start: Span, label: Option<String>) -> ExprResult<'ctx> {
+        self.consume(TokenKind::Loop)?;
+        let body = self.block_statement()?;
+
+        Ok(Box::new(LoopExpr::new(label, body, self.span_from(start))))
+    }
+
+    fn match_expression(&mut self) -> ExprResult<'ctx> {
+        let start = self.consume(TokenKind::Match)?.span;
+        let scrutinee = self.condition()?;
+
+        let open = self.consume(TokenKind::LeftBrace)?;
+        let mut arms = Vec::new();
+        while !self.check(&TokenKind::RightBrace) {
+            let arm_start = self.peek().span;
+            let pattern = self.pattern()?;
+            self.consume(TokenKind::FatArrow)?;
+
+            // A block ends the arm on its own; an expression needs a `,`
+            // unless it is the last arm.
+            let body = if self.check(&TokenKind::LeftBrace) {
+                let body = ArmBody::Block(self.block_statement()?);
+                if self.check(&TokenKind::Comma) {
+                    self.advance();
+                }
+                body
+            } else {
+                let body = ArmBody::Expr(self.expression()?);
+                if !self.check(&TokenKind::RightBrace) {
+                    self.consume(TokenKind::Comma)?;
+                }
+                body
+            };
+
+            arms.push(MatchArm::new(pattern, body, self.span_from(arm_start)));
+        }
+        self.consume_closing(TokenKind::RightBrace, &open)?;
+
+        Ok(Box::new(MatchExpr::new(scrutinee, arms, self.span_from(start)) as MatchExpr<'ctx>))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let start = self.peek().span;
+
+        match self.peek().kind {
+            TokenKind::Char(_) | TokenKind::Int(_) | TokenKind::Float(_) | TokenKind::True | TokenKind::False => {
+                let token = self.advance();
+                let value = match token.kind {
+                    TokenKind::Char(value) => LiteralValue::Char(value),
+                    TokenKind::True => LiteralValue::Bool(true),
+                    TokenKind::False => LiteralValue::Bool(false),
+                    _ => Self::number_value(&token)?,
+                };
+                Ok(Pattern::Literal(value, token.span))
+            }
+            TokenKind::Minus => {
+                self.advance();
+                let token = match self.peek().kind {
+                    TokenKind::Int(_) | TokenKind::Float(_) => self.advance(),
+                    _ => return Err(self.unexpected("number")),
+                };
+                let value = match Self::number_value(&token)? {
+                    LiteralValue::Int(value) => LiteralValue::Int(-value),
+                    LiteralValue::Float(value) => LiteralValue::Float(-value),
+                    value => value,
+                };
+                Ok(Pattern::Literal(value, self.span_from(start)))
+            }
+            TokenKind::Ident(_) => {
+                let name = self.identifier()?;
+                if !self.check(&TokenKind::ColonColon) {
+                    let name = if name == "_" { None } else { Some(name) };
+                    return Ok(Pattern::Any(name, start));
+                }
+
+                self.advance();
+                let variant = self.identifier()?;
+
+                let mut fields = Vec::new();
+                if self.check(&TokenKind::LeftParen) {
+                    let open = self.advance();
+                    while !self.check(&TokenKind::RightParen) {
+                        let span = self.peek().span;
+                        let name = self.identifier()?;
+                        fields.push((if name == "_" { None } else { Some(name) }, span));
+                        if !self.check(&TokenKind::RightParen) {
+                            self.consume(TokenKind::Comma)?;
+                        }
+                    }
+                    self.consume_closing(TokenKind::RightParen, &open)?;
+                }
+
+                Ok(Pattern::Variant { enum_name: name, variant, fields, span: self.span_from(start) })
+            }
+            _ => Err(self.unexpected("pattern")),
+        }
+    }
+
+    fn primary(&mut self) -> ExprResult<'ctx> {
+        let deref_operand = std::mem::take(&mut self.deref_operand);
+
+        // Leave anything that cannot start an expression in place, so error
+        // recovery can resynchronize on it.
+        match self.peek().kind {
+            TokenKind::Char(_) |
+            TokenKind::String(_) |
+            TokenKind::Int(_) |
+            TokenKind::Float(_) |
+            TokenKind::False |
+            TokenKind::True |
+            TokenKind::LeftBracket |
+            TokenKind::LeftParen |
+            TokenKind::Ident(_) |
+            TokenKind::Match |
+            TokenKind::Loop |
+            TokenKind::Label(_) |
+            TokenKind::If |
+            TokenKind::LeftBrace |
+            TokenKind::Illegal(_) => {}
+            _ => return Err(self.unexpected("expression")),
+        }
+
+        match self.peek().kind {
+            TokenKind::If => return self.if_expression(),
+            TokenKind::LeftBrace => return self.block_expression(),
+            TokenKind::Match => return self.match_expression(),
+            TokenKind::Loop => return self.loop_expression(self.peek().span, None),
+            TokenKind::Label(_) => {
+                let start = self.peek().span;
+                let label = self.label()?;
+                if !self.check(&TokenKind::Loop) {
+                    return Err(self.unexpected("`loop` after a label"));
+                }
+                return self.loop_expression(start, Some(label));
+            }
+            _ => {}
+        }
+
+        let token = self.advance();
+
+        match token.kind {
+            TokenKind::Char(value) => Ok(Box::new(LiteralExpr::new(LiteralValue::Char(value), token.span))),
+            TokenKind::String(value) => Ok(Box::new(StringExpr::new(value, token.span))),
+            TokenKind::Int(_) | TokenKind::Float(_) => self.number(&token),
             TokenKind::False => {
-                Box::new(LiteralExpr::new(LiteralValue::Bool(false)))
+                Ok(Box::new(LiteralExpr::new(LiteralValue::Bool(false), token.span)))
             }
             TokenKind::True => {
-                Box::new(LiteralExpr::new(LiteralValue::Bool(true)))
+                Ok(Box::new(LiteralExpr::new(LiteralValue::Bool(true), token.span)))
             }
             TokenKind::LeftBracket => {
                 let mut elements = Vec::new();
-                while self.lexer.peek().unwrap().kind != TokenKind::RightBracket {
-                    elements.push(self.expression());
-                    if self.lexer.peek().unwrap().kind != TokenKind::RightBracket {
-                        self.consume(TokenKind::Comma);
+                while !self.check(&TokenKind::RightBracket) {
+                    elements.push(self.enclosed_expression()?);
+                    if elements.len() == 1 && self.check(&TokenKind::Semicolon) {
+                        self.advance();
+                        let count = self.list_length()?;
+                        self.consume_closing(TokenKind::RightBracket, &token)?;
+                        let value = elements.pop().unwrap();
+                        return Ok(Box::new(RepeatExpr::new(value, count, self.span_from(token.span)) as RepeatExpr<'ctx>));
+                    }
+                    if !self.check(&TokenKind::RightBracket) {
+                        self.consume(TokenKind::Comma)?;
                     }
                 }
-                self.consume(TokenKind::RightBracket);
-                Box::new(ListExpr::new(elements) as ListExpr<'ctx>)
+                self.consume_closing(TokenKind::RightBracket, &token)?;
+                let list = Box::new(ListExpr::new(elements, self.span_from(token.span)) as ListExpr<'ctx>);
+                self.postfix(list, deref_operand)
             }
             TokenKind::LeftParen => {
-                let expr = self.expression();
-                self.consume(TokenKind::RightParen);
-                expr
+                let expr = self.enclosed_expression()?;
+                self.consume_closing(TokenKind::RightParen, &token)?;
+                self.postfix(expr, deref_operand)
             },
             TokenKind::Ident(name) => {
-                if self.lexer.peek().unwrap().kind == TokenKind::Assign {
-                    self.lexer.next();
-                    let value = self.expression();
-                    Box::new(VarAssignExpr::new(name, value) as VarAssignExpr<'ctx>)
-                } else if self.lexer.peek().unwrap().kind == TokenKind::LeftParen {
-                    self.lexer.next();
+                if self.check(&TokenKind::LeftParen) {
+                    let open = self.advance();
                     let mut args = Vec::new();
-                    while self.lexer.peek().unwrap().kind != TokenKind::RightParen {
-                        args.push(self.expression());
-                        if self.lexer.peek().unwrap().kind != TokenKind::RightParen {
-                            self.consume(TokenKind::Comma);
+                    while !self.check(&TokenKind::RightParen) {
+                        args.push(self.enclosed_expression()?);
+                        if !self.check(&TokenKind::RightParen) {
+                            self.consume(TokenKind::Comma)?;
                         }
                     }
-                    self.consume(TokenKind::RightParen);
-                    Box::new(CallExpr::new(name, args) as CallExpr<'ctx>)
+                    self.consume_closing(TokenKind::RightParen, &open)?;
+                    let call = Box::new(CallExpr::new(name, args, self.span_from(token.span)) as CallExpr<'ctx>);
+                    self.postfix(call, deref_operand)
+                } else if self.check(&TokenKind::ColonColon) {
+                    self.variant_expression(name, token.span)
+                } else if self.check(&TokenKind::LeftBrace) && !self.condition {
+                    let literal = self.struct_literal(name, token.span)?;
+                    self.postfix(literal, deref_operand)
                 } else {
-                    let var_expr = VariableExpr::new(name);
-                    if self.lexer.peek().unwrap().kind == TokenKind::LeftBracket {
-                        let mut indices = Vec::new();
-                        loop {
-                            self.lexer.next();
-                            let index = self.expression();
-                            indices.push(index);
-                            self.consume(TokenKind::RightBracket);
-                            if self.lexer.peek().unwrap().kind != TokenKind::LeftBracket {
-                                break;
-                            }
-                        }
-                        return Box::new(IndexExpr::new(var_expr, indices) as IndexExpr<'ctx>);
-                    }
-                    return Box::new(var_expr);
+                    let lvalue = self.lvalue(LValue::Variable(VariableExpr::new(name, token.span)))?;
+                    self.assignment(lvalue, token.span, !deref_operand)
                 }
             },
-            TokenKind::Illegal(lexeme) => panic!("Syntax Error (line: {}, column: {}): Illegal token '{}'", token.line, token.column, lexeme),
-            _ => panic!("Syntax Error (line: {}, column: {}): Unexpected token '{:?}'", token.line, token.column, token.kind),
+            TokenKind::Illegal(lexeme) => Err(Diagnostic::error(format!("illegal token `{}`", lexeme)).with_span(token.span)),
+            _ => unreachable!(),
         }
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::lexer::Lexer;
+
+    use super::Parser;
+
+    /// The messages of the diagnostics produced while parsing `source`.
+    fn parse(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(Lexer::new(source.to_string(), 0));
+        let (_, diagnostics) = parser.parse();
+        diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn accepts_a_valid_program() {
+        let source = "
+            struct Point { x: i32, y: i32 }
+            enum Shape { Dot, Circle(i32) }
+            fn main() -> i32 {
+                let p = Point { x: 1, y: 2 };
+                let xs: [i32; 3] = [1, 2, 3];
+                'outer: for i in 0..3 {
+                    loop { break 'outer; }
+                }
+                return p.x + xs[1];
+            }";
+        assert_eq!(parse(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_every_syntax_error() {
+        let source = "
+            fn main() -> i32 {
+                let a = ;
+                let b = 1
+                return a + b;
+            }";
+        assert_eq!(parse(source), ["expected expression, found `;`", "expected `;`, found `return`"]);
+    }
+
+    #[test]
+    fn reports_an_unmatched_closing_delimiter() {
+        assert_eq!(parse("fn main() -> i32 { return 0; } }"), ["unexpected closing delimiter `}`"]);
+    }
+
+    #[test]
+    fn accepts_a_bare_return() {
+        assert_eq!(parse("fn f() { return; }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reads_a_name_and_a_brace_in_a_condition_as_the_block() {
+        let source = "
+            struct P { x: i32 }
+            fn main() -> i32 {
+                let x = 1;
+                if x == x { return 1; }
+                if (P { x: 1 }).x == 1 { return 2; }
+                return 0;
+            }";
+        assert_eq!(parse(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_a_struct_literal_in_a_condition_without_parentheses() {
+        let source = "
+            struct P { x: i32 }
+            fn main() -> i32 {
+                if P { x: 1 }.x == 1 { return 2; }
+                return 0;
+            }";
+        assert!(!parse(source).is_empty());
+    }
+
+    #[test]
+    fn reports_literals_that_are_too_large() {
+        assert_eq!(parse("const A: u64 = 99999999999999999999999;"), ["integer literal is too large"]);
+        assert_eq!(parse("fn f() { let xs: [u8; 9999999999] = ys; }"), ["list length is too large"]);
+    }
+
+    #[test]
+    fn reports_illegal_tokens() {
+        assert_eq!(parse("fn main() -> i32 { return $; }"), ["illegal token `$`"]);
+    }
+
+    #[test]
+    fn reports_errors_of_the_lexer() {
+        assert_eq!(parse("const C: char = 'λ';"), ["non-ASCII character in character literal"]);
+        assert_eq!(parse("const S: str = \"a\\q\";"), ["invalid escape sequence"]);
+    }
+}
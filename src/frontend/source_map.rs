@@ -0,0 +1,66 @@
+#[derive(Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    line_starts: Vec<usize>,
+}
+
+/// Owns the text of every file being compiled and converts the byte offsets
+/// stored in spans back into human readable positions.
+#[derive(Default, Clone)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceFile {
+    pub fn new(name: String, source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    /// Returns the zero-based line containing the byte `offset`.
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        }
+    }
+
+    /// Returns the text of the zero-based `line`, without its line terminator.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line];
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.source.len());
+        self.source[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// Converts a byte offset into a zero-based (line, column) pair, where the
+    /// column counts characters rather than bytes.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.line_index(offset);
+        let column = self.source[self.line_starts[line]..offset].chars().count();
+        (line, column)
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, name: String, source: String) -> usize {
+        self.files.push(SourceFile::new(name, source));
+        self.files.len() - 1
+    }
+
+    pub fn file(&self, file_id: usize) -> &SourceFile {
+        &self.files[file_id]
+    }
+}
@@ -0,0 +1,23 @@
+/// A half-open range `start..end` of byte offsets into the file `file_id`
+/// of the `SourceMap`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file_id: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file_id: usize, start: usize, end: usize) -> Span {
+        Span {
+            file_id,
+            start,
+            end,
+        }
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.file_id, self.start.min(other.start), self.end.max(other.end))
+    }
+}
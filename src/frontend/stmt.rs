@@ -1,14 +1,18 @@
-use super::{visitor::StmtVisitor, expr::Expr, type_::Type};
+use crate::diagnostic::Diagnostic;
+
+use super::{visitor::StmtVisitor, expr::Expr, type_::{Type, LiteralType, IntType}, span::Span};
 
 use proto_rs_macros::Stmt;
 
 pub trait Stmt<'ctx> {
-    fn accept(&self, visitor: &mut dyn StmtVisitor<'ctx>);
+    fn accept(&self, visitor: &mut dyn StmtVisitor<'ctx>) -> Result<(), Diagnostic>;
+    fn span(&self) -> Span;
 }
 
 #[derive(Stmt)]
 pub struct ExprStmt<'ctx> {
     pub expr: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
 }
 
 #[derive(Stmt)]
@@ -16,16 +20,39 @@ pub struct VarDeclStmt<'ctx> {
     pub name: String,
     pub type_: Type,
     pub expr: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+/// `const NAME: T = value;`, a global whose value is computed at compile
+/// time and cannot be assigned to.
+#[derive(Stmt)]
+pub struct ConstDeclStmt<'ctx> {
+    pub name: String,
+    pub type_: Type,
+    pub value: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+/// `static NAME: T = value;`, a global variable shared by every function and
+/// initialized with a value computed at compile time.
+#[derive(Stmt)]
+pub struct StaticDeclStmt<'ctx> {
+    pub name: String,
+    pub type_: Type,
+    pub value: Box<dyn Expr<'ctx> + 'ctx>,
+    pub span: Span,
 }
 
 #[derive(Stmt)]
 pub struct ReturnStmt<'ctx> {
-    pub expr: Box<dyn Expr<'ctx> + 'ctx>,
+    pub expr: Option<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
 }
 
 #[derive(Stmt)]
 pub struct BlockStmt<'ctx> {
     pub stmts: Vec<Box<dyn Stmt<'ctx> + 'ctx>>,
+    pub span: Span,
 }
 
 #[derive(Stmt)]
@@ -33,32 +60,63 @@ pub struct IfStmt<'ctx> {
     pub cond: Box<dyn Expr<'ctx> + 'ctx>,
     pub then: Box<dyn Stmt<'ctx> + 'ctx>,
     pub otherwise: Option<Box<dyn Stmt<'ctx> + 'ctx>>,
+    pub span: Span,
 }
 
 #[derive(Stmt)]
 pub struct WhileStmt<'ctx> {
+    pub label: Option<String>,
     pub cond: Box<dyn Expr<'ctx> + 'ctx>,
     pub body: Box<dyn Stmt<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+/// What a `for` loop walks over.
+pub enum ForIterable<'ctx> {
+    /// `start..end`, counting up from `start` and stopping before `end`.
+    Range(Box<dyn Expr<'ctx> + 'ctx>, Box<dyn Expr<'ctx> + 'ctx>),
+    /// A list, visiting each of its elements in order.
+    List(Box<dyn Expr<'ctx> + 'ctx>),
+}
+
+#[derive(Stmt)]
+pub struct ForStmt<'ctx> {
+    pub label: Option<String>,
+    pub name: String,
+    pub iterable: ForIterable<'ctx>,
+    pub body: Box<dyn Stmt<'ctx> + 'ctx>,
+    pub span: Span,
 }
 
+/// `break`, leaving the innermost loop or the one labeled `label`. A `break`
+/// out of a `loop` may give the loop its value.
 #[derive(Stmt)]
-pub struct BreakStmt;
+pub struct BreakStmt<'ctx> {
+    pub label: Option<String>,
+    pub value: Option<Box<dyn Expr<'ctx> + 'ctx>>,
+    pub span: Span,
+}
 
 #[derive(Stmt)]
-pub struct ContinueStmt;
+pub struct ContinueStmt {
+    pub label: Option<String>,
+    pub span: Span,
+}
 
 
 #[derive(Clone, Eq, PartialEq)]
 pub struct Param {
     pub name: String,
     pub type_: Type,
+    pub span: Span,
 }
 
 impl Param {
-    pub fn new(name: String, type_: Type) -> Self {
+    pub fn new(name: String, type_: Type, span: Span) -> Self {
         Self {
             name,
             type_,
+            span,
         }
     }
 }
@@ -68,10 +126,73 @@ pub struct FunctionDeclStmt {
     pub name: String,
     pub params: Vec<Param>,
     pub return_type: Type,
+    pub span: Span,
+}
+
+impl FunctionDeclStmt {
+    /// The type the function returns. Without a declared return type `main`
+    /// returns the `i32` exit status of the program, and any other function
+    /// returns nothing.
+    pub fn resolved_return_type(&self) -> Type {
+        match &self.return_type {
+            Type::Inferred if self.name == "main" => Type::Literal(LiteralType::Int(IntType::I32)),
+            Type::Inferred => Type::Void,
+            return_type => return_type.clone(),
+        }
+    }
 }
 
 #[derive(Stmt)]
 pub struct FunctionDefStmt<'ctx> {
     pub func_decl: FunctionDeclStmt,
     pub body: Box<dyn Stmt<'ctx> + 'ctx>,
+    pub span: Span,
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub type_: Type,
+    pub span: Span,
+}
+
+impl Field {
+    pub fn new(name: String, type_: Type, span: Span) -> Self {
+        Self {
+            name,
+            type_,
+            span,
+        }
+    }
+}
+
+#[derive(Stmt)]
+pub struct StructDeclStmt {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub span: Span,
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Type>,
+    pub span: Span,
+}
+
+impl Variant {
+    pub fn new(name: String, fields: Vec<Type>, span: Span) -> Self {
+        Self {
+            name,
+            fields,
+            span,
+        }
+    }
+}
+
+#[derive(Stmt)]
+pub struct EnumDeclStmt {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    pub span: Span,
 }
@@ -1,16 +1,18 @@
+use std::fmt;
+
+use super::span::Span;
+
 #[derive(Debug, Default, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
-    pub line: usize,
-    pub column: usize,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(kind: TokenKind, line: usize, column: usize) -> Token {
+    pub fn new(kind: TokenKind, span: Span) -> Token {
         Token {
             kind,
-            line,
-            column,
+            span,
         }
     }
 }
@@ -28,9 +30,20 @@ pub enum TokenKind {
     Int(String),
     Float(String),
     String(String),
+    Label(String),
 
     // Operators
     Assign,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    RemainderAssign,
+    AmpersandAssign,
+    PipeAssign,
+    CaretAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
     Plus,
     Minus,
     Bang,
@@ -38,6 +51,13 @@ pub enum TokenKind {
     Slash,
     Remainder,
 
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+
     Less,
     LessEqual,
     Greater,
@@ -46,7 +66,14 @@ pub enum TokenKind {
     Equal,
     NotEqual,
 
+    And,
+    Or,
+
     // Types
+    Bool,
+    CharType,
+    Str,
+
     U8,
     U16,
     U32,
@@ -62,9 +89,13 @@ pub enum TokenKind {
 
     // Delimiters
     Comma,
+    Dot,
+    DotDot,
     Colon,
+    ColonColon,
     Semicolon,
     RightArrow,
+    FatArrow,
     
     LeftParen,
     RightParen,
@@ -75,13 +106,164 @@ pub enum TokenKind {
 
     // Keywords
     Function,
+    Struct,
+    Enum,
+    Match,
     Let,
     True,
     False,
     If,
     Else,
     While,
+    Loop,
+    For,
+    In,
+    As,
+    Const,
+    Static,
+    Mut,
     Break,
     Continue,
     Return,
 }
+
+impl TokenKind {
+    pub fn is_assignment(&self) -> bool {
+        *self == TokenKind::Assign || self.compound_operator().is_some()
+    }
+
+    /// The binary operator a compound assignment such as `+=` applies.
+    pub fn compound_operator(&self) -> Option<TokenKind> {
+        match self {
+            TokenKind::PlusAssign => Some(TokenKind::Plus),
+            TokenKind::MinusAssign => Some(TokenKind::Minus),
+            TokenKind::AsteriskAssign => Some(TokenKind::Asterisk),
+            TokenKind::SlashAssign => Some(TokenKind::Slash),
+            TokenKind::RemainderAssign => Some(TokenKind::Remainder),
+            TokenKind::AmpersandAssign => Some(TokenKind::Ampersand),
+            TokenKind::PipeAssign => Some(TokenKind::Pipe),
+            TokenKind::CaretAssign => Some(TokenKind::Caret),
+            TokenKind::ShiftLeftAssign => Some(TokenKind::ShiftLeft),
+            TokenKind::ShiftRightAssign => Some(TokenKind::ShiftRight),
+            _ => None,
+        }
+    }
+
+    /// Whether this is one of the operators that only apply to integers.
+    pub fn is_bitwise(&self) -> bool {
+        matches!(self,
+            TokenKind::Ampersand |
+            TokenKind::Pipe |
+            TokenKind::Caret |
+            TokenKind::Tilde |
+            TokenKind::ShiftLeft |
+            TokenKind::ShiftRight)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lexeme = match self {
+            TokenKind::Default => return write!(f, "end of file"),
+            TokenKind::Illegal(lexeme) => return write!(f, "`{}`", lexeme),
+            TokenKind::Ident(name) => return write!(f, "identifier `{}`", name),
+            TokenKind::Char(c) => return write!(f, "character literal {:?}", c),
+            TokenKind::Int(value) => return write!(f, "integer literal `{}`", value),
+            TokenKind::Float(value) => return write!(f, "float literal `{}`", value),
+            TokenKind::String(value) => return write!(f, "string literal {:?}", value),
+            TokenKind::Label(name) => return write!(f, "label `'{}`", name),
+
+            TokenKind::Assign => "=",
+            TokenKind::PlusAssign => "+=",
+            TokenKind::MinusAssign => "-=",
+            TokenKind::AsteriskAssign => "*=",
+            TokenKind::SlashAssign => "/=",
+            TokenKind::RemainderAssign => "%=",
+            TokenKind::AmpersandAssign => "&=",
+            TokenKind::PipeAssign => "|=",
+            TokenKind::CaretAssign => "^=",
+            TokenKind::ShiftLeftAssign => "<<=",
+            TokenKind::ShiftRightAssign => ">>=",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Bang => "!",
+            TokenKind::Asterisk => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Remainder => "%",
+
+            TokenKind::Ampersand => "&",
+            TokenKind::Pipe => "|",
+            TokenKind::Caret => "^",
+            TokenKind::Tilde => "~",
+            TokenKind::ShiftLeft => "<<",
+            TokenKind::ShiftRight => ">>",
+
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+
+            TokenKind::Equal => "==",
+            TokenKind::NotEqual => "!=",
+
+            TokenKind::And => "&&",
+            TokenKind::Or => "||",
+
+            TokenKind::Bool => "bool",
+            TokenKind::CharType => "char",
+            TokenKind::Str => "str",
+
+            TokenKind::U8 => "u8",
+            TokenKind::U16 => "u16",
+            TokenKind::U32 => "u32",
+            TokenKind::U64 => "u64",
+
+            TokenKind::I8 => "i8",
+            TokenKind::I16 => "i16",
+            TokenKind::I32 => "i32",
+            TokenKind::I64 => "i64",
+
+            TokenKind::F32 => "f32",
+            TokenKind::F64 => "f64",
+
+            TokenKind::Comma => ",",
+            TokenKind::Dot => ".",
+            TokenKind::DotDot => "..",
+            TokenKind::Colon => ":",
+            TokenKind::ColonColon => "::",
+            TokenKind::Semicolon => ";",
+            TokenKind::RightArrow => "->",
+            TokenKind::FatArrow => "=>",
+
+            TokenKind::LeftParen => "(",
+            TokenKind::RightParen => ")",
+            TokenKind::LeftBrace => "{",
+            TokenKind::RightBrace => "}",
+            TokenKind::LeftBracket => "[",
+            TokenKind::RightBracket => "]",
+
+            TokenKind::Function => "fn",
+            TokenKind::Struct => "struct",
+            TokenKind::Enum => "enum",
+            TokenKind::Match => "match",
+            TokenKind::Let => "let",
+            TokenKind::True => "true",
+            TokenKind::False => "false",
+            TokenKind::If => "if",
+            TokenKind::Else => "else",
+            TokenKind::While => "while",
+            TokenKind::Loop => "loop",
+            TokenKind::For => "for",
+            TokenKind::In => "in",
+            TokenKind::As => "as",
+            TokenKind::Const => "const",
+            TokenKind::Static => "static",
+            TokenKind::Mut => "mut",
+            TokenKind::Break => "break",
+            TokenKind::Continue => "continue",
+            TokenKind::Return => "return",
+        };
+
+        write!(f, "`{}`", lexeme)
+    }
+}
@@ -1,3 +1,6 @@
+use std::cmp::Ordering;
+use std::fmt;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum IntType {
     U8,
@@ -11,6 +14,48 @@ pub enum IntType {
     I64,
 }
 
+impl IntType {
+    pub fn is_signed(&self) -> bool {
+        matches!(self, IntType::I8 | IntType::I16 | IntType::I32 | IntType::I64)
+    }
+
+    pub fn bit_width(&self) -> u32 {
+        match self {
+            IntType::U8 | IntType::I8 => 8,
+            IntType::U16 | IntType::I16 => 16,
+            IntType::U32 | IntType::I32 => 32,
+            IntType::U64 | IntType::I64 => 64,
+        }
+    }
+
+    pub fn min_value(&self) -> i128 {
+        if self.is_signed() {
+            -(1 << (self.bit_width() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max_value(&self) -> i128 {
+        if self.is_signed() {
+            (1 << (self.bit_width() - 1)) - 1
+        } else {
+            (1 << self.bit_width()) - 1
+        }
+    }
+
+    /// The type both operands of a binary operator are converted to: the
+    /// wider of the two, or the unsigned one if they have the same width.
+    pub fn common(self, other: IntType) -> IntType {
+        match self.bit_width().cmp(&other.bit_width()) {
+            Ordering::Greater => self,
+            Ordering::Less => other,
+            Ordering::Equal if self.is_signed() => other,
+            Ordering::Equal => self,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum FloatType {
     F32,
@@ -20,15 +65,141 @@ pub enum FloatType {
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum LiteralType {
     Bool,
+    /// The single byte holding the code of an ASCII character.
     Char,
     Int(IntType),
     Float(FloatType),
 }
 
+/// What a pointer allows to be done with the place it points to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PointerKind {
+    /// `&T`, which can only be read through.
+    Shared,
+    /// `&mut T`, which can also be assigned through.
+    Mutable,
+    /// `*T`, an unchecked pointer as C has them.
+    Raw,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Type {
     Literal(LiteralType),
     List(Box<Type>, usize),
+    /// A view of a run of elements of a list: a pointer to the first of them
+    /// together with their count, which is only known at run time. A `&[T]`
+    /// can only be read through, while a `[T]` can also be assigned through.
+    Slice(Box<Type>, PointerKind),
+    /// A string slice: a pointer to bytes together with their count.
+    Str,
+    /// A struct or an enum, told apart by looking up the name among the
+    /// declared types.
+    Named(String),
+    Pointer(Box<Type>, PointerKind),
     Inferred,
-    Void
+    Void,
+    /// The type of something that has already been reported as wrong. It is
+    /// accepted wherever a type is required, so that the mistake is not
+    /// reported again everywhere it is used.
+    Error,
+}
+
+impl Type {
+    /// The type arithmetic is done in for a value of this type. A `char`
+    /// takes part in arithmetic as the `u8` holding its code.
+    pub fn numeric(&self) -> Option<LiteralType> {
+        match self {
+            Type::Literal(LiteralType::Char) => Some(LiteralType::Int(IntType::U8)),
+            Type::Literal(type_ @ (LiteralType::Int(_) | LiteralType::Float(_))) => Some(*type_),
+            _ => None,
+        }
+    }
+
+    /// The names of the structs and enums this type is made of.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Type::Named(name) => vec![name],
+            Type::List(element, _) | Type::Slice(element, _) | Type::Pointer(element, _) => element.names(),
+            _ => vec![],
+        }
+    }
+
+    /// The narrowest type that can hold values of both `self` and `other`,
+    /// used to give the elements of a constant list a single type.
+    pub fn join(self, other: Type) -> Option<Type> {
+        match (self, other) {
+            (Type::Literal(LiteralType::Int(left)), Type::Literal(LiteralType::Int(right))) => {
+                Some(Type::Literal(LiteralType::Int(left.common(right))))
+            }
+            (Type::Literal(LiteralType::Float(left)), Type::Literal(LiteralType::Float(right))) => {
+                Some(Type::Literal(LiteralType::Float(if left == FloatType::F64 { left } else { right })))
+            }
+            (Type::List(left, left_len), Type::List(right, right_len)) if left_len == right_len => {
+                Some(Type::List(Box::new(left.join(*right)?), left_len))
+            }
+            (Type::Error, type_) | (type_, Type::Error) => Some(type_),
+            (left, right) if left == right => Some(left),
+            _ => None,
+        }
+    }
+
+    /// Whether a value of this type can be used where `expected` is required.
+    /// Apart from an exact match, a `char` is accepted as the `u8` holding
+    /// its code, and a `&mut T` as a `&T` or a `*T`.
+    pub fn coerces_to(&self, expected: &Type) -> bool {
+        match (self, expected) {
+            (Type::Error, _) | (_, Type::Error) => true,
+            (Type::Literal(LiteralType::Char), Type::Literal(LiteralType::Int(IntType::U8))) => true,
+            (Type::Pointer(pointee, PointerKind::Mutable), Type::Pointer(expected, PointerKind::Shared | PointerKind::Raw)) => pointee == expected,
+            (Type::Slice(element, PointerKind::Mutable), Type::Slice(expected, PointerKind::Shared)) => element == expected,
+            _ => self == expected,
+        }
+    }
+}
+
+impl fmt::Display for IntType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for FloatType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FloatType::F32 => write!(f, "f32"),
+            FloatType::F64 => write!(f, "f64"),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Literal(LiteralType::Bool) => write!(f, "bool"),
+            Type::Literal(LiteralType::Char) => write!(f, "char"),
+            Type::Literal(LiteralType::Int(type_)) => write!(f, "{}", type_),
+            Type::Literal(LiteralType::Float(type_)) => write!(f, "{}", type_),
+            Type::List(element, len) => write!(f, "[{}; {}]", element, len),
+            Type::Slice(element, PointerKind::Shared) => write!(f, "&[{}]", element),
+            Type::Slice(element, _) => write!(f, "[{}]", element),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Str => write!(f, "str"),
+            Type::Pointer(pointee, PointerKind::Shared) => write!(f, "&{}", pointee),
+            Type::Pointer(pointee, PointerKind::Mutable) => write!(f, "&mut {}", pointee),
+            Type::Pointer(pointee, PointerKind::Raw) => write!(f, "*{}", pointee),
+            Type::Inferred => write!(f, "_"),
+            Type::Void => write!(f, "()"),
+            Type::Error => write!(f, "{{error}}"),
+        }
+    }
 }
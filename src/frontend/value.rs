@@ -1,5 +1,7 @@
 use inkwell::values::BasicValueEnum;
-use std::ops::{Add, Sub, Mul, Div, Rem, Neg};
+
+use super::type_::{Type, LiteralType, IntType, FloatType};
+use std::ops::{Add, Sub, Mul, Div, Rem, Neg, Not};
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub enum IntValue {
@@ -7,99 +9,129 @@ pub enum IntValue {
     I16(i16),
     I32(i32),
     I64(i64),
+
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+
+    /// An integer literal, or a constant folded from literals only, whose
+    /// type is decided by where it is used. It is kept at full width so that
+    /// it can be range checked against that type.
+    Untyped(i128),
 }
 
 impl IntValue {
 
-    pub fn is_zero(&self) -> bool {
-        match self {
-            IntValue::I8(value) => value == &0,
-            IntValue::I16(value) => value == &0,
-            IntValue::I32(value) => value == &0,
-            IntValue::I64(value) => value == &0,
+    /// Converts `value` to `type_`, wrapping around like an `as` cast.
+    pub fn from_i128(value: i128, type_: IntType) -> IntValue {
+        match type_ {
+            IntType::I8 => IntValue::I8(value as i8),
+            IntType::I16 => IntValue::I16(value as i16),
+            IntType::I32 => IntValue::I32(value as i32),
+            IntType::I64 => IntValue::I64(value as i64),
+            IntType::U8 => IntValue::U8(value as u8),
+            IntType::U16 => IntValue::U16(value as u16),
+            IntType::U32 => IntValue::U32(value as u32),
+            IntType::U64 => IntValue::U64(value as u64),
         }
     }
-}
 
-impl Neg for IntValue {
-    type Output = IntValue;
-
-    fn neg(self) -> IntValue {
+    pub fn get_type(&self) -> IntType {
         match self {
-            IntValue::I8(value) => IntValue::I8(-value),
-            IntValue::I16(value) => IntValue::I16(-value),
-            IntValue::I32(value) => IntValue::I32(-value),
-            IntValue::I64(value) => IntValue::I64(-value),
+            IntValue::I8(_) => IntType::I8,
+            IntValue::I16(_) => IntType::I16,
+            IntValue::I32(_) => IntType::I32,
+            IntValue::I64(_) => IntType::I64,
+            IntValue::U8(_) => IntType::U8,
+            IntValue::U16(_) => IntType::U16,
+            IntValue::U32(_) => IntType::U32,
+            IntValue::U64(_) => IntType::U64,
+            // An `i32` unless it is too large for one.
+            IntValue::Untyped(value) => [IntType::I32, IntType::I64, IntType::U64].into_iter()
+                .find(|type_| IntValue::fits(*value, *type_))
+                .unwrap_or(IntType::I64),
         }
     }
-}
 
-impl Add for IntValue {
-    type Output = IntValue;
+    /// Whether `type_` can hold `value`.
+    pub fn fits(value: i128, type_: IntType) -> bool {
+        value >= type_.min_value() && value <= type_.max_value()
+    }
 
-    fn add(self, other: IntValue) -> IntValue {
-        match (self, other) {
-            (IntValue::I8(left), IntValue::I8(right)) => IntValue::I8(left + right),
-            (IntValue::I16(left), IntValue::I16(right)) => IntValue::I16(left + right),
-            (IntValue::I32(left), IntValue::I32(right)) => IntValue::I32(left + right),
-            (IntValue::I64(left), IntValue::I64(right)) => IntValue::I64(left + right),
-            _ => panic!("Unexpected type"),
-        }
+    /// Whether `value` can be held by some integer type, which any untyped
+    /// constant must be.
+    pub fn fits_any(value: i128) -> bool {
+        value >= IntType::I64.min_value() && value <= IntType::U64.max_value()
     }
-}
 
-impl Sub for IntValue {
-    type Output = IntValue;
+    pub fn is_zero(&self) -> bool {
+        self.as_i128() == 0
+    }
 
-    fn sub(self, other: IntValue) -> IntValue {
-        match (self, other) {
-            (IntValue::I8(left), IntValue::I8(right)) => IntValue::I8(left - right),
-            (IntValue::I16(left), IntValue::I16(right)) => IntValue::I16(left - right),
-            (IntValue::I32(left), IntValue::I32(right)) => IntValue::I32(left - right),
-            (IntValue::I64(left), IntValue::I64(right)) => IntValue::I64(left - right),
-            _ => panic!("Unexpected type"),
+    pub fn as_i128(&self) -> i128 {
+        match self {
+            IntValue::I8(value) => *value as i128,
+            IntValue::I16(value) => *value as i128,
+            IntValue::I32(value) => *value as i128,
+            IntValue::I64(value) => *value as i128,
+            IntValue::U8(value) => *value as i128,
+            IntValue::U16(value) => *value as i128,
+            IntValue::U32(value) => *value as i128,
+            IntValue::U64(value) => *value as i128,
+            IntValue::Untyped(value) => *value,
         }
     }
-}
 
-impl Mul for IntValue {
-    type Output = IntValue;
+    pub fn cast(&self, type_: IntType) -> IntValue {
+        IntValue::from_i128(self.as_i128(), type_)
+    }
 
-    fn mul(self, other: IntValue) -> IntValue {
-        match (self, other) {
-            (IntValue::I8(left), IntValue::I8(right)) => IntValue::I8(left * right),
-            (IntValue::I16(left), IntValue::I16(right)) => IntValue::I16(left * right),
-            (IntValue::I32(left), IntValue::I32(right)) => IntValue::I32(left * right),
-            (IntValue::I64(left), IntValue::I64(right)) => IntValue::I64(left * right),
-            _ => panic!("Unexpected type"),
-        }
+    /// Converts `value` to `type_` like an `as` cast, rounding towards zero
+    /// and saturating at the bounds of `type_`. NaN becomes zero.
+    pub fn from_float(value: FloatValue, type_: IntType) -> IntValue {
+        let value = (value.as_f64() as i128).clamp(type_.min_value(), type_.max_value());
+        IntValue::from_i128(value, type_)
+    }
+
+    /// The value used as a shift amount.
+    pub fn as_shift_amount(&self) -> u32 {
+        self.as_i128() as u32
     }
 }
 
-impl Div for IntValue {
+impl Neg for IntValue {
     type Output = IntValue;
 
-    fn div(self, other: IntValue) -> IntValue {
-        match (self, other) {
-            (IntValue::I8(left), IntValue::I8(right)) => IntValue::I8(left / right),
-            (IntValue::I16(left), IntValue::I16(right)) => IntValue::I16(left / right),
-            (IntValue::I32(left), IntValue::I32(right)) => IntValue::I32(left / right),
-            (IntValue::I64(left), IntValue::I64(right)) => IntValue::I64(left / right),
-            _ => panic!("Unexpected type"),
+    fn neg(self) -> IntValue {
+        match self {
+            IntValue::I8(value) => IntValue::I8(value.wrapping_neg()),
+            IntValue::I16(value) => IntValue::I16(value.wrapping_neg()),
+            IntValue::I32(value) => IntValue::I32(value.wrapping_neg()),
+            IntValue::I64(value) => IntValue::I64(value.wrapping_neg()),
+            IntValue::U8(value) => IntValue::U8(value.wrapping_neg()),
+            IntValue::U16(value) => IntValue::U16(value.wrapping_neg()),
+            IntValue::U32(value) => IntValue::U32(value.wrapping_neg()),
+            IntValue::U64(value) => IntValue::U64(value.wrapping_neg()),
+            IntValue::Untyped(value) => IntValue::Untyped(-value),
         }
     }
 }
 
-impl Rem for IntValue {
+impl Not for IntValue {
     type Output = IntValue;
 
-    fn rem(self, other: IntValue) -> IntValue {
-        match (self, other) {
-            (IntValue::I8(left), IntValue::I8(right)) => IntValue::I8(left % right),
-            (IntValue::I16(left), IntValue::I16(right)) => IntValue::I16(left % right),
-            (IntValue::I32(left), IntValue::I32(right)) => IntValue::I32(left % right),
-            (IntValue::I64(left), IntValue::I64(right)) => IntValue::I64(left % right),
-            _ => panic!("Unexpected type"),
+    fn not(self) -> IntValue {
+        match self {
+            IntValue::I8(value) => IntValue::I8(!value),
+            IntValue::I16(value) => IntValue::I16(!value),
+            IntValue::I32(value) => IntValue::I32(!value),
+            IntValue::I64(value) => IntValue::I64(!value),
+            IntValue::U8(value) => IntValue::U8(!value),
+            IntValue::U16(value) => IntValue::U16(!value),
+            IntValue::U32(value) => IntValue::U32(!value),
+            IntValue::U64(value) => IntValue::U64(!value),
+            IntValue::Untyped(value) => IntValue::Untyped(!value),
         }
     }
 }
@@ -107,7 +139,16 @@ impl Rem for IntValue {
 #[derive(Debug, Copy, Clone)]
 pub enum IntegerValue<'ctx> {
     Int(IntValue),
-    LLVMInt(inkwell::values::IntValue<'ctx>),
+    LLVMInt(inkwell::values::IntValue<'ctx>, IntType),
+}
+
+impl<'ctx> IntegerValue<'ctx> {
+    pub fn get_type(&self) -> IntType {
+        match self {
+            IntegerValue::Int(value) => value.get_type(),
+            IntegerValue::LLVMInt(_, type_) => *type_,
+        }
+    }
 }
 
 impl<'ctx> Into<IntegerValue<'ctx>> for IntValue {
@@ -125,12 +166,6 @@ impl<'ctx> Into<IntValue> for IntegerValue<'ctx> {
     }
 }
 
-impl<'ctx> Into<IntegerValue<'ctx>> for inkwell::values::IntValue<'ctx> {
-    fn into(self) -> IntegerValue<'ctx> {
-        IntegerValue::LLVMInt(self)
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub enum FloatValue {
     F32(f32),
@@ -138,12 +173,43 @@ pub enum FloatValue {
 }
 
 impl FloatValue {
+    pub fn get_type(&self) -> FloatType {
+        match self {
+            FloatValue::F32(_) => FloatType::F32,
+            FloatValue::F64(_) => FloatType::F64,
+        }
+    }
+
     pub fn is_zero(&self) -> bool {
         match self {
             FloatValue::F32(value) => value == &0.0,
             FloatValue::F64(value) => value == &0.0,
         }
     }
+
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            FloatValue::F32(value) => *value as f64,
+            FloatValue::F64(value) => *value,
+        }
+    }
+
+    /// Converts `value` to `type_`, rounding to the nearest value it holds.
+    pub fn from_int(value: IntValue, type_: FloatType) -> FloatValue {
+        match type_ {
+            FloatType::F32 => FloatValue::F32(value.as_i128() as f32),
+            FloatType::F64 => FloatValue::F64(value.as_i128() as f64),
+        }
+    }
+
+    /// Converts the value to `type_`, rounding to the nearest `f32` when
+    /// narrowing.
+    pub fn cast(&self, type_: FloatType) -> FloatValue {
+        match type_ {
+            FloatType::F32 => FloatValue::F32(self.as_f64() as f32),
+            FloatType::F64 => FloatValue::F64(self.as_f64()),
+        }
+    }
 }
 
 impl Neg for FloatValue {
@@ -220,18 +286,21 @@ impl Rem for FloatValue {
 #[derive(Debug, Copy, Clone)]
 pub enum FloatingValue<'ctx> {
     Float(FloatValue),
-    LLVMFloat(inkwell::values::FloatValue<'ctx>),
+    LLVMFloat(inkwell::values::FloatValue<'ctx>, FloatType),
 }
 
-impl<'ctx> Into<FloatingValue<'ctx>> for FloatValue {
-    fn into(self) -> FloatingValue<'ctx> {
-        FloatingValue::Float(self)
+impl<'ctx> FloatingValue<'ctx> {
+    pub fn get_type(&self) -> FloatType {
+        match self {
+            FloatingValue::Float(value) => value.get_type(),
+            FloatingValue::LLVMFloat(_, type_) => *type_,
+        }
     }
 }
 
-impl<'ctx> Into<FloatingValue<'ctx>> for inkwell::values::FloatValue<'ctx> {
+impl<'ctx> Into<FloatingValue<'ctx>> for FloatValue {
     fn into(self) -> FloatingValue<'ctx> {
-        FloatingValue::LLVMFloat(self)
+        FloatingValue::Float(self)
     }
 }
 
@@ -244,18 +313,6 @@ impl<'ctx> Into<FloatValue> for FloatingValue<'ctx> {
     }
 }
 
-impl<'ctx> Into<FloatingValue<'ctx>> for IntegerValue<'ctx> {
-    fn into(self) -> FloatingValue<'ctx> {
-        match self {
-            IntegerValue::Int(IntValue::I8(value)) => FloatingValue::Float(FloatValue::F32(value as f32)),
-            IntegerValue::Int(IntValue::I16(value)) => FloatingValue::Float(FloatValue::F32(value as f32)),
-            IntegerValue::Int(IntValue::I32(value)) => FloatingValue::Float(FloatValue::F32(value as f32)),
-            IntegerValue::Int(IntValue::I64(value)) => FloatingValue::Float(FloatValue::F32(value as f32)),
-            _ => panic!("Conversion not supported"),
-        }
-    }
-}
-
 #[derive(Debug, Copy, Clone)]
 pub enum LiteralValue {
     Bool(bool),
@@ -264,13 +321,53 @@ pub enum LiteralValue {
     Float(FloatValue),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value<'ctx> {
     Literal(LiteralValue),
-    LLVMBasicValueEnum(BasicValueEnum<'ctx>),
+    /// A list whose elements are all known at compile time.
+    List(Vec<Value<'ctx>>),
+    /// A value computed at run time, along with its Proto-rs type. LLVM
+    /// integers carry no sign, so the type is what decides between signed and
+    /// unsigned operations.
+    LLVMBasicValueEnum(BasicValueEnum<'ctx>, Type),
 }
 
 impl<'ctx> Value<'ctx> {
+    pub fn new(value: impl Into<BasicValueEnum<'ctx>>, type_: Type) -> Value<'ctx> {
+        Value::LLVMBasicValueEnum(value.into(), type_)
+    }
+
+    pub fn get_type(&self) -> Type {
+        match self {
+            Value::Literal(LiteralValue::Bool(_)) => Type::Literal(LiteralType::Bool),
+            Value::Literal(LiteralValue::Char(_)) => Type::Literal(LiteralType::Char),
+            Value::Literal(LiteralValue::Int(value)) => Type::Literal(LiteralType::Int(value.get_type())),
+            Value::Literal(LiteralValue::Float(value)) => Type::Literal(LiteralType::Float(value.get_type())),
+            Value::List(values) => match values.first() {
+                Some(value) => Type::List(Box::new(value.get_type()), values.len()),
+                None => Type::List(Box::new(Type::Inferred), 0),
+            },
+            Value::LLVMBasicValueEnum(_, type_) => type_.clone(),
+        }
+    }
+
+    /// The type of a compile-time value when nothing else decides it. The
+    /// elements of a list are given the widest of their types, so that
+    /// `[1, 3000000000]` is a `[i64; 2]` rather than an `i32` list with an
+    /// element out of range. There is no such type if the elements are
+    /// unrelated.
+    pub fn constant_type(&self) -> Option<Type> {
+        match self {
+            Value::List(values) => {
+                let mut types = values.iter().map(Value::constant_type);
+                let first = types.next()??;
+                let element = types.try_fold(first, |joined, type_| joined.join(type_?))?;
+                Some(Type::List(Box::new(element), values.len()))
+            }
+            value => Some(value.get_type()),
+        }
+    }
+
     pub fn as_bool(&self) -> bool {
         match self {
             Value::Literal(LiteralValue::Bool(b)) => *b,
@@ -308,7 +405,7 @@ impl<'ctx> Value<'ctx> {
 
     pub fn as_llvm_basic_value_enum(&self) -> BasicValueEnum<'ctx> {
         match self {
-            Value::LLVMBasicValueEnum(bve) => *bve,
+            Value::LLVMBasicValueEnum(bve, _) => *bve,
             _ => panic!("Expected BasicValueEnum"),
         }
     }
@@ -332,29 +429,11 @@ impl<'ctx> Into<Value<'ctx>> for LiteralValue {
     }
 }
 
-impl<'ctx> Into<Value<'ctx>> for BasicValueEnum<'ctx> {
-    fn into(self) -> Value<'ctx> {
-        Value::LLVMBasicValueEnum(self)
-    }
-}
-
-impl<'ctx> Into<Value<'ctx>> for inkwell::values::IntValue<'ctx> {
-    fn into(self) -> Value<'ctx> {
-        Value::LLVMBasicValueEnum(self.into())
-    }
-}
-
-impl<'ctx> Into<Value<'ctx>> for inkwell::values::FloatValue<'ctx> {
-    fn into(self) -> Value<'ctx> {
-        Value::LLVMBasicValueEnum(self.into())
-    }
-}
-
 impl<'ctx> Into<Value<'ctx>> for IntegerValue<'ctx> {
     fn into(self) -> Value<'ctx> {
         match self {
             IntegerValue::Int(i) => Value::Literal(LiteralValue::Int(i)),
-            IntegerValue::LLVMInt(i) => Value::LLVMBasicValueEnum(i.into()),
+            IntegerValue::LLVMInt(i, type_) => Value::LLVMBasicValueEnum(i.into(), Type::Literal(LiteralType::Int(type_))),
         }
     }
 }
@@ -363,7 +442,7 @@ impl<'ctx> Into<Value<'ctx>> for FloatingValue<'ctx> {
     fn into(self) -> Value<'ctx> {
         match self {
             FloatingValue::Float(f) => Value::Literal(LiteralValue::Float(f)),
-            FloatingValue::LLVMFloat(f) => Value::LLVMBasicValueEnum(f.into()),
+            FloatingValue::LLVMFloat(f, type_) => Value::LLVMBasicValueEnum(f.into(), Type::Literal(LiteralType::Float(type_))),
         }
     }
 }
@@ -372,7 +451,7 @@ impl<'ctx> Into<inkwell::values::IntValue<'ctx>> for IntegerValue<'ctx> {
     fn into(self) -> inkwell::values::IntValue<'ctx> {
         match self {
             IntegerValue::Int(_) => panic!("Cannot convert IntValue to LLVMIntValue"),
-            IntegerValue::LLVMInt(i) => i,
+            IntegerValue::LLVMInt(i, _) => i,
         }
     }
 }
@@ -381,7 +460,7 @@ impl<'ctx> Into<inkwell::values::FloatValue<'ctx>> for FloatingValue<'ctx> {
     fn into(self) -> inkwell::values::FloatValue<'ctx> {
         match self {
             FloatingValue::Float(_) => panic!("Cannot convert FloatValue to LLVMFloatValue"),
-            FloatingValue::LLVMFloat(f) => f,
+            FloatingValue::LLVMFloat(f, _) => f,
         }
     }
 }
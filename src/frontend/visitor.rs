@@ -1,37 +1,85 @@
-use crate::frontend::expr::{BinaryExpr, LiteralExpr, UnaryExpr};
+use crate::diagnostic::Diagnostic;
+use crate::frontend::{span::Span, token::Token, type_::Type};
+use crate::frontend::expr::{BinaryExpr, LogicalExpr, LiteralExpr, UnaryExpr};
 
-use super::{stmt::{ExprStmt, VarDeclStmt, ReturnStmt, BlockStmt, IfStmt, WhileStmt, BreakStmt, ContinueStmt, FunctionDeclStmt, FunctionDefStmt}, expr::{VariableExpr, VarAssignExpr, CallExpr, ListExpr, IndexExpr}, value::{Value, IntegerValue, FloatingValue}};
+use super::{stmt::{ExprStmt, VarDeclStmt, ConstDeclStmt, StaticDeclStmt, ReturnStmt, BlockStmt, IfStmt, WhileStmt, ForStmt, BreakStmt, ContinueStmt, FunctionDeclStmt, FunctionDefStmt, StructDeclStmt, EnumDeclStmt}, expr::{VariableExpr, AssignExpr, CallExpr, ListExpr, RepeatExpr, IndexExpr, FieldExpr, StructExpr, VariantExpr, MatchExpr, LoopExpr, BlockExpr, IfExpr, StringExpr, CastExpr, AddressOfExpr, DerefExpr, SliceExpr}, value::{Value, IntegerValue, FloatingValue}};
 
 pub trait ExprVisitor<'ctx> {
-    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Value<'ctx>;
-    fn visit_variable_expr(&mut self, _expr: &VariableExpr) -> Value<'ctx>;
-    fn visit_var_assign_expr(&mut self, _expr: &VarAssignExpr<'ctx>) -> Value<'ctx>;
-
-    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Value<'ctx>;
-    fn visit_unary_expr_int(&mut self, value: IntegerValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Value<'ctx>;
-    fn visit_unary_expr_float(&mut self, value: FloatingValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Value<'ctx>;
-
-    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Value<'ctx>;
-    fn visit_binary_expr_int_int(&mut self, left: IntegerValue<'ctx>, right: IntegerValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx>;
-    fn visit_binary_expr_int_float(&mut self, left: IntegerValue<'ctx>, right: FloatingValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx>;
-    fn visit_binary_expr_float_int(&mut self, left: FloatingValue<'ctx>, right: IntegerValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx>;
-    fn visit_binary_expr_float_float(&mut self, left: FloatingValue<'ctx>, right: FloatingValue<'ctx>, expr: &BinaryExpr<'ctx>) -> Value<'ctx>;
-
-    fn visit_call_expr(&mut self, _expr: &CallExpr<'ctx>) -> Value<'ctx>;
-    fn visit_list_expr(&mut self, _expr: &ListExpr<'ctx>) -> Value<'ctx>;
-    fn visit_index_expr(&mut self, _expr: &IndexExpr<'ctx>) -> Value<'ctx>;
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_string_expr(&mut self, expr: &StringExpr) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_variable_expr(&mut self, _expr: &VariableExpr) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_assign_expr(&mut self, expr: &AssignExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_unary_expr_int(&mut self, value: IntegerValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_unary_expr_float(&mut self, value: FloatingValue<'ctx>, expr: &UnaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_binary_expr_int_int(&mut self, left: IntegerValue<'ctx>, right: IntegerValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_binary_expr_int_float(&mut self, left: IntegerValue<'ctx>, right: FloatingValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_binary_expr_float_int(&mut self, left: FloatingValue<'ctx>, right: IntegerValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_binary_expr_float_float(&mut self, left: FloatingValue<'ctx>, right: FloatingValue<'ctx>, op: &Token, span: Span) -> Result<Value<'ctx>, Diagnostic>;
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+
+    fn visit_call_expr(&mut self, _expr: &CallExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_list_expr(&mut self, _expr: &ListExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_repeat_expr(&mut self, expr: &RepeatExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_index_expr(&mut self, _expr: &IndexExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_field_expr(&mut self, expr: &FieldExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_struct_expr(&mut self, expr: &StructExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_variant_expr(&mut self, expr: &VariantExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_match_expr(&mut self, expr: &MatchExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_loop_expr(&mut self, expr: &LoopExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_block_expr(&mut self, expr: &BlockExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_if_expr(&mut self, expr: &IfExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_cast_expr(&mut self, expr: &CastExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_address_of_expr(&mut self, expr: &AddressOfExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_deref_expr(&mut self, expr: &DerefExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+    fn visit_slice_expr(&mut self, expr: &SliceExpr<'ctx>) -> Result<Value<'ctx>, Diagnostic>;
+}
+
+/// Visits expressions to find their types without evaluating them.
+pub trait ExprTypeVisitor<'ctx> {
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<Type, Diagnostic>;
+    fn visit_string_expr(&mut self, expr: &StringExpr) -> Result<Type, Diagnostic>;
+    fn visit_variable_expr(&mut self, expr: &VariableExpr) -> Result<Type, Diagnostic>;
+    fn visit_assign_expr(&mut self, expr: &AssignExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_call_expr(&mut self, expr: &CallExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_list_expr(&mut self, expr: &ListExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_repeat_expr(&mut self, expr: &RepeatExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_index_expr(&mut self, expr: &IndexExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_field_expr(&mut self, expr: &FieldExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_struct_expr(&mut self, expr: &StructExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_variant_expr(&mut self, expr: &VariantExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_match_expr(&mut self, expr: &MatchExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_loop_expr(&mut self, expr: &LoopExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_block_expr(&mut self, expr: &BlockExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_if_expr(&mut self, expr: &IfExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_cast_expr(&mut self, expr: &CastExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_address_of_expr(&mut self, expr: &AddressOfExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_deref_expr(&mut self, expr: &DerefExpr<'ctx>) -> Result<Type, Diagnostic>;
+    fn visit_slice_expr(&mut self, expr: &SliceExpr<'ctx>) -> Result<Type, Diagnostic>;
 }
 
 pub trait StmtVisitor<'ctx> {
-    fn visit_expr_stmt(&mut self, stmt: &ExprStmt<'ctx>);
-    fn visit_var_decl_stmt(&mut self, stmt: &VarDeclStmt<'ctx>);
-    fn visit_return_stmt(&mut self, stmt: &ReturnStmt<'ctx>);
-    fn visit_block_stmt(&mut self, stmt: &BlockStmt<'ctx>);
-    fn visit_if_stmt(&mut self, stmt: &IfStmt<'ctx>);
-    fn visit_while_stmt(&mut self, stmt: &WhileStmt<'ctx>);
-    fn visit_break_stmt(&mut self, stmt: &BreakStmt);
-    fn visit_continue_stmt(&mut self, stmt: &ContinueStmt);
-
-    fn visit_function_decl_stmt(&mut self, stmt: &FunctionDeclStmt);
-    fn visit_function_def_stmt(&mut self, stmt: &FunctionDefStmt<'ctx>);
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_var_decl_stmt(&mut self, stmt: &VarDeclStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_const_decl_stmt(&mut self, stmt: &ConstDeclStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_static_decl_stmt(&mut self, stmt: &StaticDeclStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_if_stmt(&mut self, stmt: &IfStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_for_stmt(&mut self, stmt: &ForStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_break_stmt(&mut self, stmt: &BreakStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_continue_stmt(&mut self, stmt: &ContinueStmt) -> Result<(), Diagnostic>;
+
+    fn visit_function_decl_stmt(&mut self, stmt: &FunctionDeclStmt) -> Result<(), Diagnostic>;
+    fn visit_function_def_stmt(&mut self, stmt: &FunctionDefStmt<'ctx>) -> Result<(), Diagnostic>;
+    fn visit_struct_decl_stmt(&mut self, stmt: &StructDeclStmt) -> Result<(), Diagnostic>;
+    fn visit_enum_decl_stmt(&mut self, stmt: &EnumDeclStmt) -> Result<(), Diagnostic>;
 }
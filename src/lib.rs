@@ -0,0 +1,6 @@
+pub mod compile_time_evaluator;
+pub mod code_generator;
+pub mod compiler;
+pub mod diagnostic;
+pub mod frontend;
+pub mod semantic;
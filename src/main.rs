@@ -1,12 +1,7 @@
-mod compile_time_evaluator;
-mod code_generator;
-mod compiler;
-mod frontend;
-
 use clap::{Arg, ArgAction, Command, crate_version, crate_authors, crate_name, crate_description};
 
 use std::path::Path;
-use crate::compiler::Compiler;
+use proto_rs::compiler::Compiler;
 use inkwell::{targets::FileType, context::Context};
 
 fn main() {
@@ -34,6 +29,12 @@ fn main() {
                 .help("output assembly file")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("NO_BOUNDS_CHECKS")
+                .long("no-bounds-checks")
+                .help("do not check list and slice indices at run time")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("VERBOSE")
                 .short('v')
@@ -45,9 +46,20 @@ fn main() {
     let source_file = matches.get_one::<String>("INPUT").unwrap();
 
     let context = Context::create(); 
-    let mut compiler = Compiler::new(&context, source_file);
+    let bounds_checks = !matches.get_flag("NO_BOUNDS_CHECKS");
+    let mut compiler = Compiler::new(&context, source_file, bounds_checks);
+
+    if let Err(diagnostics) = compiler.compile() {
+        for diagnostic in &diagnostics {
+            eprintln!("{}", compiler.render_diagnostic(diagnostic));
+        }
 
-    compiler.compile().unwrap();
+        let errors = diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count();
+        if errors > 0 {
+            eprintln!("error: could not compile `{}` due to {} previous error(s)", source_file, errors);
+            std::process::exit(1);
+        }
+    }
 
     let output_file = matches.get_one::<String>("OUTPUT").unwrap();
     let output_file = Path::new(output_file);
@@ -0,0 +1,56 @@
+pub mod type_checker;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::Diagnostic;
+use crate::frontend::span::Span;
+use crate::frontend::stmt::{Field, Variant};
+use crate::frontend::type_::Type;
+use crate::frontend::value::Value;
+
+pub struct FunctionSignature {
+    params: Vec<Type>,
+    return_type: Type,
+}
+
+/// The values a `match` pattern covers, used to find unreachable arms and
+/// to check that a `match` is exhaustive.
+#[derive(PartialEq, Eq)]
+pub enum PatternKey {
+    Value(i128),
+    Variant(String),
+}
+
+/// A loop around the statement being checked, which `break` and `continue`
+/// may leave.
+pub struct LoopScope<'ctx> {
+    label: Option<String>,
+    /// Whether this is a `loop`, the only kind of loop a `break` may give a
+    /// value.
+    is_loop: bool,
+    /// The type, compile-time value and span of the value each `break` gives
+    /// the loop, joined into the type of the loop once its body is checked.
+    breaks: Vec<(Option<Type>, Option<Value<'ctx>>, Span)>,
+}
+
+/// Resolves names and gives every expression a type before any code is
+/// generated, so that the code generator only ever sees well-typed programs.
+#[derive(Default)]
+pub struct TypeChecker<'ctx> {
+    scopes: Vec<HashMap<String, Type>>,
+    functions: HashMap<String, FunctionSignature>,
+    structs: HashMap<String, Vec<Field>>,
+    enums: HashMap<String, Vec<Variant>>,
+    /// The names of the `const`s, which live in the outermost scope.
+    constants: HashSet<String>,
+    return_type: Option<Type>,
+    /// The type required of the expression being checked as an initializer,
+    /// which a `match` or `loop` whose values are all constant takes as its own.
+    expected_type: Option<Type>,
+    loops: Vec<LoopScope<'ctx>>,
+    /// Whether the code checked so far cannot be run past, because every path
+    /// through it leaves by a `return`, a `break` or a `continue`, or loops
+    /// forever.
+    diverges: bool,
+    diagnostics: Vec<Diagnostic>,
+}
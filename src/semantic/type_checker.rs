@@ -0,0 +1,1599 @@
+use std::collections::HashMap;
+
+use crate::compile_time_evaluator::CompileTimeEvaluator;
+use crate::diagnostic::Diagnostic;
+use crate::frontend::expr::{Expr, BinaryExpr, LogicalExpr, LiteralExpr, UnaryExpr, VariableExpr, AssignExpr, LValue, CallExpr, ListExpr, RepeatExpr, IndexExpr, FieldExpr, StructExpr, VariantExpr, MatchExpr, LoopExpr, BlockExpr, IfExpr, StringExpr, CastExpr, AddressOfExpr, DerefExpr, SliceExpr, ArmBody, Pattern};
+use crate::frontend::stmt::{Variant, Stmt, ExprStmt, VarDeclStmt, ConstDeclStmt, StaticDeclStmt, ReturnStmt, BlockStmt, IfStmt, WhileStmt, ForStmt, ForIterable, BreakStmt, ContinueStmt, FunctionDeclStmt, FunctionDefStmt, Param, StructDeclStmt, EnumDeclStmt};
+use crate::frontend::span::Span;
+use crate::frontend::token::{Token, TokenKind};
+use crate::frontend::type_::{Type, LiteralType, IntType, FloatType, PointerKind};
+use crate::frontend::value::{self, Value, LiteralValue};
+use crate::frontend::visitor::{ExprTypeVisitor, StmtVisitor};
+
+use super::{TypeChecker, FunctionSignature, PatternKey, LoopScope};
+
+impl<'ctx> TypeChecker<'ctx> {
+    pub fn new() -> TypeChecker<'ctx> {
+        TypeChecker {
+            scopes: vec![HashMap::new()],
+            ..Default::default()
+        }
+    }
+
+    /// Checks a whole program, returning every error found in it.
+    pub fn check(&mut self, stmts: &[Box<dyn Stmt<'ctx> + 'ctx>]) -> Result<(), Vec<Diagnostic>> {
+        for stmt in stmts {
+            self.check_stmt(stmt.as_ref());
+        }
+
+        if self.diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.diagnostics))
+        }
+    }
+
+    /// Checks `stmt`, recording its error so that checking goes on with the
+    /// statements after it.
+    fn check_stmt(&mut self, stmt: &dyn Stmt<'ctx>) {
+        if let Err(diagnostic) = stmt.accept(self) {
+            let diagnostic = match diagnostic.span {
+                Some(_) => diagnostic,
+                None => diagnostic.with_span(stmt.span()),
+            };
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn declare_variable(&mut self, name: &str, type_: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), type_);
+        }
+    }
+
+    /// Checks that `target` can be changed, as `action` is about to: it must
+    /// not be a `const` or a part of one, nor be reached through a `&T`. A
+    /// `const` is only found in the outermost scope, unless a variable
+    /// shadows it.
+    fn check_mutable(&mut self, target: &LValue<'ctx>, action: &str) -> Result<(), Diagnostic> {
+        let base = match target {
+            LValue::Variable(variable) => {
+                let scope = self.scopes.iter().rposition(|scope| scope.contains_key(&variable.name));
+                if scope == Some(0) && self.constants.contains(&variable.name) {
+                    return Err(Diagnostic::error(format!("cannot {} constant `{}`", action, variable.name)).with_span(target.span()));
+                }
+                return Ok(());
+            }
+            LValue::Deref(deref) => {
+                let type_ = deref.pointer.accept_type(self)?;
+                return Self::check_pointer_mutable(&type_, action, target.span());
+            }
+            LValue::Index(index) => &index.base,
+            LValue::Field(field) => &field.base,
+            LValue::Slice(slice) => &slice.base,
+            LValue::Value(_) => {
+                return Err(Diagnostic::error(format!("cannot {} a temporary value", action))
+                           .with_span(target.span())
+                           .with_note("store the value in a variable first"));
+            }
+        };
+
+        self.check_base_mutable(base, action, target.span())
+    }
+
+    /// Checks that the place `base` is part of can be changed. Indexing,
+    /// slicing and field access look through a pointer to the place it
+    /// points to, which the pointer then decides about.
+    fn check_base_mutable(&mut self, base: &LValue<'ctx>, action: &str, span: Span) -> Result<(), Diagnostic> {
+        match base.as_expr().accept_type(self)? {
+            Type::Error => Ok(()),
+            type_ @ (Type::Pointer(_, _) | Type::Slice(_, _)) => Self::check_pointer_mutable(&type_, action, span),
+            _ => self.check_mutable(base, action),
+        }
+    }
+
+    fn check_pointer_mutable(type_: &Type, action: &str, span: Span) -> Result<(), Diagnostic> {
+        match type_ {
+            Type::Pointer(_, PointerKind::Shared) => Err(Diagnostic::error(format!("cannot {} data behind a `&` reference", action))
+                                                         .with_span(span)
+                                                         .with_note(format!("the reference is a `{}`, which would have to be a `&mut` instead", type_))),
+            Type::Slice(element, PointerKind::Shared) => Err(Diagnostic::error(format!("cannot {} data behind a `&[T]` slice", action))
+                                                             .with_span(span)
+                                                             .with_note(format!("the slice is a `{}`, which would have to be a `[{}]` instead", type_, element))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Looks through any pointers to the type of the place they point to.
+    fn auto_deref(type_: Type) -> Type {
+        match type_ {
+            Type::Pointer(pointee, _) => Self::auto_deref(*pointee),
+            type_ => type_,
+        }
+    }
+
+    /// Checks a `const` or `static`, whose value has to be known at compile
+    /// time to be put into the object file.
+    fn check_global(&mut self, constant: bool, name: &str, type_: &Type, value: &dyn Expr<'ctx>, span: Span) -> Result<(), Diagnostic> {
+        let keyword = if constant { "const" } else { "static" };
+        if self.scopes.len() > 1 {
+            return Err(Diagnostic::error(format!("`{}` items can only be declared at the top level", keyword)).with_span(span));
+        }
+
+        // The global is declared even if its value is wrong, so that its
+        // uses are not reported as well.
+        let result = self.check_type(type_, span)
+            .and_then(|_| self.check_initializer(value, type_))
+            .and_then(|_| match Self::constant(value) {
+                Some(_) => Ok(()),
+                None => Err(Diagnostic::error(format!("the value of `{}` cannot be evaluated at compile time", name))
+                            .with_span(value.span())
+                            .with_note(format!("the value of a `{}` is computed when the program is compiled, so it can only use literals and operators", keyword))),
+            });
+        self.declare_variable(name, type_.clone());
+        if constant {
+            self.constants.insert(name.to_owned());
+        } else {
+            self.constants.remove(name);
+        }
+
+        result
+    }
+
+    /// Checks a call of the built-in `len`, which gives the number of
+    /// elements of a list, a slice or a `str`.
+    fn check_len(&mut self, expr: &CallExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let [arg] = expr.args.as_slice() else {
+            return Err(Diagnostic::error(format!("function `len` takes 1 argument, but {} were supplied", expr.args.len())).with_span(expr.span));
+        };
+
+        self.expected_type = None;
+        match Self::auto_deref(arg.accept_type(self)?) {
+            Type::List(_, _) | Type::Slice(_, _) | Type::Str | Type::Error => Ok(Type::Literal(LiteralType::Int(IntType::U64))),
+            type_ => Err(Diagnostic::error(format!("`{}` has no length", type_))
+                         .with_span(arg.span())
+                         .with_note("`len` takes a list, a slice or a `str`")),
+        }
+    }
+
+    fn lookup_variable(&self, name: &str, span: Span) -> Result<Type, Diagnostic> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .ok_or_else(|| Diagnostic::error(format!("cannot find variable `{}` in this scope", name)).with_span(span))
+    }
+
+    /// Checks that every struct named in `type_` has been declared.
+    fn check_type(&self, type_: &Type, span: Span) -> Result<(), Diagnostic> {
+        match type_ {
+            Type::List(element, _) | Type::Slice(element, _) => self.check_type(element, span),
+            Type::Pointer(pointee, _) if **pointee == Type::Void => {
+                Err(Diagnostic::error(format!("cannot point to a value of type `{}`", pointee)).with_span(span))
+            }
+            Type::Pointer(pointee, _) => self.check_type(pointee, span),
+            Type::Named(name) if !self.structs.contains_key(name) && !self.enums.contains_key(name) => {
+                Err(Diagnostic::error(format!("cannot find type `{}` in this scope", name)).with_span(span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The type of `param`, which has to be one a value can have.
+    fn param_type(&self, param: &Param) -> Result<Type, Diagnostic> {
+        if let Type::Void | Type::Inferred = param.type_ {
+            return Err(Diagnostic::error(format!("parameter `{}` cannot have type `{}`", param.name, param.type_)).with_span(param.span));
+        }
+        self.check_type(&param.type_, param.span)?;
+        Ok(param.type_.clone())
+    }
+
+    fn return_type_of(&self, stmt: &FunctionDeclStmt) -> Result<Type, Diagnostic> {
+        self.check_type(&stmt.return_type, stmt.span)?;
+        Ok(stmt.resolved_return_type())
+    }
+
+    /// Checks that no struct or enum is already called `name`.
+    fn check_type_name(&self, name: &str, span: Span) -> Result<(), Diagnostic> {
+        if self.structs.contains_key(name) || self.enums.contains_key(name) {
+            return Err(Diagnostic::error(format!("the type `{}` is defined more than once", name)).with_span(span));
+        }
+        Ok(())
+    }
+
+    fn fields_noun(count: usize) -> &'static str {
+        if count == 1 { "field" } else { "fields" }
+    }
+
+    fn variant(&self, enum_name: &str, variant: &str, span: Span) -> Result<Variant, Diagnostic> {
+        let variants = self.enums.get(enum_name)
+            .ok_or_else(|| Diagnostic::error(format!("cannot find enum `{}` in this scope", enum_name)).with_span(span))?;
+
+        variants.iter()
+            .find(|candidate| candidate.name == variant)
+            .cloned()
+            .ok_or_else(|| Diagnostic::error(format!("no variant named `{}` in enum `{}`", variant, enum_name)).with_span(span))
+    }
+
+    /// Checks that `pattern` can match a value of type `scrutinee`. Returns
+    /// the values the pattern covers, or `None` if it matches every value,
+    /// together with the variables it binds.
+    fn check_pattern(&self, pattern: &Pattern, scrutinee: &Type) -> Result<(Option<PatternKey>, HashMap<String, Type>), Diagnostic> {
+        let mut bindings = HashMap::new();
+
+        match pattern {
+            Pattern::Any(name, _) => {
+                if let Some(name) = name {
+                    bindings.insert(name.clone(), scrutinee.clone());
+                }
+                Ok((None, bindings))
+            }
+            Pattern::Literal(value, span) => {
+                let key = match value {
+                    LiteralValue::Bool(value) => PatternKey::Value(*value as i128),
+                    LiteralValue::Char(value) => PatternKey::Value(*value as i128),
+                    LiteralValue::Int(value) => PatternKey::Value(value.as_i128()),
+                    LiteralValue::Float(_) => return Err(Diagnostic::error("floating-point literals cannot be used in patterns").with_span(*span)),
+                };
+
+                if scrutinee.numeric().is_none() && *scrutinee != Type::Literal(LiteralType::Bool) {
+                    return Err(Diagnostic::error(format!("mismatched types: expected `{}`, found a literal pattern", scrutinee)).with_span(*span));
+                }
+                Self::check_constant(&Value::Literal(*value), scrutinee, *span)?;
+
+                Ok((Some(key), bindings))
+            }
+            Pattern::Variant { enum_name, variant, fields, span } => {
+                if *scrutinee != Type::Named(enum_name.clone()) {
+                    return Err(Diagnostic::error(format!("mismatched types: expected `{}`, found `{}`", scrutinee, enum_name)).with_span(*span));
+                }
+
+                let declared = self.variant(enum_name, variant, *span)?;
+                if fields.len() != declared.fields.len() {
+                    return Err(Diagnostic::error(format!("this pattern has {} {}, but variant `{}::{}` has {}", fields.len(), Self::fields_noun(fields.len()), enum_name, variant, declared.fields.len())).with_span(*span));
+                }
+
+                for ((name, span), type_) in fields.iter().zip(&declared.fields) {
+                    if let Some(name) = name {
+                        if bindings.insert(name.clone(), type_.clone()).is_some() {
+                            return Err(Diagnostic::error(format!("identifier `{}` is bound more than once in the same pattern", name)).with_span(*span));
+                        }
+                    }
+                }
+
+                Ok((Some(PatternKey::Variant(variant.clone())), bindings))
+            }
+        }
+    }
+
+    /// Checks that the patterns in `covered` match every value of type
+    /// `scrutinee`, for a `match` without a catch-all arm.
+    fn check_exhaustive(&self, scrutinee: &Type, covered: &[(PatternKey, Span)], span: Span) -> Result<(), Diagnostic> {
+        let missing = match scrutinee {
+            Type::Named(name) if self.enums.contains_key(name) => self.enums[name].iter()
+                .filter(|variant| !covered.iter().any(|(key, _)| *key == PatternKey::Variant(variant.name.clone())))
+                .map(|variant| match variant.fields.len() {
+                    0 => format!("`{}::{}`", name, variant.name),
+                    _ => format!("`{}::{}(..)`", name, variant.name),
+                })
+                .collect::<Vec<String>>(),
+            Type::Literal(LiteralType::Bool) => [false, true].iter()
+                .filter(|value| !covered.iter().any(|(key, _)| *key == PatternKey::Value(**value as i128)))
+                .map(|value| format!("`{}`", value))
+                .collect(),
+            _ => vec!["`_`".to_owned()],
+        };
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        Err(Diagnostic::error(format!("non-exhaustive patterns: {} not covered", missing.join(", ")))
+            .with_span(span)
+            .with_note("add the missing arms, or a `_` arm matching every other value"))
+    }
+
+    /// The type of an expression whose value comes from one of several
+    /// branches, such as the arms of a `match`. Each branch is given by its
+    /// type, its compile-time value if it has one, and its span.
+    ///
+    /// Constant branches take the type of the other branches, as they do in
+    /// an initializer, while branches computed at run time must agree exactly.
+    /// When every branch is constant, they take `expected_type`, the type
+    /// required of the whole expression, or else the type their values have
+    /// in common.
+    fn join_branches(branches: &[(Option<Type>, Option<Value<'ctx>>, Span)], expected_type: Option<Type>, what: &str, span: Span) -> Result<Type, Diagnostic> {
+        let all_constant = branches.iter().all(|(_, constant, _)| constant.is_some());
+        let joined = if all_constant && expected_type.is_some() {
+            expected_type
+        } else {
+            branches.iter()
+                .filter(|(_, constant, _)| all_constant || constant.is_none())
+                .map(|(type_, _, _)| type_.clone())
+                .reduce(|joined, type_| joined?.join(type_?))
+                .flatten()
+        };
+
+        let expected = joined.ok_or_else(|| {
+            Diagnostic::error(format!("{} have incompatible types", what)).with_span(span)
+        })?;
+
+        for (type_, constant, span) in branches {
+            match constant {
+                Some(constant) => Self::check_constant(constant, &expected, *span)?,
+                None => {
+                    let type_ = type_.as_ref().unwrap();
+                    if !type_.coerces_to(&expected) {
+                        return Err(Diagnostic::error(format!("{} have incompatible types: expected `{}`, found `{}`", what, expected, type_)).with_span(*span));
+                    }
+                }
+            }
+        }
+
+        Ok(expected)
+    }
+
+    /// Checks the body of a loop, which `break` and `continue` may leave.
+    /// Returns the values the `break`s out of the loop give it.
+    fn check_loop_body(&mut self, label: Option<String>, is_loop: bool, body: &dyn Stmt<'ctx>) -> Vec<(Option<Type>, Option<Value<'ctx>>, Span)> {
+        self.loops.push(LoopScope {
+            label,
+            is_loop,
+            breaks: Vec::new(),
+        });
+        // The body may not run at all, or be left by a `break`, so the code
+        // after the loop is reached as long as the code before it is.
+        let diverges = self.diverges;
+        self.check_stmt(body);
+        self.diverges = diverges;
+        self.loops.pop().unwrap().breaks
+    }
+
+    /// Finds the loop a `break` or `continue` leaves: the one named `label`,
+    /// or else the innermost one.
+    fn target_loop(&self, label: &Option<String>, keyword: &str, span: Span) -> Result<usize, Diagnostic> {
+        match label {
+            Some(label) => self.loops.iter()
+                .rposition(|scope| scope.label.as_ref() == Some(label))
+                .ok_or_else(|| Diagnostic::error(format!("use of undeclared label `'{}`", label)).with_span(span)),
+            None if self.loops.is_empty() => Err(Diagnostic::error(format!("`{}` outside of a loop", keyword)).with_span(span)),
+            None => Ok(self.loops.len() - 1),
+        }
+    }
+
+    /// The value of `expr` if it can be computed at compile time.
+    fn constant(expr: &dyn Expr<'ctx>) -> Option<Value<'ctx>> {
+        expr.accept(&mut CompileTimeEvaluator::new()).ok()
+    }
+
+    fn constant_int(constant: &Option<Value>) -> Option<i128> {
+        match constant {
+            Some(Value::Literal(LiteralValue::Int(value))) => Some(value.as_i128()),
+            Some(Value::Literal(LiteralValue::Char(value))) => Some(*value as i128),
+            _ => None,
+        }
+    }
+
+    fn is_zero(constant: &Option<Value>) -> bool {
+        match constant {
+            Some(Value::Literal(LiteralValue::Int(value))) => value.is_zero(),
+            _ => false,
+        }
+    }
+
+    fn expect_bool(&mut self, expr: &dyn Expr<'ctx>) -> Result<(), Diagnostic> {
+        match expr.accept_type(self)? {
+            Type::Literal(LiteralType::Bool) | Type::Error => Ok(()),
+            type_ => Err(Diagnostic::error(format!("mismatched types: expected `bool`, found `{}`", type_)).with_span(expr.span())),
+        }
+    }
+
+    /// Checks that `expr` can be used as a value of type `type_`. Constant
+    /// expressions are converted to `type_` when no information is lost; any
+    /// other expression must already have it.
+    fn check_initializer(&mut self, expr: &dyn Expr<'ctx>, type_: &Type) -> Result<(), Diagnostic> {
+        if let Some(constant) = Self::constant(expr) {
+            return Self::check_constant(&constant, type_, expr.span());
+        }
+
+        self.expected_type = Some(type_.clone());
+        let found = expr.accept_type(self);
+        self.expected_type = None;
+
+        let found = found?;
+        if !found.coerces_to(type_) {
+            return Err(Diagnostic::error(format!("mismatched types: expected `{}`, found `{}`", type_, found)).with_span(expr.span()));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the compile-time value `value` can be converted to `type_`.
+    fn check_constant(value: &Value, type_: &Type, span: Span) -> Result<(), Diagnostic> {
+        let mismatch = |found: &str| Diagnostic::error(format!("mismatched types: expected `{}`, found {}", type_, found)).with_span(span);
+
+        match (value, type_) {
+            (Value::Literal(LiteralValue::Int(value)), Type::Literal(LiteralType::Int(int_type))) => {
+                let value = value.as_i128();
+                if value < int_type.min_value() || value > int_type.max_value() {
+                    return Err(Diagnostic::error(format!("literal out of range for `{}`", int_type))
+                               .with_span(span)
+                               .with_note(format!("the literal `{}` does not fit into the type `{}` whose range is `{}..={}`", value, int_type, int_type.min_value(), int_type.max_value())));
+                }
+                Ok(())
+            }
+            (Value::Literal(LiteralValue::Int(_)), Type::Literal(LiteralType::Float(_))) => Ok(()),
+            (Value::Literal(LiteralValue::Float(value)), Type::Literal(LiteralType::Float(float_type))) => {
+                if let (value::FloatValue::F64(value), FloatType::F32) = (value, float_type) {
+                    if (*value as f32).is_infinite() {
+                        return Err(Diagnostic::error(format!("literal out of range for `{}`", float_type)).with_span(span));
+                    }
+                }
+                Ok(())
+            }
+            (Value::List(values), Type::List(element, len)) => {
+                if values.len() != *len {
+                    return Err(mismatch(&format!("a list of {} elements", values.len())));
+                }
+
+                values.iter().try_for_each(|value| Self::check_constant(value, element, span))
+            }
+            (value, type_) if value.get_type().coerces_to(type_) => Ok(()),
+            (Value::Literal(LiteralValue::Int(_)), _) => Err(mismatch("integer")),
+            (Value::Literal(LiteralValue::Float(_)), _) => Err(mismatch("floating-point number")),
+            (Value::Literal(LiteralValue::Bool(_)), _) => Err(mismatch("`bool`")),
+            (Value::Literal(LiteralValue::Char(_)), _) => Err(mismatch("`char`")),
+            (Value::List(_), _) => Err(mismatch("list")),
+            (Value::LLVMBasicValueEnum(_, found), _) => Err(mismatch(&format!("`{}`", found))),
+        }
+    }
+
+    /// The type of `left op right`. The compile-time values of the operands,
+    /// where they have one, decide whether a constant can take the type of the
+    /// other operand.
+    fn binary_type(left: &Type, left_constant: Option<Value>, right: &Type, right_constant: Option<Value>, op: &Token, span: Span) -> Result<Type, Diagnostic> {
+        let bool_type = Type::Literal(LiteralType::Bool);
+        let is_comparison = matches!(op.kind, TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual | TokenKind::Equal | TokenKind::NotEqual);
+        let is_arithmetic = matches!(op.kind, TokenKind::Plus | TokenKind::Minus | TokenKind::Asterisk | TokenKind::Slash | TokenKind::Remainder);
+
+        if *left == Type::Error || *right == Type::Error {
+            return Ok(Type::Error);
+        }
+
+        if *left == bool_type && *right == bool_type {
+            return match op.kind {
+                TokenKind::Ampersand | TokenKind::Pipe | TokenKind::Caret | TokenKind::Equal | TokenKind::NotEqual => Ok(bool_type),
+                _ => Err(Diagnostic::error(format!("cannot apply {} to `bool`", op.kind)).with_span(span)),
+            };
+        }
+
+        match (left.numeric(), right.numeric()) {
+            (Some(LiteralType::Float(_)), Some(_)) | (Some(_), Some(LiteralType::Float(_))) if op.kind.is_bitwise() => {
+                Err(Diagnostic::error(format!("cannot apply {} to floating-point operands", op.kind)).with_span(span))
+            }
+            (Some(LiteralType::Int(left)), Some(LiteralType::Int(right))) => {
+                // The result of a shift has the type of its left operand.
+                if let TokenKind::ShiftLeft | TokenKind::ShiftRight = op.kind {
+                    return Ok(Type::Literal(LiteralType::Int(left)));
+                }
+
+                let int_type = Self::unify_ints(left, &left_constant, right, &right_constant, op, span)?;
+
+                if matches!(op.kind, TokenKind::Slash | TokenKind::Remainder) && Self::is_zero(&right_constant) {
+                    return Err(Diagnostic::error("division by zero").with_span(span));
+                }
+
+                match op.kind {
+                    _ if is_comparison => Ok(bool_type),
+                    _ if is_arithmetic || op.kind.is_bitwise() => Ok(Type::Literal(LiteralType::Int(int_type))),
+                    _ => Err(Diagnostic::error(format!("unexpected operator {}", op.kind)).with_span(span)),
+                }
+            }
+            (Some(LiteralType::Int(int_type)), Some(LiteralType::Float(float_type))) if !Self::converts_exactly(&left_constant, float_type) => {
+                Err(Self::int_float_mismatch(left, right, int_type, float_type, op, span))
+            }
+            (Some(LiteralType::Float(float_type)), Some(LiteralType::Int(int_type))) if !Self::converts_exactly(&right_constant, float_type) => {
+                Err(Self::int_float_mismatch(left, right, int_type, float_type, op, span))
+            }
+            (Some(left), Some(right)) => {
+                // A constant meeting an `f32` that is not one is rounded to
+                // `f32`, as a float literal has no type of its own.
+                let float_type = match (left, right) {
+                    (LiteralType::Float(FloatType::F64), LiteralType::Float(FloatType::F32)) if Self::narrows(&left_constant) && right_constant.is_none() => FloatType::F32,
+                    (LiteralType::Float(FloatType::F32), LiteralType::Float(FloatType::F64)) if Self::narrows(&right_constant) && left_constant.is_none() => FloatType::F32,
+                    (LiteralType::Float(FloatType::F64), _) | (_, LiteralType::Float(FloatType::F64)) => FloatType::F64,
+                    _ => FloatType::F32,
+                };
+
+                match op.kind {
+                    _ if is_comparison => Ok(bool_type),
+                    _ if is_arithmetic => Ok(Type::Literal(LiteralType::Float(float_type))),
+                    _ => Err(Diagnostic::error(format!("unexpected operator {}", op.kind)).with_span(span)),
+                }
+            }
+            _ => Err(Diagnostic::error(format!("cannot apply {} to `{}` and `{}`", op.kind, left, right)).with_span(span)),
+        }
+    }
+
+    /// Whether `constant` is a floating-point number that stays finite when
+    /// rounded to `f32`.
+    fn narrows(constant: &Option<Value>) -> bool {
+        matches!(constant, Some(Value::Literal(LiteralValue::Float(value))) if (value.as_f64() as f32).is_finite())
+    }
+
+    /// Whether `constant` is an integer that `float_type` holds exactly. Only
+    /// such an integer is converted implicitly when it meets a floating-point
+    /// operand; any other one needs an `as`.
+    fn converts_exactly(constant: &Option<Value>, float_type: FloatType) -> bool {
+        Self::constant_int(constant).is_some_and(|value| match float_type {
+            FloatType::F32 => value as f32 as i128 == value,
+            FloatType::F64 => value as f64 as i128 == value,
+        })
+    }
+
+    fn int_float_mismatch(left: &Type, right: &Type, int_type: IntType, float_type: FloatType, op: &Token, span: Span) -> Diagnostic {
+        Diagnostic::error(format!("mismatched types: cannot apply {} to `{}` and `{}`", op.kind, left, right))
+            .with_span(span)
+            .with_note(format!("convert the `{}` operand with `as {}`", int_type, float_type))
+    }
+
+    /// The type two integer operands are brought to. A constant operand takes
+    /// the type of the other one if its value fits there; otherwise the
+    /// narrower operand is extended, which requires both to have the same
+    /// signedness.
+    fn unify_ints(left: IntType, left_constant: &Option<Value>, right: IntType, right_constant: &Option<Value>, op: &Token, span: Span) -> Result<IntType, Diagnostic> {
+        let fits = |constant: &Option<Value>, type_: IntType| {
+            Self::constant_int(constant).is_some_and(|value| value >= type_.min_value() && value <= type_.max_value())
+        };
+
+        if left == right || fits(right_constant, left) {
+            Ok(left)
+        } else if fits(left_constant, right) {
+            Ok(right)
+        } else if left.is_signed() == right.is_signed() {
+            Ok(if left.bit_width() > right.bit_width() { left } else { right })
+        } else {
+            Err(Diagnostic::error(format!("mismatched types: cannot apply {} to `{}` and `{}`", op.kind, left, right)).with_span(span))
+        }
+    }
+}
+
+impl<'ctx> ExprTypeVisitor<'ctx> for TypeChecker<'ctx> {
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<Type, Diagnostic> {
+        Ok(Value::Literal(expr.value).get_type())
+    }
+
+    fn visit_string_expr(&mut self, _expr: &StringExpr) -> Result<Type, Diagnostic> {
+        Ok(Type::Str)
+    }
+
+    fn visit_variable_expr(&mut self, expr: &VariableExpr) -> Result<Type, Diagnostic> {
+        self.lookup_variable(&expr.name, expr.span)
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignExpr<'ctx>) -> Result<Type, Diagnostic> {
+        if let LValue::Slice(slice) = &expr.target {
+            return Err(Diagnostic::error("cannot assign to a range of a list")
+                       .with_span(slice.span)
+                       .with_note("assign to its elements one at a time instead"));
+        }
+
+        let target = expr.target.as_expr().accept_type(self)?;
+        self.check_mutable(&expr.target, "assign to")?;
+
+        match expr.op.kind.compound_operator() {
+            Some(kind) => {
+                let value = expr.value.accept_type(self)?;
+                let op = Token::new(kind, expr.op.span);
+                // The current value of the target is loaded at run time, so it
+                // is never a constant.
+                let found = Self::binary_type(&target, None, &value, Self::constant(expr.value.as_ref()), &op, expr.span)?;
+
+                if !found.coerces_to(&target) {
+                    return Err(Diagnostic::error(format!("mismatched types: expected `{}`, found `{}`", target, found))
+                               .with_span(expr.value.span())
+                               .with_label(expr.target.span(), "assigned to here"));
+                }
+            }
+            None => self.check_initializer(expr.value.as_ref(), &target)
+                .map_err(|diagnostic| diagnostic.with_label(expr.target.span(), "assigned to here"))?,
+        }
+
+        Ok(target)
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let type_ = expr.right.accept_type(self)?;
+
+        if type_ == Type::Literal(LiteralType::Bool) || type_ == Type::Error {
+            return match expr.op.kind {
+                TokenKind::Bang => Ok(type_),
+                _ => Err(Diagnostic::error(format!("cannot apply unary {} to `bool`", expr.op.kind)).with_span(expr.span)),
+            };
+        }
+
+        // A constant operand is folded at full width, which also reports an
+        // overflow.
+        if Self::constant(expr.right.as_ref()).is_some() {
+            return Ok(expr.accept(&mut CompileTimeEvaluator::new())?.get_type());
+        }
+
+        match (type_.numeric(), &expr.op.kind) {
+            (Some(LiteralType::Int(int_type)), TokenKind::Minus) if !int_type.is_signed() => {
+                Err(Diagnostic::error(format!("cannot apply unary `-` to type `{}`", int_type)).with_span(expr.span))
+            }
+            (Some(LiteralType::Int(int_type)), TokenKind::Minus | TokenKind::Plus | TokenKind::Bang | TokenKind::Tilde) => {
+                Ok(Type::Literal(LiteralType::Int(int_type)))
+            }
+            (Some(LiteralType::Float(_)), TokenKind::Minus | TokenKind::Plus) => Ok(type_),
+            (Some(LiteralType::Float(_)), TokenKind::Bang | TokenKind::Tilde) => {
+                Err(Diagnostic::error(format!("cannot apply unary {} to a floating-point operand", expr.op.kind)).with_span(expr.span))
+            }
+            _ => Err(Diagnostic::error(format!("cannot apply unary {} to `{}`", expr.op.kind, type_)).with_span(expr.span)),
+        }
+    }
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let left = expr.left.accept_type(self)?;
+        let right = expr.right.accept_type(self)?;
+        let (left_constant, right_constant) = (Self::constant(expr.left.as_ref()), Self::constant(expr.right.as_ref()));
+        let both_constant = left_constant.is_some() && right_constant.is_some();
+
+        let type_ = Self::binary_type(&left, left_constant, &right, right_constant, &expr.op, expr.span)?;
+
+        // Constant operands are folded at full width, which also reports an
+        // overflow.
+        if both_constant {
+            return Ok(expr.accept(&mut CompileTimeEvaluator::new())?.get_type());
+        }
+
+        Ok(type_)
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr<'ctx>) -> Result<Type, Diagnostic> {
+        self.expect_bool(expr.left.as_ref())?;
+
+        let diverges = self.diverges;
+        let result = self.expect_bool(expr.right.as_ref());
+        self.diverges = diverges;
+        result?;
+
+        Ok(Type::Literal(LiteralType::Bool))
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let name = &expr.callee;
+
+        if name == "len" && !self.functions.contains_key(name) {
+            return self.check_len(expr);
+        }
+
+        let signature = self.functions.get(name)
+            .ok_or_else(|| Diagnostic::error(format!("cannot find function `{}`", name)).with_span(expr.span))?;
+        let params = signature.params.clone();
+        let return_type = signature.return_type.clone();
+
+        if expr.args.len() != params.len() {
+            return Err(Diagnostic::error(format!("function `{}` takes {} arguments, but {} were supplied", name, params.len(), expr.args.len())).with_span(expr.span));
+        }
+
+        for (arg, param) in expr.args.iter().zip(&params) {
+            self.check_initializer(arg.as_ref(), param)?;
+        }
+
+        Ok(return_type)
+    }
+
+    fn visit_list_expr(&mut self, expr: &ListExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let expected = self.expected_type.take();
+        if let Some(type_) = Self::constant(expr).and_then(|constant| constant.constant_type()) {
+            return Ok(type_);
+        }
+
+        // The elements take the element type required of the list if there
+        // is one, or else the type of the first element computed at run time,
+        // which the constant elements are then converted to.
+        let (element, first) = match expected {
+            Some(Type::List(element, _)) => (*element, None),
+            _ => {
+                let first = expr.values.iter()
+                    .position(|value| Self::constant(value.as_ref()).is_none())
+                    .unwrap_or(0);
+                let value = expr.values.get(first)
+                    .ok_or_else(|| Diagnostic::error("cannot infer the element type of an empty list").with_span(expr.span))?;
+                (value.accept_type(self)?, Some(first))
+            }
+        };
+
+        for (i, value) in expr.values.iter().enumerate() {
+            if Some(i) == first {
+                continue;
+            }
+            self.check_initializer(value.as_ref(), &element).map_err(|diagnostic| match first {
+                Some(first) => diagnostic.with_label(expr.values[first].span(), "the element type is taken from here"),
+                None => diagnostic,
+            })?;
+        }
+
+        Ok(Type::List(Box::new(element), expr.values.len()))
+    }
+
+    fn visit_repeat_expr(&mut self, expr: &RepeatExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let expected = self.expected_type.take();
+        if let Some(type_) = Self::constant(expr).and_then(|constant| constant.constant_type()) {
+            return Ok(type_);
+        }
+
+        let element = match expected {
+            Some(Type::List(element, _)) => {
+                self.check_initializer(expr.value.as_ref(), &element)?;
+                *element
+            }
+            _ => expr.value.accept_type(self)?,
+        };
+
+        Ok(Type::List(Box::new(element), expr.count))
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let type_ = Self::auto_deref(expr.base.as_expr().accept_type(self)?);
+        let index_type = expr.index.accept_type(self)?;
+
+        let element = match type_ {
+            Type::List(element, _) | Type::Slice(element, _) => *element,
+            Type::Error => Type::Error,
+            _ => return Err(Diagnostic::error(format!("cannot index into a value of type `{}`", type_)).with_span(expr.span)),
+        };
+
+        if !matches!(index_type, Type::Literal(LiteralType::Int(_)) | Type::Error) {
+            return Err(Diagnostic::error(format!("list index must be an integer, found `{}`", index_type)).with_span(expr.index.span()));
+        }
+
+        Ok(element)
+    }
+
+    fn visit_slice_expr(&mut self, expr: &SliceExpr<'ctx>) -> Result<Type, Diagnostic> {
+        self.expected_type = None;
+        let type_ = Self::auto_deref(expr.base.as_expr().accept_type(self)?);
+
+        let element = match type_ {
+            Type::List(element, _) | Type::Slice(element, _) => element,
+            Type::Error => return Ok(Type::Error),
+            _ => return Err(Diagnostic::error(format!("cannot slice a value of type `{}`", type_)).with_span(expr.span)),
+        };
+
+        for bound in expr.start.iter().chain(&expr.end) {
+            let bound_type = bound.accept_type(self)?;
+            if !matches!(bound_type, Type::Literal(LiteralType::Int(_)) | Type::Error) {
+                return Err(Diagnostic::error(format!("slice index must be an integer, found `{}`", bound_type)).with_span(bound.span()));
+            }
+        }
+
+        // The elements can only be assigned through the slice if the list it
+        // is taken from allows that.
+        let kind = match self.check_base_mutable(&expr.base, "slice", expr.span) {
+            Ok(()) => PointerKind::Mutable,
+            Err(_) => PointerKind::Shared,
+        };
+
+        Ok(Type::Slice(element, kind))
+    }
+
+    fn visit_field_expr(&mut self, expr: &FieldExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let type_ = Self::auto_deref(expr.base.as_expr().accept_type(self)?);
+
+        let field = match &type_ {
+            Type::Named(name) => self.structs.get(name).and_then(|fields| fields.iter().find(|field| field.name == expr.field)),
+            Type::Error => return Ok(Type::Error),
+            _ => None,
+        };
+
+        field
+            .map(|field| field.type_.clone())
+            .ok_or_else(|| Diagnostic::error(format!("no field `{}` on type `{}`", expr.field, type_)).with_span(expr.span))
+    }
+
+    fn visit_struct_expr(&mut self, expr: &StructExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let fields = self.structs.get(&expr.name)
+            .cloned()
+            .ok_or_else(|| Diagnostic::error(format!("cannot find struct `{}` in this scope", expr.name)).with_span(expr.span))?;
+
+        for (i, init) in expr.fields.iter().enumerate() {
+            let field = fields.iter().find(|field| field.name == init.name)
+                .ok_or_else(|| Diagnostic::error(format!("struct `{}` has no field named `{}`", expr.name, init.name)).with_span(init.span))?;
+
+            if let Some(previous) = expr.fields[..i].iter().find(|previous| previous.name == init.name) {
+                return Err(Diagnostic::error(format!("field `{}` specified more than once", init.name))
+                           .with_span(init.span)
+                           .with_label(previous.span, "first use of the field"));
+            }
+
+            self.check_initializer(init.value.as_ref(), &field.type_)?;
+        }
+
+        let missing = fields.iter()
+            .filter(|field| !expr.fields.iter().any(|init| init.name == field.name))
+            .map(|field| format!("`{}`", field.name))
+            .collect::<Vec<String>>();
+
+        if !missing.is_empty() {
+            return Err(Diagnostic::error(format!("missing {} {} in initializer of `{}`", Self::fields_noun(missing.len()), missing.join(", "), expr.name)).with_span(expr.span));
+        }
+
+        Ok(Type::Named(expr.name.clone()))
+    }
+
+    fn visit_variant_expr(&mut self, expr: &VariantExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let variant = self.variant(&expr.enum_name, &expr.variant, expr.span)?;
+
+        if expr.args.len() != variant.fields.len() {
+            return Err(Diagnostic::error(format!("variant `{}::{}` has {} {}, but {} were supplied", expr.enum_name, expr.variant, variant.fields.len(), Self::fields_noun(variant.fields.len()), expr.args.len())).with_span(expr.span));
+        }
+
+        for (arg, field) in expr.args.iter().zip(&variant.fields) {
+            self.check_initializer(arg.as_ref(), field)?;
+        }
+
+        Ok(Type::Named(expr.enum_name.clone()))
+    }
+
+    fn visit_match_expr(&mut self, expr: &MatchExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let expected_type = self.expected_type.take();
+        let scrutinee = expr.scrutinee.accept_type(self)?;
+        if scrutinee == Type::Error {
+            return Ok(Type::Error);
+        }
+
+        let mut catch_all: Option<Span> = None;
+        let mut covered: Vec<(PatternKey, Span)> = Vec::new();
+        let mut types = Vec::new();
+        let mut has_value = true;
+        let diverges = self.diverges;
+        let mut arms_diverge = true;
+
+        for arm in &expr.arms {
+            let unreachable = |span: Span, label: Span, message: &str| {
+                Diagnostic::error("unreachable pattern").with_span(span).with_label(label, message.to_owned())
+            };
+            if let Some(previous) = catch_all {
+                return Err(unreachable(arm.pattern.span(), previous, "this pattern matches every value"));
+            }
+
+            let (key, bindings) = self.check_pattern(&arm.pattern, &scrutinee)?;
+            match key {
+                Some(key) => {
+                    if let Some((_, previous)) = covered.iter().find(|(covered, _)| *covered == key) {
+                        return Err(unreachable(arm.pattern.span(), *previous, "this pattern matches the same values"));
+                    }
+                    covered.push((key, arm.pattern.span()));
+                }
+                None => catch_all = Some(arm.pattern.span()),
+            }
+
+            self.scopes.push(bindings);
+            self.diverges = diverges;
+            let result = match &arm.body {
+                ArmBody::Expr(body) => {
+                    let constant = Self::constant(body.as_ref());
+                    let type_ = match &constant {
+                        Some(constant) => Ok(constant.constant_type()),
+                        None => body.accept_type(self).map(Some),
+                    };
+                    type_.map(|type_| types.push((type_, constant, body.span())))
+                }
+                ArmBody::Block(body) => {
+                    has_value = false;
+                    self.check_stmt(body.as_ref());
+                    Ok(())
+                }
+            };
+            self.scopes.pop();
+            arms_diverge &= self.diverges;
+            result?;
+        }
+
+        if catch_all.is_none() {
+            self.check_exhaustive(&scrutinee, &covered, expr.span)?;
+        }
+        self.diverges = diverges || arms_diverge;
+
+        if !has_value || types.is_empty() {
+            return Ok(Type::Void);
+        }
+
+        Self::join_branches(&types, expected_type, "`match` arms", expr.span)
+    }
+
+    fn visit_block_expr(&mut self, expr: &BlockExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let expected_type = self.expected_type.take();
+
+        self.scopes.push(HashMap::new());
+        for stmt in &expr.stmts {
+            self.check_stmt(stmt.as_ref());
+        }
+        let result = match (&expr.value, expected_type) {
+            (Some(value), Some(expected_type)) => self.check_initializer(value.as_ref(), &expected_type).map(|_| expected_type),
+            (Some(value), None) => value.accept_type(self),
+            (None, _) => Ok(Type::Void),
+        };
+        self.scopes.pop();
+
+        result
+    }
+
+    fn visit_if_expr(&mut self, expr: &IfExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let expected_type = self.expected_type.take();
+        self.expect_bool(expr.cond.as_ref())?;
+
+        let diverges = self.diverges;
+        let otherwise = match &expr.otherwise {
+            Some(otherwise) => otherwise,
+            None => {
+                let type_ = expr.then.accept_type(self);
+                self.diverges = diverges;
+                let type_ = type_?;
+                if type_ != Type::Void && type_ != Type::Error {
+                    return Err(Diagnostic::error("`if` may be missing an `else` clause")
+                               .with_span(expr.then.span())
+                               .with_note(format!("the `if` has a value of type `{}`, which it cannot have without an `else`", type_)));
+                }
+                return Ok(Type::Void);
+            }
+        };
+
+        // Required to have a type, both branches are checked as initializers
+        // of it. Otherwise they are joined the same way the arms of a `match`
+        // are.
+        if let Some(expected_type) = expected_type {
+            let then = self.check_initializer(expr.then.as_ref(), &expected_type);
+            let then_diverges = std::mem::replace(&mut self.diverges, diverges);
+            let otherwise = self.check_initializer(otherwise.as_ref(), &expected_type);
+            self.diverges = diverges || (then_diverges && self.diverges);
+            otherwise?;
+            return then.map(|_| expected_type);
+        }
+
+        let mut branches = Vec::new();
+        let mut branches_diverge = true;
+        for branch in [&expr.then, otherwise] {
+            self.diverges = diverges;
+            let constant = Self::constant(branch.as_ref());
+            let type_ = match &constant {
+                Some(constant) => constant.constant_type(),
+                None => Some(branch.accept_type(self)?),
+            };
+            branches_diverge &= self.diverges;
+            branches.push((type_, constant, branch.span()));
+        }
+        self.diverges = diverges || branches_diverge;
+
+        Self::join_branches(&branches, None, "`if` and `else`", expr.span)
+    }
+
+    fn visit_loop_expr(&mut self, expr: &LoopExpr<'ctx>) -> Result<Type, Diagnostic> {
+        let expected_type = self.expected_type.take();
+        let breaks = self.check_loop_body(expr.label.clone(), true, expr.body.as_ref());
+
+        // A loop without a `break` never finishes, so it has no value.
+        if breaks.is_empty() {
+            self.diverges = true;
+            return Ok(Type::Void);
+        }
+
+        Self::join_branches(&breaks, expected_type, "`break` values", expr.span)
+    }
+
+    fn visit_address_of_expr(&mut self, expr: &AddressOfExpr<'ctx>) -> Result<Type, Diagnostic> {
+        // The place does not take the type required of its address.
+        self.expected_type = None;
+        let type_ = expr.target.as_expr().accept_type(self)?;
+        if type_ == Type::Error {
+            return Ok(Type::Error);
+        }
+
+        if expr.mutable {
+            self.check_mutable(&expr.target, "mutably borrow")?;
+            Ok(Type::Pointer(Box::new(type_), PointerKind::Mutable))
+        } else {
+            Ok(Type::Pointer(Box::new(type_), PointerKind::Shared))
+        }
+    }
+
+    fn visit_deref_expr(&mut self, expr: &DerefExpr<'ctx>) -> Result<Type, Diagnostic> {
+        self.expected_type = None;
+        match expr.pointer.accept_type(self)? {
+            Type::Pointer(pointee, _) => Ok(*pointee),
+            Type::Error => Ok(Type::Error),
+            type_ => Err(Diagnostic::error(format!("type `{}` cannot be dereferenced", type_)).with_span(expr.span)),
+        }
+    }
+
+    fn visit_cast_expr(&mut self, expr: &CastExpr<'ctx>) -> Result<Type, Diagnostic> {
+        // The operand does not take the type required of the cast.
+        self.expected_type = None;
+        let found = expr.expr.accept_type(self)?;
+        let type_ = &expr.type_;
+        let cannot_cast = || Diagnostic::error(format!("cannot cast `{}` as `{}`", found, type_)).with_span(expr.span);
+
+        match (&found, type_) {
+            (found, type_) if found == type_ || *found == Type::Error => Ok(type_.clone()),
+            (Type::Literal(LiteralType::Int(_) | LiteralType::Char | LiteralType::Bool), Type::Literal(LiteralType::Int(_)))
+            | (Type::Literal(LiteralType::Int(IntType::U8)), Type::Literal(LiteralType::Char))
+            | (Type::Literal(LiteralType::Int(_) | LiteralType::Float(_)), Type::Literal(LiteralType::Int(_) | LiteralType::Float(_))) => Ok(type_.clone()),
+            (Type::Literal(LiteralType::Int(_)), Type::Literal(LiteralType::Char)) => {
+                Err(Diagnostic::error(format!("only `u8` can be cast as `char`, not `{}`", found)).with_span(expr.span))
+            }
+            (Type::Literal(LiteralType::Int(_) | LiteralType::Float(_)), Type::Literal(LiteralType::Bool)) => {
+                Err(cannot_cast().with_note("compare with zero instead, as in `x != 0`"))
+            }
+            (Type::Literal(_), Type::Literal(_)) => Err(cannot_cast()),
+            _ => Err(Diagnostic::error(format!("non-primitive cast: `{}` as `{}`", found, type_))
+                     .with_span(expr.span)
+                     .with_note("`as` only converts between numbers, `bool` and `char`")),
+        }
+    }
+}
+
+impl<'ctx> StmtVisitor<'ctx> for TypeChecker<'ctx> {
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt<'ctx>) -> Result<(), Diagnostic> {
+        stmt.expr.accept_type(self)?;
+        Ok(())
+    }
+
+    fn visit_var_decl_stmt(&mut self, stmt: &VarDeclStmt<'ctx>) -> Result<(), Diagnostic> {
+        if self.return_type.is_none() {
+            return Err(Diagnostic::error("`let` outside of a function")
+                       .with_span(stmt.span)
+                       .with_note("use `static` for a global variable, or `const` for a global constant"));
+        }
+
+        // The variable is declared even if it is wrong, with the error type
+        // when its own type is not known, so that its uses are not reported
+        // as well.
+        let (type_, result) = match &stmt.type_ {
+            Type::Inferred => match stmt.expr.accept_type(self) {
+                Ok(Type::Void) => (Type::Error, Err(Diagnostic::error(format!("cannot declare variable `{}` of type `()`", stmt.name)).with_span(stmt.expr.span()))),
+                Ok(type_) => (type_, Ok(())),
+                Err(diagnostic) => (Type::Error, Err(diagnostic)),
+            },
+            type_ => match self.check_type(type_, stmt.span) {
+                Ok(()) => (type_.clone(), self.check_initializer(stmt.expr.as_ref(), type_)),
+                Err(diagnostic) => (Type::Error, Err(diagnostic)),
+            },
+        };
+
+        self.declare_variable(&stmt.name, type_);
+        result
+    }
+
+    fn visit_const_decl_stmt(&mut self, stmt: &ConstDeclStmt<'ctx>) -> Result<(), Diagnostic> {
+        self.check_global(true, &stmt.name, &stmt.type_, stmt.value.as_ref(), stmt.span)
+    }
+
+    fn visit_static_decl_stmt(&mut self, stmt: &StaticDeclStmt<'ctx>) -> Result<(), Diagnostic> {
+        self.check_global(false, &stmt.name, &stmt.type_, stmt.value.as_ref(), stmt.span)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt<'ctx>) -> Result<(), Diagnostic> {
+        self.diverges = true;
+        let return_type = self.return_type.clone()
+            .ok_or_else(|| Diagnostic::error("`return` outside of a function").with_span(stmt.span))?;
+
+        match (&stmt.expr, return_type) {
+            (None, Type::Void | Type::Error) => Ok(()),
+            (None, return_type) => {
+                Err(Diagnostic::error(format!("mismatched types: expected `{}`, found `()`", return_type))
+                    .with_span(stmt.span)
+                    .with_note("`return;` can only leave a function without a return value"))
+            }
+            (Some(expr), Type::Void) => {
+                let found = expr.accept_type(self)?;
+                if found == Type::Error {
+                    return Ok(());
+                }
+                Err(Diagnostic::error(format!("mismatched types: expected `()`, found `{}`", found))
+                    .with_span(expr.span())
+                    .with_note("a function without `-> T` after its parameters returns nothing, so it is left with `return;`"))
+            }
+            (Some(expr), return_type) => self.check_initializer(expr.as_ref(), &return_type),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt<'ctx>) -> Result<(), Diagnostic> {
+        self.scopes.push(HashMap::new());
+        for stmt in &stmt.stmts {
+            self.check_stmt(stmt.as_ref());
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt<'ctx>) -> Result<(), Diagnostic> {
+        let result = self.expect_bool(stmt.cond.as_ref());
+
+        let diverges = self.diverges;
+        self.check_stmt(stmt.then.as_ref());
+        let then_diverges = std::mem::replace(&mut self.diverges, diverges);
+        if let Some(otherwise) = &stmt.otherwise {
+            self.check_stmt(otherwise.as_ref());
+            self.diverges = diverges || (then_diverges && self.diverges);
+        }
+
+        result
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt<'ctx>) -> Result<(), Diagnostic> {
+        let result = self.expect_bool(stmt.cond.as_ref());
+
+        self.check_loop_body(stmt.label.clone(), false, stmt.body.as_ref());
+
+        result
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &ForStmt<'ctx>) -> Result<(), Diagnostic> {
+        let element = match &stmt.iterable {
+            ForIterable::Range(start, end) => {
+                let start_type = start.accept_type(self)?;
+                let end_type = end.accept_type(self)?;
+
+                match (start_type.numeric(), end_type.numeric()) {
+                    _ if start_type == Type::Error || end_type == Type::Error => Type::Error,
+                    (Some(LiteralType::Int(start_int)), Some(LiteralType::Int(end_int))) => {
+                        let span = start.span().to(end.span());
+                        let op = Token::new(TokenKind::DotDot, span);
+                        let int_type = Self::unify_ints(start_int, &Self::constant(start.as_ref()), end_int, &Self::constant(end.as_ref()), &op, span)?;
+                        Type::Literal(LiteralType::Int(int_type))
+                    }
+                    (Some(LiteralType::Int(_)), _) => {
+                        return Err(Diagnostic::error(format!("mismatched types: expected an integer, found `{}`", end_type)).with_span(end.span()));
+                    }
+                    _ => {
+                        return Err(Diagnostic::error(format!("mismatched types: expected an integer, found `{}`", start_type)).with_span(start.span()));
+                    }
+                }
+            }
+            ForIterable::List(iterable) => match iterable.accept_type(self)? {
+                Type::List(element, _) | Type::Slice(element, _) => *element,
+                Type::Error => Type::Error,
+                type_ => {
+                    return Err(Diagnostic::error(format!("`{}` is not iterable", type_))
+                               .with_span(iterable.span())
+                               .with_note("a `for` loop walks over a range such as `0..n`, a list or a slice"));
+                }
+            },
+        };
+
+        self.scopes.push(HashMap::new());
+        self.declare_variable(&stmt.name, element);
+
+        self.check_loop_body(stmt.label.clone(), false, stmt.body.as_ref());
+
+        self.scopes.pop();
+
+        Ok(())
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &BreakStmt<'ctx>) -> Result<(), Diagnostic> {
+        self.diverges = true;
+        let index = self.target_loop(&stmt.label, "break", stmt.span)?;
+
+        let break_value = match &stmt.value {
+            Some(value) => {
+                if !self.loops[index].is_loop {
+                    return Err(Diagnostic::error("`break` with a value can only leave a `loop`").with_span(value.span()));
+                }
+
+                let constant = Self::constant(value.as_ref());
+                let type_ = match &constant {
+                    Some(constant) => constant.constant_type(),
+                    None => Some(value.accept_type(self)?),
+                };
+                (type_, constant, value.span())
+            }
+            None => (Some(Type::Void), None, stmt.span),
+        };
+        self.loops[index].breaks.push(break_value);
+
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &ContinueStmt) -> Result<(), Diagnostic> {
+        self.diverges = true;
+        self.target_loop(&stmt.label, "continue", stmt.span)?;
+        Ok(())
+    }
+
+    fn visit_function_decl_stmt(&mut self, stmt: &FunctionDeclStmt) -> Result<(), Diagnostic> {
+        if self.functions.contains_key(&stmt.name) {
+            return Err(Diagnostic::error(format!("function `{}` is defined more than once", stmt.name)).with_span(stmt.span));
+        }
+
+        // The function is declared even if its signature is wrong, so that
+        // its calls are not reported as well.
+        let mut error = None;
+        let params = stmt.params.iter()
+            .map(|param| self.param_type(param).unwrap_or_else(|diagnostic| {
+                error.get_or_insert(diagnostic);
+                Type::Error
+            }))
+            .collect();
+        let return_type = self.return_type_of(stmt).unwrap_or_else(|diagnostic| {
+            error.get_or_insert(diagnostic);
+            Type::Error
+        });
+
+        self.functions.insert(stmt.name.clone(), FunctionSignature { params, return_type });
+
+        error.map_or(Ok(()), Err)
+    }
+
+    fn visit_function_def_stmt(&mut self, stmt: &FunctionDefStmt<'ctx>) -> Result<(), Diagnostic> {
+        let declared = stmt.func_decl.accept(self);
+
+        // The body is checked even if the signature is wrong, with the error
+        // type for what is.
+        let params = stmt.func_decl.params.iter()
+            .map(|param| (param.name.clone(), self.param_type(param).unwrap_or(Type::Error)))
+            .collect();
+
+        let return_type = self.return_type_of(&stmt.func_decl).unwrap_or(Type::Error);
+        self.scopes.push(params);
+        self.return_type = Some(return_type.clone());
+        self.diverges = false;
+        let result = stmt.body.accept(self);
+        self.return_type = None;
+        self.scopes.pop();
+
+        // Only a function without a return value can run off the end of its
+        // body.
+        let result = result.and_then(|_| match return_type {
+            Type::Void | Type::Error => Ok(()),
+            _ if self.diverges => Ok(()),
+            _ => Err(Diagnostic::error(format!("mismatched types: expected `{}`, found `()`", return_type))
+                     .with_span(stmt.func_decl.span)
+                     .with_note(format!("the body of `{}` can reach its end without returning a value", stmt.func_decl.name))),
+        });
+
+        declared.and(result)
+    }
+
+    fn visit_struct_decl_stmt(&mut self, stmt: &StructDeclStmt) -> Result<(), Diagnostic> {
+        self.check_type_name(&stmt.name, stmt.span)?;
+
+        for (i, field) in stmt.fields.iter().enumerate() {
+            if let Some(previous) = stmt.fields[..i].iter().find(|previous| previous.name == field.name) {
+                return Err(Diagnostic::error(format!("field `{}` is already declared", field.name))
+                           .with_span(field.span)
+                           .with_label(previous.span, "first declared here"));
+            }
+            if let Type::Void | Type::Inferred = field.type_ {
+                return Err(Diagnostic::error(format!("field `{}` cannot have type `{}`", field.name, field.type_)).with_span(field.span));
+            }
+            // The struct is only declared once its fields are checked, so a
+            // struct cannot contain itself.
+            self.check_type(&field.type_, field.span)?;
+        }
+
+        self.structs.insert(stmt.name.clone(), stmt.fields.clone());
+        Ok(())
+    }
+
+    fn visit_enum_decl_stmt(&mut self, stmt: &EnumDeclStmt) -> Result<(), Diagnostic> {
+        self.check_type_name(&stmt.name, stmt.span)?;
+
+        for (i, variant) in stmt.variants.iter().enumerate() {
+            if let Some(previous) = stmt.variants[..i].iter().find(|previous| previous.name == variant.name) {
+                return Err(Diagnostic::error(format!("variant `{}` is already declared", variant.name))
+                           .with_span(variant.span)
+                           .with_label(previous.span, "first declared here"));
+            }
+            for field in &variant.fields {
+                if let Type::Void | Type::Inferred = field {
+                    return Err(Diagnostic::error(format!("variant `{}` cannot have a field of type `{}`", variant.name, field)).with_span(variant.span));
+                }
+                self.check_type(field, variant.span)?;
+            }
+        }
+
+        self.enums.insert(stmt.name.clone(), stmt.variants.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{lexer::Lexer, parser::Parser};
+
+    use super::TypeChecker;
+
+    /// The messages of the diagnostics the type checker reports for `source`,
+    /// which has to parse.
+    fn check(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(Lexer::new(source.to_string(), 0));
+        let (stmts, diagnostics) = parser.parse();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>());
+        match TypeChecker::new().check(&stmts) {
+            Ok(()) => vec![],
+            Err(diagnostics) => diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect(),
+        }
+    }
+
+    /// Checks `body` as the body of a `main` returning `i32`.
+    fn check_main(body: &str) -> Vec<String> {
+        check(&format!("fn main() -> i32 {{ {} }}", body))
+    }
+
+    #[test]
+    fn accepts_a_valid_program() {
+        let source = "
+            fn main() -> i32 {
+                let p = Point { x: 1, y: 2 };
+                let s = Shape::Circle(3);
+                let r = match s {
+                    Shape::Dot => 0,
+                    Shape::Circle(r) => r,
+                };
+                return p.x + r;
+            }
+            struct Point { x: i32, y: i32 }
+            enum Shape { Dot, Circle(i32) }";
+        assert_eq!(check(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_every_type_error() {
+        assert_eq!(check_main("let a: i32 = true; let b: bool = 1; return 0;"), [
+            "mismatched types: expected `i32`, found `bool`",
+            "mismatched types: expected `bool`, found integer",
+        ]);
+    }
+
+    #[test]
+    fn defaults_untyped_integers_to_i32() {
+        assert_eq!(check_main("let a = 1; let b: i64 = 2; return a;"), Vec::<String>::new());
+        assert_eq!(check_main("let a = 3000000000; return a;"), ["mismatched types: expected `i32`, found `i64`"]);
+    }
+
+    #[test]
+    fn reports_literals_out_of_range() {
+        assert_eq!(check_main("let a: u8 = 256; return 0;"), ["literal out of range for `u8`"]);
+        assert_eq!(check_main("let a: i8 = -129; return 0;"), ["literal out of range for `i8`"]);
+        assert_eq!(check_main("let a: f32 = 1e39; return 0;"), ["literal out of range for `f32`"]);
+    }
+
+    #[test]
+    fn reports_overflow_of_constants() {
+        assert_eq!(check("const A: u8 = 200 as u8 + 100;"), ["this arithmetic operation will overflow"]);
+        assert_eq!(check("const A: u64 = 18446744073709551615 * 2;"), ["this arithmetic operation will overflow"]);
+        assert_eq!(check("const A: i64 = 1 << 200;"), ["this arithmetic operation will overflow"]);
+    }
+
+    #[test]
+    fn reports_integer_division_by_zero() {
+        assert_eq!(check_main("let a = 1; return a / 0;"), ["division by zero"]);
+        assert_eq!(check_main("let a = 1; return a % 0;"), ["division by zero"]);
+        assert_eq!(check("const A: i32 = 1 / 0;"), ["division by zero"]);
+    }
+
+    #[test]
+    fn accepts_float_division_by_zero() {
+        assert_eq!(check_main("let a = 1.0 / 0.0; let b: f64 = a % 0.0; return 0;"), Vec::<String>::new());
+        assert_eq!(check("const A: f64 = 1.0 / 0.0;"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unifies_signed_and_unsigned_operands() {
+        assert_eq!(check_main("let a: u8 = 1; let b: u8 = a + 255; return 0;"), Vec::<String>::new());
+        assert_eq!(check_main("let a: u8 = 1; let b: i8 = 1; let c = a + b; return 0;"), [
+            "mismatched types: cannot apply `+` to `u8` and `i8`",
+        ]);
+        assert_eq!(check_main("let a: u8 = 1; return -a;"), ["cannot apply unary `-` to type `u8`"]);
+    }
+
+    #[test]
+    fn reports_operators_applied_to_the_wrong_types() {
+        assert_eq!(check_main("let a = true + false; return 0;"), ["cannot apply `+` to `bool`"]);
+        assert_eq!(check_main("let a = 1.0 & 2.0; return 0;"), ["cannot apply `&` to floating-point operands"]);
+        assert_eq!(check_main("let a = ~1.0; return 0;"), ["cannot apply unary `~` to a floating-point operand"]);
+        assert_eq!(check_main("let a = -true; return 0;"), ["cannot apply unary `-` to `bool`"]);
+        assert_eq!(check_main("let a = 1 && true; return 0;"), ["mismatched types: expected `bool`, found `i32`"]);
+    }
+
+    #[test]
+    fn reports_conditions_that_are_not_bool() {
+        assert_eq!(check_main("if 1 { return 1; } return 0;"), ["mismatched types: expected `bool`, found `i32`"]);
+        assert_eq!(check_main("while 1.0 { } return 0;"), ["mismatched types: expected `bool`, found `f64`"]);
+    }
+
+    #[test]
+    fn reports_an_if_expression_without_else() {
+        assert_eq!(check_main("let a = if true { 1 }; return a;"), ["`if` may be missing an `else` clause"]);
+        assert_eq!(check_main("let a = if true { 1 } else { false }; return 0;"), ["`if` and `else` have incompatible types"]);
+    }
+
+    #[test]
+    fn checks_returns() {
+        assert_eq!(check("fn f() -> i32 { return; }"), ["mismatched types: expected `i32`, found `()`"]);
+        assert_eq!(check("fn f() { return 1; }"), ["mismatched types: expected `()`, found `i32`"]);
+        assert_eq!(check("fn f() { return; }"), Vec::<String>::new());
+        assert_eq!(check("fn f(x: i32) -> i32 { if x > 0 { return 1; } }"), [
+            "mismatched types: expected `i32`, found `()`",
+        ]);
+        assert_eq!(check("fn f(x: i32) -> i32 { loop { if x > 0 { return 1; } } }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_statements_outside_of_functions() {
+        assert_eq!(check("fn f() { const A: i32 = 1; }"), ["`const` items can only be declared at the top level"]);
+        assert_eq!(check("fn f() { break; }"), ["`break` outside of a loop"]);
+        assert_eq!(check("fn f() { continue; }"), ["`continue` outside of a loop"]);
+    }
+
+    #[test]
+    fn checks_labels() {
+        assert_eq!(check("fn f() { 'a: while true { loop { break 'a; } } }"), Vec::<String>::new());
+        assert_eq!(check("fn f() { while true { break 'a; } }"), ["use of undeclared label `'a`"]);
+        assert_eq!(check("fn f() { 'a: loop { while true { continue 'b; } } }"), ["use of undeclared label `'b`"]);
+    }
+
+    #[test]
+    fn checks_breaks_with_a_value() {
+        assert_eq!(check_main("let a = loop { break 1; }; return a;"), Vec::<String>::new());
+        assert_eq!(check_main("while true { break 1; } return 0;"), ["`break` with a value can only leave a `loop`"]);
+        assert_eq!(check_main("let a = loop { if true { break 1; } break true; }; return 0;"), ["`break` values have incompatible types"]);
+    }
+
+    #[test]
+    fn checks_matches() {
+        let source = "
+            enum E { A, B(i32), C }
+            fn f(e: E) -> i32 {
+                return match e {
+                    E::A => 1,
+                    E::B(n) => n,
+                };
+            }";
+        assert_eq!(check(source), ["non-exhaustive patterns: `E::C` not covered"]);
+        assert_eq!(check_main("let x = 1; return match x { 1 => 1, 2 => 2 };"), [
+            "non-exhaustive patterns: `_` not covered",
+        ]);
+        assert_eq!(check_main("let b = true; return match b { true => 1 };"), [
+            "non-exhaustive patterns: `false` not covered",
+        ]);
+        assert_eq!(check_main("let x = 1; return match x { _ => 1, 2 => 2 };"), ["unreachable pattern"]);
+        assert_eq!(check_main("let x = 1.0; return match x { 1.0 => 1, _ => 2 };"), [
+            "floating-point literals cannot be used in patterns",
+        ]);
+        assert_eq!(check_main("let x = true; return match x { 1 => 1, _ => 2 };"), ["mismatched types: expected `bool`, found integer"]);
+    }
+
+    #[test]
+    fn checks_enum_patterns() {
+        let check_arm = |arm: &str| check(&format!("
+            enum E {{ A, B(i32, i32) }}
+            enum F {{ X }}
+            fn f(e: E) -> i32 {{
+                return match e {{
+                    {},
+                    _ => 0,
+                }};
+            }}", arm));
+        assert_eq!(check_arm("E::B(a) => a"), ["this pattern has 1 field, but variant `E::B` has 2"]);
+        assert_eq!(check_arm("E::B(a, a) => a"), ["identifier `a` is bound more than once in the same pattern"]);
+        assert_eq!(check_arm("E::D => 1"), ["no variant named `D` in enum `E`"]);
+        assert_eq!(check_arm("F::X => 2"), ["mismatched types: expected `E`, found `F`"]);
+    }
+
+    #[test]
+    fn checks_casts() {
+        assert_eq!(check_main("let a = 1.5 as i32; let b = true as u8; let c = 65 as u8 as char; return a;"), Vec::<String>::new());
+        assert_eq!(check_main("let a = 1 as bool; return 0;"), ["cannot cast `i32` as `bool`"]);
+        assert_eq!(check_main("let a = 65 as char; return 0;"), ["only `u8` can be cast as `char`, not `i32`"]);
+        assert_eq!(check_main("let a = [1] as i32; return 0;"), ["non-primitive cast: `[i32; 1]` as `i32`"]);
+    }
+
+    #[test]
+    fn checks_assignments() {
+        assert_eq!(check("const A: i32 = 1; fn f() { A = 2; }"), ["cannot assign to constant `A`"]);
+        assert_eq!(check("struct P { x: i32 } fn g() -> P { return P { x: 1 }; } fn f() { g().x = 2; }"), ["cannot assign to a temporary value"]);
+        assert_eq!(check("fn f(p: &i32) { *p = 2; }"), ["cannot assign to data behind a `&` reference"]);
+        assert_eq!(check("fn f(p: &mut i32) { *p = 2; }"), Vec::<String>::new());
+        assert_eq!(check("fn f(xs: [i32; 2]) { xs[0..1] = xs; }"), ["cannot assign to a range of a list"]);
+    }
+
+    #[test]
+    fn checks_slices() {
+        assert_eq!(check("fn f(xs: [i32]) { xs[0] = 1; }"), Vec::<String>::new());
+        assert_eq!(check("fn f(xs: &[i32]) { xs[0] = 1; }"), ["cannot assign to data behind a `&[T]` slice"]);
+        assert_eq!(check("const A: [i32; 2] = [1, 2]; fn f() { let s: [i32] = A[..]; }"), [
+            "mismatched types: expected `[i32]`, found `&[i32]`",
+        ]);
+        assert_eq!(check("fn g(xs: &[i32]) {} fn f(xs: [i32]) { g(xs); }"), Vec::<String>::new());
+        assert_eq!(check("fn f(xs: [i32]) { let s = xs[true..]; }"), ["slice index must be an integer, found `bool`"]);
+        assert_eq!(check("fn f() { let x = 1; let s = x[..]; }"), ["cannot slice a value of type `i32`"]);
+    }
+
+    #[test]
+    fn checks_indexing() {
+        assert_eq!(check("fn f(xs: [i32; 2]) -> i32 { return xs[1.0]; }"), ["list index must be an integer, found `f64`"]);
+        assert_eq!(check("fn f(x: i32) -> i32 { return x[0]; }"), ["cannot index into a value of type `i32`"]);
+    }
+
+    #[test]
+    fn checks_len() {
+        assert_eq!(check("fn f(xs: [i32], s: str) -> u64 { return len(xs) + len(s) + len([1, 2]); }"), Vec::<String>::new());
+        assert_eq!(check("fn f() -> u64 { return len(1); }"), ["`i32` has no length"]);
+        assert_eq!(check("fn f(xs: [i32]) -> u64 { return len(xs, xs); }"), ["function `len` takes 1 argument, but 2 were supplied"]);
+    }
+
+    #[test]
+    fn reports_unknown_names() {
+        assert_eq!(check_main("return a;"), ["cannot find variable `a` in this scope"]);
+        assert_eq!(check_main("return f();"), ["cannot find function `f`"]);
+        assert_eq!(check("fn f(p: P) {}"), ["cannot find type `P` in this scope"]);
+        assert_eq!(check_main("let p = P { x: 1 }; return 0;"), ["cannot find struct `P` in this scope"]);
+    }
+
+    #[test]
+    fn reports_duplicate_declarations() {
+        assert_eq!(check("fn f() {} fn f() {}"), ["function `f` is defined more than once"]);
+        assert_eq!(check("struct P { x: i32 } enum P { A }"), ["the type `P` is defined more than once"]);
+        assert_eq!(check("struct P { x: i32, x: i32 }"), ["field `x` is already declared"]);
+        assert_eq!(check("enum E { A, A }"), ["variant `A` is already declared"]);
+    }
+
+    #[test]
+    fn checks_struct_literals() {
+        let source = "
+            struct P { x: i32, y: i32 }
+            fn f() -> i32 {
+                let a = P { x: 1, y: 2, z: 3 };
+                let b = P { x: 1, x: 2, y: 3 };
+                let c = P { x: 1 };
+                return 0;
+            }";
+        assert_eq!(check(source), [
+            "struct `P` has no field named `z`",
+            "field `x` specified more than once",
+            "missing field `y` in initializer of `P`",
+        ]);
+        assert_eq!(check("struct P { x: i32 } fn f(p: P) -> i32 { return p.w; }"), ["no field `w` on type `P`"]);
+    }
+
+    #[test]
+    fn checks_variant_constructors() {
+        assert_eq!(check("enum E { A(i32) } fn f() { let e = E::A(1, 2); }"), ["variant `E::A` has 1 field, but 2 were supplied"]);
+        assert_eq!(check("enum E { A } fn f() { let e = E::B; }"), ["no variant named `B` in enum `E`"]);
+        assert_eq!(check("fn f() { let e = G::A; }"), ["cannot find enum `G` in this scope"]);
+    }
+
+    #[test]
+    fn checks_calls() {
+        assert_eq!(check("fn g(a: i32) {} fn f() { g(1, 2); }"), ["function `g` takes 1 arguments, but 2 were supplied"]);
+        assert_eq!(check("fn g(a: u8) {} fn f() { g(true); }"), ["mismatched types: expected `u8`, found `bool`"]);
+    }
+
+    #[test]
+    fn checks_lists() {
+        assert_eq!(check_main("let a = []; return 0;"), ["cannot infer the element type of an empty list"]);
+        assert_eq!(check_main("let a: [i32; 0] = []; return 0;"), Vec::<String>::new());
+        assert_eq!(check_main("let a = [1, true]; return 0;"), ["mismatched types: expected `i32`, found `bool`"]);
+    }
+
+    #[test]
+    fn checks_for_loops() {
+        assert_eq!(check("fn f() { for i in 0..true {} }"), ["mismatched types: expected an integer, found `bool`"]);
+        assert_eq!(check("fn f() { for i in 1 {} }"), ["`i32` is not iterable"]);
+    }
+
+    #[test]
+    fn checks_pointers() {
+        assert_eq!(check("fn f(x: i32) -> i32 { return *x; }"), ["type `i32` cannot be dereferenced"]);
+        assert_eq!(check("fn f(p: &i32) -> &i32 { return p; }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn checks_constants() {
+        assert_eq!(check("fn g() -> i32 { return 1; } const A: i32 = g();"), ["the value of `A` cannot be evaluated at compile time"]);
+        assert_eq!(check("static B: i32 = 1; const A: i32 = B;"), ["the value of `A` cannot be evaluated at compile time"]);
+    }
+}